@@ -0,0 +1,29 @@
+/* generated by cbindgen from src/ffi.rs, do not edit */
+
+#ifndef AMINUS_H
+#define AMINUS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+uint8_t *aminus_alloc(size_t len);
+
+void aminus_dealloc(uint8_t *ptr, size_t len);
+
+void aminus_string_free(char *ptr);
+
+const char *aminus_version(void);
+
+char *aminus_calculate(const char *request);
+
+char *aminus_optimize(const char *request);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* AMINUS_H */
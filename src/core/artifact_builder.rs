@@ -2,6 +2,7 @@ use crate::functions::stat_factory::StatFactory;
 use crate::core::stattable::StatTable;
 use crate::core::types::Stat;
 use crate::core::artifact::*;
+use crate::core::calc_standard::CalcStandard;
 use crate::assert_aprx;
 
 /// Builder pattern for making artifact stattables
@@ -51,6 +52,12 @@ impl ArtifactBuilder{
     /// 4-star artifacts have a x0.8 substat value modifer compared to 5-stars and penalty of -2 distributed substats per 4-star artifact
     /// 1 5-star and 4 4-star means the 5 star artifact will have a stat modifer of (1 * 1 + 0.8 * 4) / 5 = 0.84x rather than 1 
     pub fn kqmc(flower: Option<ArtifactPiece>, feather: Option<ArtifactPiece>, sands: Option<ArtifactPiece>, goblet: Option<ArtifactPiece>, circlet: Option<ArtifactPiece>) -> Self {
+        ArtifactBuilder::with_standard(flower, feather, sands, goblet, circlet, &CalcStandard::KQMC)
+    }
+
+    /// constructs artifact builder for the substat assumptions of a calc standard
+    /// every substat type gets the standard's fixed rolls, fluid rolls are capped per piece and reduced by a penalty per piece
+    pub fn with_standard(flower: Option<ArtifactPiece>, feather: Option<ArtifactPiece>, sands: Option<ArtifactPiece>, goblet: Option<ArtifactPiece>, circlet: Option<ArtifactPiece>, standard: &CalcStandard) -> Self {
         //invariant checks
         assert!(flower.as_ref().map(|x| x.stat_type == Stat::FlatHP).unwrap_or(true));
         assert!(feather.as_ref().map(|x| x.stat_type == Stat::FlatATK).unwrap_or(true));
@@ -85,7 +92,7 @@ impl ArtifactBuilder{
                 .filter(|piece| piece.stat_type != stat)
                 .for_each(|piece| {
                     let key = (stat, piece.rarity);
-                    let new_value = constraints.get(&key).unwrap_or(&0)+2; //only 2 rolls for each substat per artifact
+                    let new_value = constraints.get(&key).unwrap_or(&0)+standard.fluid_rolls_per_substat_per_piece; //limited rolls for each substat per artifact
                     constraints.insert(key, new_value);
                 });
         }
@@ -98,7 +105,7 @@ impl ArtifactBuilder{
 
         let penalty = [&flower, &feather, &sands, &goblet, &circlet].iter()
             .filter_map(|piece| piece.as_ref())
-            .count() as i8;
+            .count() as i8 * standard.roll_penalty_per_piece;
 
         let roll_limit = base - penalty;
 
@@ -110,10 +117,10 @@ impl ArtifactBuilder{
 
         POSSIBLE_SUB_STATS.iter()
             .for_each(|&stat| {
-                bob.roll(stat, RollQuality::AVG, roll_rarity, 2);
-                //increase constraint for each stat by 2
+                //increase constraint for each stat by the fixed rolls
                 let old_constraint = bob.constraints.get(&(stat, roll_rarity)).unwrap_or(&0);
-                bob.constraints.insert((stat, roll_rarity), old_constraint + 2);
+                bob.constraints.insert((stat, roll_rarity), old_constraint + standard.fixed_rolls_per_substat);
+                bob.roll(stat, standard.roll_quality.clone(), roll_rarity, standard.fixed_rolls_per_substat);
             });
         bob
     }
@@ -121,12 +128,18 @@ impl ArtifactBuilder{
 
     /// constructs artifact builder for kqmc assumptions with all 5 star artifacts
     pub fn kqm_all_5_star(sands_main: Stat, goblet_main: Stat, circlet_main: Stat) -> Self {
-       ArtifactBuilder::kqmc(
+        ArtifactBuilder::all_5_star_with_standard(sands_main, goblet_main, circlet_main, &CalcStandard::KQMC)
+    }
+
+    /// constructs artifact builder for the assumptions of a calc standard with all 5 star artifacts
+    pub fn all_5_star_with_standard(sands_main: Stat, goblet_main: Stat, circlet_main: Stat, standard: &CalcStandard) -> Self {
+       ArtifactBuilder::with_standard(
         Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatHP}),
         Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatATK}),
         Some(ArtifactPiece{rarity:5, level:20, stat_type: sands_main}),
         Some(ArtifactPiece{rarity:5, level:20, stat_type: goblet_main}),
         Some(ArtifactPiece{rarity:5, level:20, stat_type: circlet_main}),
+        standard,
         )
     }
 
@@ -317,7 +330,7 @@ pub struct ArtifactPiece {
 
 
 //type ArtifactPiece = (f32, f32, Stat);
-#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, serde::Serialize, serde::Deserialize)]
 pub enum RollQuality{
     MAX,
     HIGH,
@@ -0,0 +1,365 @@
+use crate::core::types::{Amplifier, Element};
+use anyhow::{Result, anyhow};
+
+/// standard internal cooldown: an icd group applies its element on the first hit and every 3rd hit after,
+/// or on the next hit once 2.5s have passed since the window started
+pub const ICD_DURATION: f32 = 2.5;
+pub const ICD_HITS: u32 = 3;
+
+/// element an action applies to the enemy
+/// eg: 1U, 2U or 4U of pyro in the "normal attack" icd group
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementApplication {
+    pub element: Element,
+    pub units: f32,
+    pub icd_group: Option<String>, // none means the action has no icd and always applies
+}
+
+impl ElementApplication {
+    pub fn new(element: Element, units: f32) -> Self {
+        Self { element, units, icd_group: None }
+    }
+
+    pub fn with_icd(mut self, group: &str) -> Self {
+        self.icd_group = Some(String::from(group));
+        self
+    }
+}
+
+/// reaction triggered by an element application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Reaction {
+    None,
+    Vaporize,
+    ReverseVaporize,
+    Melt,
+    ReverseMelt,
+    Aggravate,
+    Spread,
+    Quicken,
+    Burning,
+    Overloaded,
+    Superconduct,
+    ElectroCharged,
+    Frozen,
+    Swirl,
+    Crystallize,
+    Bloom,
+}
+
+impl Reaction {
+    /// amplifier the reaction applies to the triggering hit
+    pub fn amplifier(&self) -> Amplifier {
+        match self {
+            Reaction::Vaporize | Reaction::Melt => Amplifier::Forward,
+            Reaction::ReverseVaporize | Reaction::ReverseMelt => Amplifier::Reverse,
+            _ => Amplifier::None,
+        }
+    }
+
+    /// the reaction as triggered by a hit of an element
+    /// melt and vaporize take their forward or reverse direction from the trigger (eg: pyro on hydro is reverse vaporize)
+    /// errors if the element cannot trigger the reaction (eg: electro melt)
+    pub fn triggered_by(self, element: Element) -> Result<Reaction> {
+        let reaction = match (self, element) {
+            (Reaction::Vaporize | Reaction::ReverseVaporize, Element::Hydro) => Reaction::Vaporize,
+            (Reaction::Vaporize | Reaction::ReverseVaporize, Element::Pyro) => Reaction::ReverseVaporize,
+            (Reaction::Melt | Reaction::ReverseMelt, Element::Pyro) => Reaction::Melt,
+            (Reaction::Melt | Reaction::ReverseMelt, Element::Cryo) => Reaction::ReverseMelt,
+            (Reaction::None, _)
+            | (Reaction::Aggravate, Element::Electro)
+            | (Reaction::Spread, Element::Dendro)
+            | (Reaction::Quicken, Element::Electro | Element::Dendro)
+            | (Reaction::Burning, Element::Pyro | Element::Dendro)
+            | (Reaction::Overloaded, Element::Pyro | Element::Electro)
+            | (Reaction::Superconduct, Element::Cryo | Element::Electro)
+            | (Reaction::ElectroCharged, Element::Hydro | Element::Electro)
+            | (Reaction::Frozen, Element::Hydro | Element::Cryo)
+            | (Reaction::Bloom, Element::Hydro | Element::Dendro)
+            | (Reaction::Swirl, Element::Anemo)
+            | (Reaction::Crystallize, Element::Geo) => self,
+            _ => return Err(anyhow!("{:?} cannot be triggered by a {:?} hit", self, element)),
+        };
+        Ok(reaction)
+    }
+
+    /// base damage multiplier of catalyze reactions, added to the triggering hit
+    pub fn catalyze_multiplier(&self) -> Option<f32> {
+        match self {
+            Reaction::Aggravate => Some(1.15),
+            Reaction::Spread => Some(1.25),
+            _ => None,
+        }
+    }
+}
+
+/// tracks icd groups over a rotation
+#[derive(Debug, Clone, Default)]
+pub struct IcdTracker {
+    groups: std::collections::HashMap<String, (f32, u32)>, // group : window start, hits in window
+}
+
+impl IcdTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// returns true if a hit of an icd group at a given time applies its element
+    pub fn applies(&mut self, group: Option<&str>, time: f32) -> bool {
+        let Some(group) = group else {
+            return true;
+        };
+        let (start, hits) = self.groups.entry(String::from(group)).or_insert((time, 0));
+        if *hits == 0 || time - *start >= ICD_DURATION {
+            *start = time;
+            *hits = 1;
+            return true;
+        }
+        let applies = *hits % ICD_HITS == 0;
+        *hits += 1;
+        applies
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Gauge {
+    units: f32,
+    decay_rate: f32, // units per second
+}
+
+impl Gauge {
+    /// aura left by an application, 0.8x of the applied units that decays over 2.5s per unit + 7s
+    fn aura(units: f32) -> Self {
+        Self { units: 0.8 * units, decay_rate: 0.8 * units / (2.5 * units + 7.0) }
+    }
+
+    /// quicken aura, decays over 5s per unit + 6s
+    fn quicken(units: f32) -> Self {
+        Self { units, decay_rate: units / (5.0 * units + 6.0) }
+    }
+}
+
+/// elemental aura on an enemy
+/// auras coexist for burning (pyro and dendro) and quicken (with any other aura)
+/// quicken reacts like a dendro aura with hydro (bloom) and pyro (burning)
+#[derive(Debug, Clone, Default)]
+pub struct Aura {
+    gauges: Vec<(Element, Gauge)>,
+    quicken: Option<Gauge>,
+    time: f32,
+}
+
+impl Aura {
+    /// enemy without any aura
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// enemy with an aura applied at time 0 (eg: from an enemy attack or a previous rotation)
+    pub fn with(element: Element, units: f32) -> Self {
+        let mut aura = Self::new();
+        aura.apply(0.0, element, units);
+        aura
+    }
+
+    /// units of an element's aura left
+    pub fn gauge(&self, element: Element) -> f32 {
+        self.gauges.iter().find(|(e, _)| *e == element).map(|(_, g)| g.units).unwrap_or(0.0)
+    }
+
+    /// units of quicken aura left
+    pub fn quicken_gauge(&self) -> f32 {
+        self.quicken.map(|g| g.units).unwrap_or(0.0)
+    }
+
+    /// decays every aura up to a time
+    pub fn advance(&mut self, time: f32) {
+        let dt = (time - self.time).max(0.0);
+        self.time = self.time.max(time);
+        for (_, gauge) in self.gauges.iter_mut() {
+            gauge.units -= gauge.decay_rate * dt;
+        }
+        self.gauges.retain(|(_, g)| g.units > 0.0);
+        if let Some(quicken) = self.quicken.as_mut() {
+            quicken.units -= quicken.decay_rate * dt;
+        }
+        self.quicken = self.quicken.filter(|g| g.units > 0.0);
+    }
+
+    /// applies an element at a time, returns the reaction it triggers
+    /// a trigger consumes the reacting aura by its units times the reaction's consumption ratio
+    /// (eg: 2x for forward melt and vaporize, 0.5x for reverse)
+    pub fn apply(&mut self, time: f32, element: Element, units: f32) -> Reaction {
+        self.advance(time);
+
+        if let Some(quicken) = self.quicken.as_mut() {
+            let reaction = match element {
+                Element::Electro => Reaction::Aggravate,
+                Element::Dendro => Reaction::Spread,
+                _ => Reaction::None,
+            };
+            if reaction != Reaction::None {
+                // refreshes the gauge but keeps its decay rate
+                quicken.units = quicken.units.max(Gauge::quicken(units).units);
+                return reaction;
+            }
+        }
+
+        // reactions in priority order: (aura, reaction, consumption ratio)
+        let candidates: &[(Element, Reaction, f32)] = match element {
+            Element::Pyro => &[(Element::Cryo, Reaction::Melt, 2.0), (Element::Hydro, Reaction::ReverseVaporize, 0.5), (Element::Electro, Reaction::Overloaded, 1.0), (Element::Dendro, Reaction::Burning, 0.0)],
+            Element::Hydro => &[(Element::Pyro, Reaction::Vaporize, 2.0), (Element::Cryo, Reaction::Frozen, 1.0), (Element::Electro, Reaction::ElectroCharged, 1.0), (Element::Dendro, Reaction::Bloom, 2.0)],
+            Element::Cryo => &[(Element::Pyro, Reaction::ReverseMelt, 0.5), (Element::Hydro, Reaction::Frozen, 1.0), (Element::Electro, Reaction::Superconduct, 1.0)],
+            Element::Electro => &[(Element::Dendro, Reaction::Quicken, 1.0), (Element::Pyro, Reaction::Overloaded, 1.0), (Element::Hydro, Reaction::ElectroCharged, 1.0), (Element::Cryo, Reaction::Superconduct, 1.0)],
+            Element::Dendro => &[(Element::Electro, Reaction::Quicken, 1.0), (Element::Hydro, Reaction::Bloom, 0.5), (Element::Pyro, Reaction::Burning, 0.0)],
+            Element::Anemo => &[(Element::Pyro, Reaction::Swirl, 0.5), (Element::Hydro, Reaction::Swirl, 0.5), (Element::Electro, Reaction::Swirl, 0.5), (Element::Cryo, Reaction::Swirl, 0.5)],
+            Element::Geo => &[(Element::Pyro, Reaction::Crystallize, 0.5), (Element::Hydro, Reaction::Crystallize, 0.5), (Element::Electro, Reaction::Crystallize, 0.5), (Element::Cryo, Reaction::Crystallize, 0.5)],
+            Element::Physical | Element::None => &[],
+        };
+
+        let Some(&(aura, reaction, ratio)) = candidates.iter().find(|(aura, _, _)| self.reacts_with(*aura)) else {
+            self.add_aura(element, units);
+            return Reaction::None;
+        };
+
+        match reaction {
+            Reaction::Quicken => {
+                let quicken_units = units.min(self.gauge(aura));
+                self.consume(aura, units);
+                self.quicken = Some(Gauge::quicken(quicken_units));
+            },
+            Reaction::Burning => {
+                // pyro and dendro coexist while burning, quicken burns away
+                if self.gauge(Element::Dendro) <= 0.0 {
+                    self.consume(Element::Dendro, units);
+                }
+                self.add_aura(element, units);
+            },
+            _ => self.consume(aura, ratio * units),
+        }
+        reaction
+    }
+
+    fn add_aura(&mut self, element: Element, units: f32) {
+        if !matches!(element, Element::Pyro | Element::Hydro | Element::Electro | Element::Cryo | Element::Dendro) {
+            return;
+        }
+        let applied = Gauge::aura(units);
+        match self.gauges.iter_mut().find(|(e, _)| *e == element) {
+            Some((_, gauge)) => gauge.units = gauge.units.max(applied.units), // refreshes gauge but keeps decay rate
+            None => self.gauges.push((element, applied)),
+        }
+    }
+
+    /// true if an aura of an element is left to react with, quicken counts as dendro
+    fn reacts_with(&self, element: Element) -> bool {
+        self.gauge(element) > 0.0 || (element == Element::Dendro && self.quicken.is_some())
+    }
+
+    /// consumes an aura, dendro is consumed from quicken when there is no dendro aura
+    fn consume(&mut self, element: Element, units: f32) {
+        match self.gauges.iter_mut().find(|(e, _)| *e == element) {
+            Some((_, gauge)) => gauge.units -= units,
+            None if element == Element::Dendro => {
+                if let Some(quicken) = self.quicken.as_mut() {
+                    quicken.units -= units;
+                }
+                self.quicken = self.quicken.filter(|g| g.units > 0.0);
+            },
+            None => {},
+        }
+        self.gauges.retain(|(_, g)| g.units > 0.0);
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::assert_aprx;
+
+    #[test] fn icd_applies_every_third_hit_or_after_timer() {
+        let mut icd = IcdTracker::new();
+        let applied: Vec<bool> = [0.0, 0.3, 0.6, 0.9, 1.2, 1.5, 1.8, 2.6]
+            .iter()
+            .map(|t| icd.applies(Some("normal attack"), *t))
+            .collect();
+        assert_eq!(applied, vec![true, false, false, true, false, false, true, true]);
+        assert!(icd.applies(None, 2.7));
+    }
+
+    #[test] fn aura_decays_over_time() {
+        let mut aura = Aura::with(Element::Hydro, 1.0);
+        assert_aprx!(aura.gauge(Element::Hydro), 0.8, 0.0001);
+        aura.advance(4.75); // half of 9.5s
+        assert_aprx!(aura.gauge(Element::Hydro), 0.4, 0.0001);
+        aura.advance(10.0);
+        assert_eq!(aura.gauge(Element::Hydro), 0.0);
+    }
+
+    #[test] fn reverse_reactions_consume_less_gauge() {
+        // 1U pyro reverse vaporizes off a 2U (1.6U aura) hydro aura four times before it is gone
+        let mut aura = Aura::with(Element::Hydro, 2.0);
+        assert_eq!(aura.apply(0.0, Element::Pyro, 1.0), Reaction::ReverseVaporize);
+        assert_aprx!(aura.gauge(Element::Hydro), 1.1, 0.0001);
+        for _ in 0..3 {
+            assert_eq!(aura.apply(0.0, Element::Pyro, 1.0), Reaction::ReverseVaporize);
+        }
+        assert_eq!(aura.gauge(Element::Hydro), 0.0);
+        // pyro becomes the aura once hydro is consumed
+        assert_eq!(aura.apply(0.0, Element::Pyro, 1.0), Reaction::None);
+        assert_aprx!(aura.gauge(Element::Pyro), 0.8, 0.0001);
+        // forward vaporize consumes double
+        assert_eq!(aura.apply(0.0, Element::Hydro, 1.0), Reaction::Vaporize);
+        assert_eq!(aura.gauge(Element::Pyro), 0.0);
+    }
+
+    #[test] fn reactions_take_their_direction_from_the_trigger() {
+        assert_eq!(Reaction::Vaporize.triggered_by(Element::Pyro).unwrap(), Reaction::ReverseVaporize);
+        assert_eq!(Reaction::ReverseVaporize.triggered_by(Element::Hydro).unwrap(), Reaction::Vaporize);
+        assert_eq!(Reaction::Melt.triggered_by(Element::Cryo).unwrap(), Reaction::ReverseMelt);
+        assert_eq!(Reaction::Spread.triggered_by(Element::Dendro).unwrap(), Reaction::Spread);
+        assert!(Reaction::Melt.triggered_by(Element::Electro).is_err());
+        assert!(Reaction::Aggravate.triggered_by(Element::Dendro).is_err());
+    }
+
+    #[test] fn bloom_consumes_more_dendro_than_hydro() {
+        // hydro on dendro consumes 2x of the dendro aura
+        let mut dendro = Aura::with(Element::Dendro, 2.0);
+        assert_eq!(dendro.apply(0.0, Element::Hydro, 0.5), Reaction::Bloom);
+        assert_aprx!(dendro.gauge(Element::Dendro), 0.6, 0.0001);
+        // dendro on hydro consumes 0.5x of the hydro aura
+        let mut hydro = Aura::with(Element::Hydro, 2.0);
+        assert_eq!(hydro.apply(0.0, Element::Dendro, 1.0), Reaction::Bloom);
+        assert_aprx!(hydro.gauge(Element::Hydro), 1.1, 0.0001);
+    }
+
+    #[test] fn quicken_coexists_and_catalyzes() {
+        let mut aura = Aura::with(Element::Dendro, 1.0);
+        assert_eq!(aura.apply(0.0, Element::Electro, 1.0), Reaction::Quicken);
+        assert!(aura.quicken_gauge() > 0.0);
+        assert_eq!(aura.apply(1.0, Element::Electro, 1.0), Reaction::Aggravate);
+        assert_eq!(aura.apply(1.0, Element::Dendro, 1.0), Reaction::Spread);
+        // catalyzing refreshes quicken
+        assert_aprx!(aura.quicken_gauge(), 1.0, 0.001);
+        // hydro blooms with quicken and consumes 2x its units
+        assert_eq!(aura.apply(1.0, Element::Hydro, 0.25), Reaction::Bloom);
+        assert_aprx!(aura.quicken_gauge(), 0.5, 0.001);
+        assert_eq!(aura.gauge(Element::Hydro), 0.0);
+        assert_eq!(aura.apply(1.0, Element::Hydro, 1.0), Reaction::Bloom);
+        assert_eq!(aura.quicken_gauge(), 0.0);
+    }
+
+    #[test] fn pyro_burns_quicken() {
+        let mut aura = Aura::with(Element::Dendro, 1.0);
+        aura.apply(0.0, Element::Electro, 1.0);
+        assert_eq!(aura.apply(0.0, Element::Pyro, 2.0), Reaction::Burning);
+        assert_eq!(aura.quicken_gauge(), 0.0);
+        assert!(aura.gauge(Element::Pyro) > 0.0);
+    }
+
+    #[test] fn burning_keeps_pyro_and_dendro() {
+        let mut aura = Aura::with(Element::Dendro, 1.0);
+        assert_eq!(aura.apply(0.0, Element::Pyro, 1.0), Reaction::Burning);
+        assert!(aura.gauge(Element::Pyro) > 0.0 && aura.gauge(Element::Dendro) > 0.0);
+        assert_eq!(aura.apply(0.5, Element::Hydro, 1.0), Reaction::Vaporize);
+    }
+}
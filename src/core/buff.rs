@@ -0,0 +1,262 @@
+use crate::core::stattable::StatTable;
+
+/// who a buff applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuffTarget {
+    Own, // only the hits of the character that applied it
+    Team, // hits of every character
+    OnField, // hits of whoever is on field at the time of the hit
+}
+
+/// what happens when an active buff is applied again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackingPolicy {
+    Refresh, // adds a stack and refreshes the duration of every stack, per character applying it
+    Independent, // every application is a stack with its own duration, the oldest stack is dropped past max stacks
+    UniquePerSource, // a single instance per source id across the team, a new application replaces the old one
+}
+
+/// a buff with a duration, eg: bennett burst for 12s, or up to 3 stacks of 8% dmg
+#[derive(Debug, Clone, PartialEq)]
+pub struct Buff {
+    pub name: String,
+    pub source: String, // source id, eg: "noblesse oblige 4pc", copies of the same source share it
+    pub stats: StatTable, // stats per stack
+    pub duration: f32,
+    pub max_stacks: u32,
+    pub stacking: StackingPolicy,
+    pub target: BuffTarget,
+}
+
+impl Buff {
+    /// single stack buff that refreshes on reapplication, its source id is its name
+    pub fn new(name: &str, stats: StatTable, duration: f32, target: BuffTarget) -> Self {
+        Self { name: String::from(name), source: String::from(name), stats, duration, max_stacks: 1, stacking: StackingPolicy::Refresh, target }
+    }
+
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = String::from(source);
+        self
+    }
+
+    pub fn with_max_stacks(mut self, max_stacks: u32) -> Self {
+        self.max_stacks = max_stacks.max(1);
+        self
+    }
+
+    pub fn with_stacking(mut self, stacking: StackingPolicy) -> Self {
+        self.stacking = stacking;
+        self
+    }
+}
+
+/// a buff over the window it is active with a fixed number of stacks
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuffWindow {
+    pub name: String,
+    pub source: String,
+    pub holder: String, // character that applied the buff
+    pub target: BuffTarget,
+    pub stats: StatTable, // stats per stack
+    pub start: f32,
+    pub end: f32,
+    pub stacks: u32,
+}
+
+impl BuffWindow {
+    pub fn is_active(&self, time: f32) -> bool {
+        self.start <= time && time < self.end
+    }
+
+    /// true if the window buffs a character's hits
+    pub fn applies_to(&self, character: &str, on_field: bool) -> bool {
+        match self.target {
+            BuffTarget::Own => self.holder == character,
+            BuffTarget::Team => true,
+            BuffTarget::OnField => on_field,
+        }
+    }
+}
+
+/// buffs applied over an ordered rotation, resolved into windows by their stacking policy
+#[derive(Debug, Clone, Default)]
+pub struct BuffSchedule {
+    windows: Vec<BuffWindow>,
+}
+
+impl BuffSchedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// applies a buff from a character at a time, applications must be in time order
+    pub fn apply(&mut self, time: f32, holder: &str, buff: &Buff) -> &mut Self {
+        let active = |w: &&mut BuffWindow| w.name == buff.name && w.is_active(time);
+        let mut stacks = 1;
+        match buff.stacking {
+            StackingPolicy::Refresh => {
+                if let Some(window) = self.windows.iter_mut().filter(active).find(|w| w.holder == holder) {
+                    stacks = (window.stacks + 1).min(buff.max_stacks);
+                    window.end = time;
+                }
+            },
+            StackingPolicy::Independent => {
+                let mut running: Vec<&mut BuffWindow> = self.windows.iter_mut().filter(active).filter(|w| w.holder == holder).collect();
+                if running.len() >= buff.max_stacks as usize {
+                    running.sort_by(|a, b| a.start.total_cmp(&b.start));
+                    let drop = running.len() + 1 - buff.max_stacks as usize;
+                    for window in running.into_iter().take(drop) {
+                        window.end = time;
+                    }
+                }
+            },
+            StackingPolicy::UniquePerSource => {
+                for window in self.windows.iter_mut().filter(|w| w.source == buff.source && w.is_active(time)) {
+                    window.end = time;
+                }
+            },
+        }
+        self.windows.push(BuffWindow {
+            name: buff.name.clone(),
+            source: buff.source.clone(),
+            holder: String::from(holder),
+            target: buff.target,
+            stats: buff.stats.clone(),
+            start: time,
+            end: time + buff.duration,
+            stacks,
+        });
+        self
+    }
+
+    pub fn windows(&self) -> &[BuffWindow] {
+        &self.windows
+    }
+
+    /// sum of the buffs active on a character's hit at a time
+    pub fn stats_at(&self, time: f32, character: &str, on_field: bool) -> StatTable {
+        let mut res = StatTable::new();
+        for window in self.windows.iter().filter(|w| w.is_active(time) && w.applies_to(character, on_field)) {
+            for (stat, value) in window.stats.iter() {
+                res.add(&stat, value * window.stacks as f32);
+            }
+        }
+        res
+    }
+
+    /// fraction of a time range at least one stack of a buff is active
+    pub fn uptime(&self, name: &str, start: f32, end: f32) -> f32 {
+        if end <= start {
+            return 0.0;
+        }
+        self.segments(start, end).iter()
+            .filter(|(a, _)| self.windows.iter().any(|w| w.name == name && w.is_active(*a)))
+            .map(|(a, b)| b - a)
+            .sum::<f32>() / (end - start)
+    }
+
+    /// time weighted average of the buffs on a character over a time range
+    pub fn average_stats(&self, character: &str, on_field: bool, start: f32, end: f32) -> StatTable {
+        let mut res = StatTable::new();
+        if end <= start {
+            return res;
+        }
+        for (a, b) in self.segments(start, end) {
+            for (stat, value) in self.stats_at(a, character, on_field).iter() {
+                res.add(&stat, value * (b - a) / (end - start));
+            }
+        }
+        res
+    }
+
+    /// average of the buffs on a character over the times of its hits
+    /// eg: a buff active for 2 of 4 hits contributes half its stats
+    pub fn hit_weighted_stats(&self, character: &str, on_field: bool, hit_times: &[f32]) -> StatTable {
+        let mut res = StatTable::new();
+        for time in hit_times {
+            for (stat, value) in self.stats_at(*time, character, on_field).iter() {
+                res.add(&stat, value / hit_times.len() as f32);
+            }
+        }
+        res
+    }
+
+    /// splits a time range at every window start and end
+    fn segments(&self, start: f32, end: f32) -> Vec<(f32, f32)> {
+        let mut points: Vec<f32> = self.windows.iter()
+            .flat_map(|w| [w.start, w.end])
+            .filter(|t| *t > start && *t < end)
+            .chain([start, end])
+            .collect();
+        points.sort_by(|a, b| a.total_cmp(b));
+        points.dedup();
+        points.windows(2).map(|x| (x[0], x[1])).collect()
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::types::Stat;
+    use crate::assert_aprx;
+
+    fn dmg(name: &str, value: f32, duration: f32) -> Buff {
+        Buff::new(name, StatTable::of(&[(Stat::DMGBonus, value)]), duration, BuffTarget::Team)
+    }
+
+    #[test] fn refresh_adds_stacks_up_to_max() {
+        let buff = dmg("stacks", 0.08, 6.0).with_max_stacks(3);
+        let mut schedule = BuffSchedule::new();
+        for t in [0.0, 1.0, 2.0, 3.0] {
+            schedule.apply(t, "a", &buff);
+        }
+        assert_aprx!(schedule.stats_at(3.5, "a", true).get(&Stat::DMGBonus), 0.24, 0.0001);
+        // every stack refreshed at 3s
+        assert_aprx!(schedule.stats_at(8.5, "a", true).get(&Stat::DMGBonus), 0.24, 0.0001);
+        assert_eq!(schedule.stats_at(9.0, "a", true).get(&Stat::DMGBonus), 0.0);
+    }
+
+    #[test] fn independent_stacks_expire_on_their_own() {
+        let buff = dmg("stacks", 0.1, 3.0).with_max_stacks(2).with_stacking(StackingPolicy::Independent);
+        let mut schedule = BuffSchedule::new();
+        schedule.apply(0.0, "a", &buff).apply(1.0, "a", &buff);
+        assert_aprx!(schedule.stats_at(2.0, "a", true).get(&Stat::DMGBonus), 0.2, 0.0001);
+        assert_aprx!(schedule.stats_at(3.5, "a", true).get(&Stat::DMGBonus), 0.1, 0.0001);
+        // a third stack drops the oldest
+        schedule.apply(2.0, "a", &buff);
+        assert_aprx!(schedule.stats_at(2.5, "a", true).get(&Stat::DMGBonus), 0.2, 0.0001);
+    }
+
+    #[test] fn same_source_does_not_stack() {
+        let noblesse = Buff::new("noblesse", StatTable::of(&[(Stat::ATKPercent, 0.2)]), 12.0, BuffTarget::Team)
+            .with_stacking(StackingPolicy::UniquePerSource);
+        let mut schedule = BuffSchedule::new();
+        schedule.apply(0.0, "bennett", &noblesse).apply(5.0, "zhongli", &noblesse);
+        assert_aprx!(schedule.stats_at(6.0, "xiangling", false).get(&Stat::ATKPercent), 0.2, 0.0001);
+        assert_aprx!(schedule.stats_at(16.0, "xiangling", false).get(&Stat::ATKPercent), 0.2, 0.0001);
+
+        // the same buff with refresh from two characters stacks
+        let refresh = noblesse.with_stacking(StackingPolicy::Refresh);
+        let mut schedule = BuffSchedule::new();
+        schedule.apply(0.0, "bennett", &refresh).apply(5.0, "zhongli", &refresh);
+        assert_aprx!(schedule.stats_at(6.0, "xiangling", false).get(&Stat::ATKPercent), 0.4, 0.0001);
+    }
+
+    #[test] fn target_scope() {
+        let own = dmg("own", 0.1, 10.0).with_stacking(StackingPolicy::Refresh);
+        let field = Buff::new("field", StatTable::of(&[(Stat::DMGBonus, 0.2)]), 10.0, BuffTarget::OnField);
+        let mut schedule = BuffSchedule::new();
+        schedule.apply(0.0, "a", &Buff { target: BuffTarget::Own, ..own }).apply(0.0, "a", &field);
+        assert_aprx!(schedule.stats_at(1.0, "a", false).get(&Stat::DMGBonus), 0.1, 0.0001);
+        assert_aprx!(schedule.stats_at(1.0, "b", true).get(&Stat::DMGBonus), 0.2, 0.0001);
+    }
+
+    #[test] fn uptime_weighted_stats() {
+        let bennett = Buff::new("bennett burst", StatTable::of(&[(Stat::FlatATK, 1000.0)]), 12.0, BuffTarget::OnField);
+        let mut schedule = BuffSchedule::new();
+        schedule.apply(0.0, "bennett", &bennett);
+        assert_aprx!(schedule.uptime("bennett burst", 0.0, 20.0), 0.6, 0.0001);
+        assert_aprx!(schedule.average_stats("xiangling", true, 0.0, 20.0).get(&Stat::FlatATK), 600.0, 0.01);
+        assert_eq!(schedule.average_stats("xiangling", false, 0.0, 20.0).get(&Stat::FlatATK), 0.0);
+        assert_aprx!(schedule.hit_weighted_stats("xiangling", true, &[1.0, 5.0, 11.0, 15.0]).get(&Stat::FlatATK), 750.0, 0.01);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::core::artifact_builder::RollQuality;
+use crate::core::stattable::StatTable;
+use crate::core::types::Stat;
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// assumptions a calculation is done under (artifact substat budget, character and enemy defaults)
+/// fields missing from a json preset fall back to kqmc
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CalcStandard {
+    pub fixed_rolls_per_substat: i8, // rolls of every substat type given for free
+    pub fluid_rolls_per_substat_per_piece: i8, // max fluid rolls of a substat on a piece with a different main stat
+    pub roll_penalty_per_piece: i8, // fluid rolls removed per artifact piece
+    pub roll_quality: RollQuality,
+    pub base_crit_rate: f32,
+    pub base_crit_dmg: f32,
+    pub base_energy_recharge: f32,
+    pub character_level: i8,
+    pub enemy_level: i8,
+    pub enemy_resistance: f32,
+}
+
+impl CalcStandard {
+    /// keqingmains calculation standard
+    // https://compendium.keqingmains.com/
+    pub const KQMC: CalcStandard = CalcStandard {
+        fixed_rolls_per_substat: 2,
+        fluid_rolls_per_substat_per_piece: 2,
+        roll_penalty_per_piece: 1,
+        roll_quality: RollQuality::AVG,
+        base_crit_rate: 0.05,
+        base_crit_dmg: 0.5,
+        base_energy_recharge: 1.0,
+        character_level: 90,
+        enemy_level: 100,
+        enemy_resistance: 0.1,
+    };
+
+    /// parses a custom preset from json
+    /// eg: {"fluid_rolls_per_substat_per_piece": 1, "roll_quality": "MAX", "enemy_resistance": 0.7}
+    pub fn from_json(json: &str) -> Result<Self> {
+        let standard: CalcStandard = serde_json::from_str(json).context("failed to parse calc standard")?;
+        standard.validate()?;
+        Ok(standard)
+    }
+
+    /// reads a custom preset from a json file
+    pub fn from_json_file(path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path).with_context(|| format!("failed to read calc standard {path}"))?;
+        Self::from_json(&json)
+    }
+
+    /// true if damage computed under both standards is the same (same character level and enemy)
+    pub fn same_damage_assumptions(&self, other: &CalcStandard) -> bool {
+        self.character_level == other.character_level
+            && self.enemy_level == other.enemy_level
+            && self.enemy_resistance == other.enemy_resistance
+    }
+
+    /// checks the assumptions are usable by the formulas
+    pub fn validate(&self) -> Result<()> {
+        if self.fixed_rolls_per_substat < 0 || self.fluid_rolls_per_substat_per_piece < 0 || self.roll_penalty_per_piece < 0 {
+            return Err(anyhow!("substat roll counts cannot be negative"));
+        }
+        if !(1..=90).contains(&self.character_level) {
+            return Err(anyhow!("character level {} out of range", self.character_level));
+        }
+        if self.enemy_level < 1 {
+            return Err(anyhow!("enemy level {} out of range", self.enemy_level));
+        }
+        Ok(())
+    }
+
+    /// stats every character has without gear under this standard
+    pub fn character_base_stats(&self) -> StatTable {
+        StatTable::of(&[
+            (Stat::CritRate, self.base_crit_rate),
+            (Stat::CritDMG, self.base_crit_dmg),
+            (Stat::EnergyRecharge, self.base_energy_recharge),
+        ])
+    }
+}
+
+impl Default for CalcStandard {
+    fn default() -> Self {
+        Self::KQMC
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn partial_json_preset_falls_back_to_kqmc() {
+        let standard = CalcStandard::from_json(r#"{"fluid_rolls_per_substat_per_piece": 1, "roll_quality": "MAX", "enemy_resistance": 0.7}"#).unwrap();
+        assert_eq!(standard.fluid_rolls_per_substat_per_piece, 1);
+        assert_eq!(standard.roll_quality, RollQuality::MAX);
+        assert_eq!(standard.enemy_resistance, 0.7);
+        assert_eq!(standard.fixed_rolls_per_substat, CalcStandard::KQMC.fixed_rolls_per_substat);
+        assert_eq!(standard.enemy_level, 100);
+    }
+
+    #[test] fn invalid_json_preset_is_error() {
+        assert!(CalcStandard::from_json(r#"{"character_level": 100}"#).is_err());
+        assert!(CalcStandard::from_json(r#"{"roll_penalty_per_piece": -1}"#).is_err());
+        assert!(CalcStandard::from_json("not json").is_err());
+    }
+
+    #[test] fn kqmc_base_stats() {
+        let stats = CalcStandard::KQMC.character_base_stats();
+        assert_eq!(stats.get(&Stat::CritRate), 0.05);
+        assert_eq!(stats.get(&Stat::CritDMG), 0.5);
+        assert_eq!(stats.get(&Stat::EnergyRecharge), 1.0);
+    }
+}
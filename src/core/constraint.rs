@@ -0,0 +1,146 @@
+use crate::core::stattable::StatTable;
+use crate::core::types::Stat;
+
+/// computes a value derived from a stattable (eg: total hp, crit rate during burst)
+pub type Derivation = Box<dyn Fn(&StatTable) -> f32 + 'static>;
+
+/// a requirement a stattable needs to meet for a build to be valid
+/// eg: er >= 130%, crit rate <= 100% in burst window, total hp >= 30000 for a hp scaling passive
+pub struct Constraint {
+    name: String,
+    kind: ConstraintKind,
+}
+
+enum ConstraintKind {
+    Predicate(Box<dyn Fn(&StatTable) -> bool + 'static>),
+    Min(Derivation, f32),
+    Max(Derivation, f32),
+}
+
+impl Constraint {
+    /// construct a constraint from an arbitrary predicate
+    /// a predicate only tells whether it holds, so the substat search can only meet it by rolling a single substat until it does
+    /// prefer min/max when the requirement is a bound on a derived value
+    pub fn predicate(name: &str, predicate: impl Fn(&StatTable) -> bool + 'static) -> Self {
+        Self { name: String::from(name), kind: ConstraintKind::Predicate(Box::new(predicate)) }
+    }
+
+    /// construct a constraint that requires a derived value to be at least a given value
+    pub fn min(name: &str, derivation: impl Fn(&StatTable) -> f32 + 'static, value: f32) -> Self {
+        Self { name: String::from(name), kind: ConstraintKind::Min(Box::new(derivation), value) }
+    }
+
+    /// construct a constraint that requires a derived value to be at most a given value
+    pub fn max(name: &str, derivation: impl Fn(&StatTable) -> f32 + 'static, value: f32) -> Self {
+        Self { name: String::from(name), kind: ConstraintKind::Max(Box::new(derivation), value) }
+    }
+
+    /// construct a constraint that requires a stat to be at least a given value
+    pub fn min_stat(stat: Stat, value: f32) -> Self {
+        Self::min(&format!("{stat} >= {value}"), move |s| s.get(&stat), value)
+    }
+
+    /// construct a constraint that requires a stat to be at most a given value
+    pub fn max_stat(stat: Stat, value: f32) -> Self {
+        Self::max(&format!("{stat} <= {value}"), move |s| s.get(&stat), value)
+    }
+
+    /// construct a constraint for energy recharge requirements
+    pub fn energy_recharge(value: f32) -> Self {
+        Self::min_stat(Stat::EnergyRecharge, value)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// returns the derived value the constraint bounds, none for predicates
+    pub fn derived_value(&self, stats: &StatTable) -> Option<f32> {
+        match &self.kind {
+            ConstraintKind::Predicate(_) => None,
+            ConstraintKind::Min(f, _) | ConstraintKind::Max(f, _) => Some(f(stats)),
+        }
+    }
+
+    /// returns how far a stattable is from meeting the constraint, 0 if it is met
+    pub fn deficit(&self, stats: &StatTable) -> f32 {
+        match &self.kind {
+            ConstraintKind::Predicate(p) => if p(stats) { 0.0 } else { 1.0 },
+            ConstraintKind::Min(f, value) => f32::max(value - f(stats), 0.0),
+            ConstraintKind::Max(f, value) => f32::max(f(stats) - value, 0.0),
+        }
+    }
+
+    pub fn is_satisfied(&self, stats: &StatTable) -> bool {
+        self.deficit(stats) <= 0.0
+    }
+
+    /// returns a violation if the stattable does not meet the constraint
+    pub fn check(&self, stats: &StatTable) -> Result<(), ConstraintViolation> {
+        if self.is_satisfied(stats) {
+            return Ok(());
+        }
+        Err(ConstraintViolation { constraint: self.name.clone(), value: self.derived_value(stats) })
+    }
+}
+
+/// returns the first violated constraint of a list of constraints
+pub fn check_all(constraints: &[Constraint], stats: &StatTable) -> Result<(), ConstraintViolation> {
+    constraints.iter().try_for_each(|c| c.check(stats))
+}
+
+/// error for a constraint that a stattable does not (or cannot) meet
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConstraintViolation {
+    pub constraint: String,
+    pub value: Option<f32>, // derived value when the constraint was violated
+}
+
+impl std::fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            Some(value) => write!(f, "constraint {} cannot be met (reached {})", self.constraint, value),
+            None => write!(f, "constraint {} cannot be met", self.constraint),
+        }
+    }
+}
+
+impl std::error::Error for ConstraintViolation {}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::functions::formulas::formulas::total_hp;
+
+    #[test] fn min_stat_constraint() {
+        let c = Constraint::energy_recharge(1.3);
+        assert!(!c.is_satisfied(&StatTable::of(&[(Stat::EnergyRecharge, 1.0)])));
+        assert!(c.is_satisfied(&StatTable::of(&[(Stat::EnergyRecharge, 1.3)])));
+        assert!((c.deficit(&StatTable::of(&[(Stat::EnergyRecharge, 1.0)])) - 0.3).abs() < 0.0001);
+    }
+
+    #[test] fn max_constraint_on_buffed_stats() {
+        // crit rate during a burst window with a 20% crit rate buff
+        let c = Constraint::max("burst crit rate <= 100%", |s| s.get(&Stat::CritRate) + 0.2, 1.0);
+        assert!(c.is_satisfied(&StatTable::of(&[(Stat::CritRate, 0.8)])));
+        assert!(!c.is_satisfied(&StatTable::of(&[(Stat::CritRate, 0.9)])));
+    }
+
+    #[test] fn min_constraint_on_derived_value() {
+        let c = Constraint::min("total hp >= 30000", total_hp, 30000.0);
+        let stats = StatTable::of(&[(Stat::BaseHP, 15000.0), (Stat::HPPercent, 0.5)]);
+        let violation = c.check(&stats).unwrap_err();
+        assert_eq!(violation.constraint, "total hp >= 30000");
+        assert_eq!(violation.value, Some(22500.0));
+    }
+
+    #[test] fn check_all_reports_first_violation() {
+        let constraints = vec![
+            Constraint::min_stat(Stat::ElementalMastery, 100.0),
+            Constraint::predicate("has crit dmg", |s| s.get(&Stat::CritDMG) > 0.0),
+        ];
+        assert!(check_all(&constraints, &StatTable::of(&[(Stat::ElementalMastery, 100.0), (Stat::CritDMG, 0.5)])).is_ok());
+        let violation = check_all(&constraints, &StatTable::of(&[(Stat::ElementalMastery, 100.0)])).unwrap_err();
+        assert_eq!(violation.constraint, "has crit dmg");
+        assert_eq!(violation.value, None);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::core::stattable::{StatLookup, StatTable};
+use crate::core::types::{Stat, ALL_STATS, STAT_COUNT};
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// number type the damage formulas are generic over
+/// f32 computes plain values, [`Dual`] also carries partial derivatives with respect to every stat
+pub trait Numeric: Copy + PartialOrd
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> + Neg<Output = Self>
+{
+    /// a value that does not depend on any stat
+    fn constant(value: f32) -> Self;
+    /// the plain value
+    fn value(&self) -> f32;
+
+    /// on a tie the other value is picked, so a capped stat has no gradient at the cap
+    fn min(self, other: Self) -> Self {
+        if other.value() <= self.value() { other } else { self }
+    }
+
+    fn max(self, other: Self) -> Self {
+        if other.value() > self.value() { other } else { self }
+    }
+}
+
+impl Numeric for f32 {
+    fn constant(value: f32) -> Self {
+        value
+    }
+    fn value(&self) -> f32 {
+        *self
+    }
+}
+
+/// forward mode dual number: a value and its partial derivatives with respect to every stat
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Dual {
+    pub value: f32,
+    pub grad: [f32; STAT_COUNT], // indexed by Stat::index
+}
+
+impl Dual {
+    /// a stat variable, its derivative with respect to itself is 1
+    pub fn variable(stat: Stat, value: f32) -> Self {
+        let mut grad = [0.0; STAT_COUNT];
+        grad[stat.index()] = 1.0;
+        Self { value, grad }
+    }
+
+    /// partial derivative with respect to a stat
+    pub fn derivative(&self, stat: &Stat) -> f32 {
+        self.grad[stat.index()]
+    }
+
+    fn map_grad(self, other: Self, f: impl Fn(f32, f32) -> f32) -> [f32; STAT_COUNT] {
+        let mut grad = [0.0; STAT_COUNT];
+        for (i, g) in grad.iter_mut().enumerate() {
+            *g = f(self.grad[i], other.grad[i]);
+        }
+        grad
+    }
+}
+
+impl Numeric for Dual {
+    fn constant(value: f32) -> Self {
+        Self { value, grad: [0.0; STAT_COUNT] }
+    }
+    fn value(&self) -> f32 {
+        self.value
+    }
+}
+
+impl PartialOrd for Dual {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl Add for Dual {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self { value: self.value + other.value, grad: self.map_grad(other, |a, b| a + b) }
+    }
+}
+
+impl Sub for Dual {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Self { value: self.value - other.value, grad: self.map_grad(other, |a, b| a - b) }
+    }
+}
+
+impl Mul for Dual {
+    type Output = Self;
+    #[allow(clippy::suspicious_arithmetic_impl)] // product and quotient rules
+    fn mul(self, other: Self) -> Self {
+        let (u, v) = (self.value, other.value);
+        Self { value: u * v, grad: self.map_grad(other, |du, dv| du * v + u * dv) }
+    }
+}
+
+impl Div for Dual {
+    type Output = Self;
+    #[allow(clippy::suspicious_arithmetic_impl)] // product and quotient rules
+    fn div(self, other: Self) -> Self {
+        let (u, v) = (self.value, other.value);
+        Self { value: u / v, grad: self.map_grad(other, |du, dv| (du * v - u * dv) / (v * v)) }
+    }
+}
+
+impl Neg for Dual {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self { value: -self.value, grad: self.grad.map(|g| -g) }
+    }
+}
+
+/// stattable where every stat is a dual variable, evaluating formulas on it gives exact stat gradients
+#[derive(Debug, Clone, PartialEq)]
+pub struct DualStatTable {
+    inner: Vec<Dual>, // indexed by Stat::index
+}
+
+impl DualStatTable {
+    /// seeds a dual variable for every stat from a stattable
+    pub fn seed(stats: &StatTable) -> Self {
+        Self { inner: ALL_STATS.iter().map(|stat| Dual::variable(*stat, stats.get(stat))).collect() }
+    }
+}
+
+impl StatLookup for DualStatTable {
+    type Value = Dual;
+    fn get(&self, stat_type: &Stat) -> Dual {
+        self.inner[stat_type.index()]
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn product_and_quotient_rules() {
+        let x = Dual::variable(Stat::BaseATK, 3.0);
+        let y = Dual::variable(Stat::ATKPercent, 2.0);
+        let f = x * y + Dual::constant(1.0);
+        assert_eq!(f.value, 7.0);
+        assert_eq!(f.derivative(&Stat::BaseATK), 2.0);
+        assert_eq!(f.derivative(&Stat::ATKPercent), 3.0);
+
+        let g = x / y;
+        assert_eq!(g.value, 1.5);
+        assert_eq!(g.derivative(&Stat::BaseATK), 0.5);
+        assert_eq!(g.derivative(&Stat::ATKPercent), -0.75);
+        assert_eq!((-g).derivative(&Stat::ATKPercent), 0.75);
+    }
+
+    #[test] fn min_max_follow_the_chosen_branch() {
+        let x = Dual::variable(Stat::CritRate, 1.2);
+        let capped = x.min(Dual::constant(1.0));
+        assert_eq!(capped.value, 1.0);
+        assert_eq!(capped.derivative(&Stat::CritRate), 0.0);
+        assert_eq!(Numeric::min(0.5_f32, 1.0), 0.5);
+    }
+
+    #[test] fn seeded_table_has_unit_derivatives() {
+        let table = DualStatTable::seed(&StatTable::of(&[(Stat::FlatATK, 10.0)]));
+        assert_eq!(table.get(&Stat::FlatATK).value, 10.0);
+        assert_eq!(table.get(&Stat::FlatATK).derivative(&Stat::FlatATK), 1.0);
+        assert_eq!(table.get(&Stat::CritDMG).value, 0.0);
+        assert_eq!(table.get(&Stat::CritDMG).derivative(&Stat::CritDMG), 1.0);
+        assert_eq!(table.get(&Stat::CritDMG).derivative(&Stat::FlatATK), 0.0);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::core::calc_standard::CalcStandard;
+use crate::core::enemy::Enemy;
+use crate::core::rotation::Rotation;
+use crate::core::stattable::StatTable;
+use crate::core::types::Element;
+use anyhow::{Result, anyhow};
+
+/// how a team spreads its damage over the enemies of a wave
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaveTargeting {
+    Sequential, // enemies are killed one after another
+    Aoe, // every enemy takes the full damage at once
+}
+
+/// a wave of enemies, downtime is the transition time before the wave can be damaged
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wave {
+    pub enemies: Vec<Enemy>,
+    pub downtime: f32,
+    pub targeting: WaveTargeting,
+}
+
+impl Wave {
+    pub fn new(enemies: Vec<Enemy>, downtime: f32, targeting: WaveTargeting) -> Self {
+        Self { enemies, downtime, targeting }
+    }
+}
+
+/// damage output of a team, measured against the enemy of the encounter's calculation standard
+pub enum TeamDamage<'a> {
+    Dps(f32),
+    /// a rotation repeated until every wave is cleared, partial rotations are prorated
+    Rotation { rotation: &'a Rotation, stats: &'a StatTable, duration: f32 },
+}
+
+impl TeamDamage<'_> {
+    /// damage per second and length of a rotation (0 for a flat dps)
+    fn dps(&self) -> Result<(f32, f32)> {
+        let (dps, duration) = match self {
+            TeamDamage::Dps(dps) => (*dps, 0.0),
+            TeamDamage::Rotation { duration, .. } if *duration <= 0.0 => return Err(anyhow!("rotation duration must be positive")),
+            TeamDamage::Rotation { rotation, stats, duration } => (rotation.evaluate(stats) / duration, *duration),
+        };
+        if dps <= 0.0 {
+            return Err(anyhow!("team deals no damage"));
+        }
+        Ok((dps, duration))
+    }
+}
+
+/// time spent on a wave
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveResult {
+    pub start: f32, // time the wave can be damaged, after its downtime
+    pub clear_time: f32, // time to kill every enemy of the wave
+    pub end: f32,
+    pub rotations: f32, // rotations used on the wave, 0 for a flat dps
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncounterResult {
+    pub waves: Vec<WaveResult>,
+    pub total_time: f32,
+}
+
+/// waves of enemies a team has to clear, eg: an abyss chamber
+pub struct Encounter {
+    waves: Vec<Wave>,
+    element: Element, // main element of the team's damage, for enemy res
+    standard: CalcStandard,
+}
+
+impl Encounter {
+    pub fn new(element: Element, standard: &CalcStandard) -> Self {
+        Self { waves: Vec::new(), element, standard: standard.clone() }
+    }
+
+    pub fn add_wave(&mut self, wave: Wave) -> &mut Self {
+        self.waves.push(wave);
+        self
+    }
+
+    pub fn waves(&self) -> &[Wave] {
+        &self.waves
+    }
+
+    /// estimated time to clear every wave
+    /// damage is scaled from the standard's enemy to each enemy by their def and res multipliers
+    pub fn time_to_clear(&self, damage: &TeamDamage) -> Result<EncounterResult> {
+        if let TeamDamage::Rotation { rotation, .. } = damage {
+            if !rotation.standard().same_damage_assumptions(&self.standard) {
+                return Err(anyhow!("rotation is evaluated under a different calc standard than the encounter"));
+            }
+        }
+        let (dps, rotation_duration) = damage.dps()?;
+        let reference = Enemy::from_standard("reference", &self.standard).damage_multiplier(self.element, &self.standard);
+
+        let mut time = 0.0;
+        let mut waves = Vec::with_capacity(self.waves.len());
+        for (i, wave) in self.waves.iter().enumerate() {
+            let kill_times = wave.enemies.iter()
+                .map(|enemy| {
+                    let hp = enemy.hp.ok_or(anyhow!("enemy {} of wave {} has no hp", enemy.name, i + 1))?;
+                    Ok(hp / (dps * enemy.damage_multiplier(self.element, &self.standard) / reference))
+                })
+                .collect::<Result<Vec<f32>>>()?;
+            let clear_time = match wave.targeting {
+                WaveTargeting::Sequential => kill_times.iter().sum(),
+                WaveTargeting::Aoe => kill_times.iter().cloned().fold(0.0, f32::max),
+            };
+            let start = time + wave.downtime;
+            time = start + clear_time;
+            let rotations = if rotation_duration > 0.0 { clear_time / rotation_duration } else { 0.0 };
+            waves.push(WaveResult { start, clear_time, end: time, rotations });
+        }
+        Ok(EncounterResult { waves, total_time: time })
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::types::*;
+    use crate::assert_aprx;
+
+    fn mob(name: &str, hp: f32) -> Enemy {
+        Enemy::from_standard(name, &CalcStandard::KQMC).with_hp(hp)
+    }
+
+    fn encounter() -> Encounter {
+        let mut encounter = Encounter::new(Element::Pyro, &CalcStandard::KQMC);
+        encounter.add_wave(Wave::new(vec![mob("a", 100000.0), mob("b", 50000.0)], 2.0, WaveTargeting::Aoe));
+        encounter.add_wave(Wave::new(vec![mob("c", 100000.0), mob("d", 50000.0)], 3.0, WaveTargeting::Sequential));
+        encounter
+    }
+
+    #[test] fn waves_are_cleared_in_order() {
+        let result = encounter().time_to_clear(&TeamDamage::Dps(10000.0)).unwrap();
+        assert_aprx!(result.waves[0].clear_time, 10.0, 0.001);
+        assert_aprx!(result.waves[1].start, 15.0, 0.001);
+        assert_aprx!(result.waves[1].clear_time, 15.0, 0.001);
+        assert_aprx!(result.total_time, 30.0, 0.001);
+    }
+
+    #[test] fn partial_rotations_are_prorated() {
+        let stats = StatTable::of(&[(Stat::BaseATK, 1000.0), (Stat::CritRate, 0.5), (Stat::CritDMG, 1.0)]);
+        let rotation = crate::rotation! {
+            ("burst", Element::Pyro, DamageType::Burst, BaseScaling::ATK, Amplifier::None, 10.0, 1.0, None),
+        }.unwrap();
+        let per_rotation = rotation.evaluate(&stats);
+        let result = encounter().time_to_clear(&TeamDamage::Rotation { rotation: &rotation, stats: &stats, duration: 20.0 }).unwrap();
+        assert_aprx!(result.waves[0].rotations, 100000.0 / per_rotation, 0.001);
+        assert_aprx!(result.waves[0].clear_time, 20.0 * 100000.0 / per_rotation, 0.01);
+    }
+
+    #[test] fn rotations_must_match_the_encounter_standard() {
+        let stats = StatTable::of(&[(Stat::BaseATK, 1000.0)]);
+        let tanky = CalcStandard { enemy_resistance: 0.7, ..CalcStandard::KQMC };
+        let rotation = crate::rotation! {
+            ("burst", Element::Pyro, DamageType::Burst, BaseScaling::ATK, Amplifier::None, 10.0, 1.0, None),
+        }.unwrap();
+        let mut encounter = Encounter::new(Element::Pyro, &tanky);
+        encounter.add_wave(Wave::new(vec![Enemy::from_standard("a", &tanky).with_hp(100000.0)], 0.0, WaveTargeting::Aoe));
+        assert!(encounter.time_to_clear(&TeamDamage::Rotation { rotation: &rotation, stats: &stats, duration: 20.0 }).is_err());
+
+        let tanky_rotation = crate::rotation! {
+            standard: tanky;
+            ("burst", Element::Pyro, DamageType::Burst, BaseScaling::ATK, Amplifier::None, 10.0, 1.0, None),
+        }.unwrap();
+        let result = encounter.time_to_clear(&TeamDamage::Rotation { rotation: &tanky_rotation, stats: &stats, duration: 20.0 }).unwrap();
+        assert_aprx!(result.waves[0].rotations, 100000.0 / tanky_rotation.evaluate(&stats), 0.001);
+    }
+
+    #[test] fn tanky_enemies_take_longer() {
+        let mut encounter = Encounter::new(Element::Pyro, &CalcStandard::KQMC);
+        encounter.add_wave(Wave::new(vec![mob("slime", 10000.0).with_element_resistance(Element::Pyro, 0.55)], 0.0, WaveTargeting::Aoe));
+        let result = encounter.time_to_clear(&TeamDamage::Dps(1000.0)).unwrap();
+        assert_aprx!(result.total_time, 20.0, 0.001); // 0.45 / 0.9 of the damage
+
+        let mut immortal = Encounter::new(Element::Pyro, &CalcStandard::KQMC);
+        immortal.add_wave(Wave::new(vec![Enemy::from_standard("dummy", &CalcStandard::KQMC)], 0.0, WaveTargeting::Aoe));
+        assert!(immortal.time_to_clear(&TeamDamage::Dps(1000.0)).is_err());
+        assert!(encounter.time_to_clear(&TeamDamage::Dps(0.0)).is_err());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::core::calc_standard::CalcStandard;
+use crate::core::types::Element;
+use crate::functions::formulas::formulas;
+use serde::{Deserialize, Serialize};
+
+/// an enemy a character deals damage to
+/// def follows from the level, res defaults to one value for every element with per element overrides
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Enemy {
+    pub name: String,
+    pub level: i8,
+    pub resistance: f32,
+    #[serde(default)]
+    pub element_resistance: std::collections::HashMap<Element, f32>, // eg: 0.7 pyro res for a pyro slime
+    #[serde(default)]
+    pub hp: Option<f32>, // none for a target that can not die (eg: a training dummy)
+}
+
+impl Enemy {
+    pub fn new(name: &str, level: i8, resistance: f32) -> Self {
+        Self { name: String::from(name), level, resistance, element_resistance: std::collections::HashMap::new(), hp: None }
+    }
+
+    /// enemy with the level and res of a calculation standard
+    pub fn from_standard(name: &str, standard: &CalcStandard) -> Self {
+        Self::new(name, standard.enemy_level, standard.enemy_resistance)
+    }
+
+    pub fn with_element_resistance(mut self, element: Element, resistance: f32) -> Self {
+        self.element_resistance.insert(element, resistance);
+        self
+    }
+
+    pub fn with_hp(mut self, hp: f32) -> Self {
+        self.hp = Some(hp);
+        self
+    }
+
+    /// base res against an element before shred
+    pub fn resistance(&self, element: Element) -> f32 {
+        self.element_resistance.get(&element).cloned().unwrap_or(self.resistance)
+    }
+
+    /// calculation standard to compute damage of an element against the enemy
+    pub fn standard(&self, element: Element, base: &CalcStandard) -> CalcStandard {
+        CalcStandard { enemy_level: self.level, enemy_resistance: self.resistance(element), ..base.clone() }
+    }
+
+    /// def and res multiplier of a hit of an element on the enemy without shred
+    pub fn damage_multiplier(&self, element: Element, standard: &CalcStandard) -> f32 {
+        formulas::def_multiplier(standard.character_level, self.level, 0.0, 0.0)
+            * formulas::res_multiplier(self.resistance(element), 0.0)
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn element_resistance_overrides_base() {
+        let slime = Enemy::new("pyro slime", 90, 0.1).with_element_resistance(Element::Pyro, 0.7);
+        assert_eq!(slime.resistance(Element::Pyro), 0.7);
+        assert_eq!(slime.resistance(Element::Hydro), 0.1);
+        let standard = slime.standard(Element::Pyro, &CalcStandard::KQMC);
+        assert_eq!((standard.enemy_level, standard.enemy_resistance), (90, 0.7));
+
+        let parsed: Enemy = serde_json::from_str(r#"{"name": "boss", "level": 100, "resistance": 0.1, "hp": 1000000.0}"#).unwrap();
+        assert_eq!(parsed, Enemy::new("boss", 100, 0.1).with_hp(1000000.0));
+    }
+}
@@ -10,21 +10,72 @@
 }
 
 
+/// builds a rotation of damage actions
+/// the reaction of an action is either an amplifier applied to every instance,
+/// or a reaction mix that splits its instances, eg: [Reaction::Vaporize => 0.7]
+/// vaporize and melt in a reaction mix take their direction from the element of the action
+/// healing and shield actions start with heal or shield, eg: (heal "burst", BaseScaling::HP, 0.1, 1000.0, 6.0, None)
+/// effective hp actions start with ehp, eg: (ehp "survival", Element::Pyro, 100, None)
+/// damage is evaluated under the kqmc standard unless one is given first, eg: rotation! { standard: my_standard; (...), }
+/// evaluates to an anyhow::Result, errors if a reaction mix cannot be triggered by the element of its action
 #[macro_export] macro_rules! rotation {
     (
+        standard: $standard:expr;
         $(
-            ($name:literal, $element:expr, $damage_t:expr, $base_scaling:expr, $amplifier:expr, $multiplier:expr, $instances:expr, $buffs:expr)
+            ($($entry:tt)*)
         ),* $(,)?
-    ) => {{
-        let mut res = $crate::core::rotation::Rotation::new();
+    ) => {
+        {
+            #[allow(clippy::redundant_closure_call)]
+            let res = (|| -> ::anyhow::Result<$crate::core::rotation::Rotation> {
+                let mut res = $crate::core::rotation::Rotation::with_standard(&$standard);
+                $(
+                    $crate::rotation!(@entry res; $($entry)*);
+                )*
+                Ok(res)
+            })();
+            res
+        }
+    };
 
+    (
         $(
-            let damage_function = Box::new(move |s: &$crate::core::stattable::StatTable| $crate::functions::dmg_function::DMGFunction::calculate_damage($element, $damage_t, $base_scaling, $amplifier, $instances, $multiplier, s, $buffs));
-            res.add(String::from($name), damage_function);
-        )*
-
-        res
-    }};
+            ($($entry:tt)*)
+        ),* $(,)?
+    ) => {
+        {
+            #[allow(clippy::redundant_closure_call)]
+            let res = (|| -> ::anyhow::Result<$crate::core::rotation::Rotation> {
+                let mut res = $crate::core::rotation::Rotation::new();
+                $(
+                    $crate::rotation!(@entry res; $($entry)*);
+                )*
+                Ok(res)
+            })();
+            res
+        }
+    };
+
+    (@entry $res:ident; heal $name:literal, $base_scaling:expr, $multiplier:expr, $flat:expr, $instances:expr, $buffs:expr) => {
+        $res.add_healing($name, $base_scaling, $multiplier, $flat, $instances, Option::<&$crate::core::stattable::StatTable>::cloned($buffs));
+    };
+
+    (@entry $res:ident; shield $name:literal, $shield_element:expr, $damage_element:expr, $base_scaling:expr, $multiplier:expr, $flat:expr, $buffs:expr) => {
+        $res.add_shield($name, $shield_element, $damage_element, $base_scaling, $multiplier, $flat, Option::<&$crate::core::stattable::StatTable>::cloned($buffs));
+    };
+
+    (@entry $res:ident; ehp $name:literal, $element:expr, $enemy_level:expr, $buffs:expr) => {
+        $res.add_effective_hp($name, $element, $enemy_level, None, Option::<&$crate::core::stattable::StatTable>::cloned($buffs));
+    };
+
+    (@entry $res:ident; $name:literal, $element:expr, $damage_t:expr, $base_scaling:expr, [$($reaction:expr => $share:expr),* $(,)?], $multiplier:expr, $instances:expr, $buffs:expr) => {
+        let reactions = $crate::core::reaction_mix::ReactionMix::new()$(.try_with($reaction, $share)?)*;
+        $res.add_damage_with_reactions($name, $element, $damage_t, $base_scaling, &reactions, $multiplier, $instances as f32, Option::<&$crate::core::stattable::StatTable>::cloned($buffs))?;
+    };
+
+    (@entry $res:ident; $name:literal, $element:expr, $damage_t:expr, $base_scaling:expr, $amplifier:expr, $multiplier:expr, $instances:expr, $buffs:expr) => {
+        $res.add_damage($name, $element, $damage_t, $base_scaling, $amplifier, $multiplier, $instances, Option::<&$crate::core::stattable::StatTable>::cloned($buffs));
+    };
 }
 
 
@@ -70,7 +121,7 @@ mod tests {
 
         let r = rotation! {
             ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
-        };
+        }.unwrap();
 
         let dps = r.evaluate(&stats);
         assert!(dps > 0.0);
@@ -90,11 +141,64 @@ mod tests {
         let r = rotation! {
             ("normal", Element::Physical, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 0.84, 3.0, None),
             ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
-        };
+        }.unwrap();
 
         let dps = r.evaluate(&stats);
         assert!(dps > 0.0);
         assert_ne!(r.evaluate(&stats), 0.0);
     }
+
+    #[test]
+    fn rotation_macro_uses_given_standard() {
+        use crate::core::calc_standard::CalcStandard;
+        let stats = StatTable::of(&[(Stat::BaseATK, 1000.0)]);
+        let tanky = CalcStandard { enemy_resistance: 0.7, ..CalcStandard::KQMC };
+        let kqmc = rotation! {
+            ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+        }.unwrap();
+        let r = rotation! {
+            standard: tanky;
+            ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+        }.unwrap();
+        assert_eq!(r.standard(), &tanky);
+        // 0.3 of the damage through 70% res instead of 0.9 through 10%
+        assert!((r.evaluate(&stats) / kqmc.evaluate(&stats) - 0.3 / 0.9).abs() < 0.001);
+        assert!(r.gradients(&stats).is_some());
+    }
+
+    #[test]
+    fn rotation_macro_reaction_mix() {
+        use crate::core::aura::Reaction;
+        let stats = StatTable::of(&[
+            (Stat::BaseATK, 844.85),
+            (Stat::CritRate, 0.6),
+            (Stat::CritDMG, 1.2),
+            (Stat::ElementalMastery, 100.0),
+        ]);
+        let mixed = rotation! {
+            ("n1", Element::Pyro, DamageType::Normal, BaseScaling::ATK, [Reaction::Vaporize => 0.7], 1.0, 10.0, None),
+            ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+        }.unwrap();
+        let split = rotation! {
+            ("vaped", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::Reverse, 1.0, 7.0, None),
+            ("unreacted", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 3.0, None),
+            ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+        }.unwrap();
+        assert!((mixed.evaluate(&stats) - split.evaluate(&stats)).abs() < 0.1);
+        assert!(mixed.is_differentiable());
+    }
+
+    #[test]
+    fn rotation_macro_invalid_reaction_mix() {
+        use crate::core::aura::Reaction;
+        let wrong_element = rotation! {
+            ("n1", Element::Cryo, DamageType::Normal, BaseScaling::ATK, [Reaction::Vaporize => 0.7], 1.0, 10.0, None),
+        };
+        assert!(wrong_element.is_err());
+        let too_many = rotation! {
+            ("n1", Element::Pyro, DamageType::Normal, BaseScaling::ATK, [Reaction::Vaporize => 0.7, Reaction::Overloaded => 0.7], 1.0, 10.0, None),
+        };
+        assert!(too_many.is_err());
+    }
 }
 
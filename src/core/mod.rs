@@ -3,4 +3,17 @@ pub mod stattable;
 pub mod rotation;
 pub mod artifact;
 pub mod artifact_builder;
-pub mod macros;
\ No newline at end of file
+pub mod macros;
+pub mod constraint;
+pub mod dual;
+pub mod calc_standard;
+pub mod aura;
+pub mod timeline;
+pub mod reaction_mix;
+pub mod rotation_spec;
+pub mod buff;
+pub mod simulator;
+pub mod shred;
+pub mod enemy;
+pub mod scenario;
+pub mod encounter;
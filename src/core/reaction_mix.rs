@@ -0,0 +1,110 @@
+use crate::core::aura::Reaction;
+use crate::core::types::Element;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// share of an action's instances that trigger each reaction, the remaining share is unreacted
+/// eg: 70% of hits vaporized, or 2 of 5 hits spread
+/// in json a list of [reaction, share] pairs, eg: [["Vaporize", 0.7]]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "Vec<(Reaction, f32)>", into = "Vec<(Reaction, f32)>")]
+pub struct ReactionMix {
+    shares: Vec<(Reaction, f32)>,
+}
+
+impl ReactionMix {
+    /// every instance is unreacted
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// every instance triggers a reaction
+    pub fn all(reaction: Reaction) -> Self {
+        Self::new().with(reaction, 1.0)
+    }
+
+    /// adds a share of instances that trigger a reaction
+    /// panics if the share is not in [0, 1] or the total share goes over 1
+    pub fn with(self, reaction: Reaction, share: f32) -> Self {
+        self.try_with(reaction, share).unwrap_or_else(|e| panic!("{e}"))
+    }
+
+    pub fn try_with(mut self, reaction: Reaction, share: f32) -> Result<Self> {
+        if !(0.0..=1.0).contains(&share) {
+            return Err(anyhow!("reaction share {share} is not between 0 and 1"));
+        }
+        if self.reacted_share() + share > 1.0 + 1e-6 {
+            return Err(anyhow!("reaction shares add up to more than 1"));
+        }
+        match self.shares.iter_mut().find(|(r, _)| *r == reaction) {
+            Some((_, s)) => *s += share,
+            None => self.shares.push((reaction, share)),
+        }
+        Ok(self)
+    }
+
+    /// the mix as triggered by hits of an element, see Reaction::triggered_by
+    pub fn triggered_by(&self, element: Element) -> Result<Self> {
+        self.shares.iter().try_fold(ReactionMix::new(), |mix, (reaction, share)| mix.try_with(reaction.triggered_by(element)?, *share))
+    }
+
+    /// share of instances that trigger any reaction
+    pub fn reacted_share(&self) -> f32 {
+        self.shares.iter().filter(|(r, _)| *r != Reaction::None).map(|(_, s)| s).sum()
+    }
+
+    /// every reaction with its share, including the unreacted remainder as Reaction::None
+    pub fn shares(&self) -> Vec<(Reaction, f32)> {
+        let mut shares: Vec<(Reaction, f32)> = self.shares.iter().filter(|(r, _)| *r != Reaction::None).cloned().collect();
+        let unreacted = (1.0 - self.reacted_share()).max(0.0);
+        if unreacted > 0.0 {
+            shares.push((Reaction::None, unreacted));
+        }
+        shares
+    }
+}
+
+impl TryFrom<Vec<(Reaction, f32)>> for ReactionMix {
+    type Error = anyhow::Error;
+    fn try_from(shares: Vec<(Reaction, f32)>) -> Result<Self> {
+        shares.into_iter().try_fold(ReactionMix::new(), |mix, (reaction, share)| mix.try_with(reaction, share))
+    }
+}
+
+impl From<ReactionMix> for Vec<(Reaction, f32)> {
+    fn from(mix: ReactionMix) -> Self {
+        mix.shares
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn unreacted_remainder() {
+        let mix = ReactionMix::new().with(Reaction::Vaporize, 0.7);
+        assert_eq!(mix.shares(), vec![(Reaction::Vaporize, 0.7), (Reaction::None, 0.3)]);
+        assert_eq!(ReactionMix::new().shares(), vec![(Reaction::None, 1.0)]);
+        assert_eq!(ReactionMix::all(Reaction::Spread).shares(), vec![(Reaction::Spread, 1.0)]);
+    }
+
+    #[test] fn invalid_shares_are_errors() {
+        assert!(ReactionMix::new().try_with(Reaction::Melt, 1.5).is_err());
+        assert!(ReactionMix::new().with(Reaction::Melt, 0.6).try_with(Reaction::Vaporize, 0.6).is_err());
+    }
+
+    #[test] fn triggers_pick_the_reaction_direction() {
+        let mix = ReactionMix::new().with(Reaction::Vaporize, 0.5).with(Reaction::ReverseVaporize, 0.2);
+        let shares = mix.triggered_by(Element::Pyro).unwrap().shares();
+        assert_eq!(shares.len(), 2);
+        assert_eq!(shares[0].0, Reaction::ReverseVaporize);
+        assert!((shares[0].1 - 0.7).abs() < 1e-6);
+        assert!(ReactionMix::all(Reaction::Melt).triggered_by(Element::Electro).is_err());
+    }
+
+    #[test] fn json_round_trip() {
+        let mix: ReactionMix = serde_json::from_str(r#"[["Spread", 0.4]]"#).unwrap();
+        assert_eq!(mix, ReactionMix::new().with(Reaction::Spread, 0.4));
+        assert_eq!(serde_json::to_string(&mix).unwrap(), r#"[["Spread",0.4]]"#);
+        assert!(serde_json::from_str::<ReactionMix>(r#"[["Spread", 2.0]]"#).is_err());
+    }
+}
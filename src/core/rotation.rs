@@ -1,32 +1,175 @@
 use crate::core::stattable::StatTable;
+use crate::core::calc_standard::CalcStandard;
+use crate::core::dual::{Dual, DualStatTable};
+use crate::core::reaction_mix::ReactionMix;
+use crate::core::types::{Amplifier, BaseScaling, DamageType, Element, Stat, ALL_STATS};
+use crate::functions::dmg_function::DMGFunction;
+use crate::functions::support_function::{DefenseFunction, HealFunction, ShieldFunction, ShieldSource};
+use crate::functions::crit_distribution::{rotation_distribution, DamageDistribution, DistributionMode};
 
 pub type Operation = Box<dyn Fn(&StatTable) -> f32 + 'static>;
+/// an operation evaluated on dual numbers, gives the exact stat gradients of an action
+pub type DualOperation = Box<dyn Fn(&DualStatTable) -> Dual + 'static>;
 
 /// represents a sequence of action performed by a 
 pub struct Rotation {        
     inner: std::collections::HashMap<String, Operation>,
+    differentiable: std::collections::HashMap<String, DualOperation>,
+    hits: std::collections::HashMap<String, u32>, // number of hits that roll crit independently per action
+    standard: CalcStandard, // standard the damage actions built by the rotation are evaluated under
 }
 
 impl Rotation {
-    /// construct a new empty rotation
+    /// construct a new empty rotation under the kqmc standard
     pub fn new() -> Self {
-        Self{inner: std::collections::HashMap::new()}
+        Self::with_standard(&CalcStandard::KQMC)
+    }
+
+    /// construct a new empty rotation whose damage actions are evaluated under a calc standard
+    /// the standard is only recorded, closures added with add or add_differentiable are trusted to use it
+    pub fn with_standard(standard: &CalcStandard) -> Self {
+        Self{inner: std::collections::HashMap::new(), differentiable: std::collections::HashMap::new(), hits: std::collections::HashMap::new(), standard: standard.clone()}
     }
     /// construct a new rotation with default values
+    /// closures have no exact gradients, see add
     pub fn of(actions: Vec<(String, Operation)>) -> Self {
         let mut map = std::collections::HashMap::new();
         for (k, v) in actions {
             map.insert(k, v);
         }
-        Self{inner: map}
+        Self{inner: map, differentiable: std::collections::HashMap::new(), hits: std::collections::HashMap::new(), standard: CalcStandard::KQMC}
+    }
+
+    pub fn standard(&self) -> &CalcStandard {
+        &self.standard
     }
 
     /// add an action to the rotation
+    /// a plain closure has no exact gradients, optimizers fall back to finite differences on the whole rotation
+    /// use add_damage, the other built in actions or add_differentiable for exact gradients
     pub fn add(&mut self, name: String, action: Operation) -> &mut Self {
+        self.differentiable.remove(&name);
+        self.hits.remove(&name);
         self.inner.insert(name, action);
         self
     }
 
+    /// add an action along with its dual number counterpart for exact gradients
+    pub fn add_differentiable(&mut self, name: String, action: Operation, dual_action: DualOperation) -> &mut Self {
+        self.differentiable.insert(name.clone(), dual_action);
+        self.inner.insert(name, action);
+        self
+    }
+
+    /// add a damage action evaluated under the rotation's standard, with exact gradients
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_damage(&mut self, name: &str, element: Element, damage_type: DamageType, scaling: BaseScaling, amplifier: Amplifier, motion_value: f32, instances: f32, buffs: Option<StatTable>) -> &mut Self {
+        let dual_buffs = buffs.clone();
+        let (standard, dual_standard) = (self.standard.clone(), self.standard.clone());
+        self.add_differentiable(
+            String::from(name),
+            Box::new(move |s| DMGFunction::calculate_damage_generic(element, damage_type, scaling, amplifier, instances, motion_value, s, buffs.as_ref(), &standard)),
+            Box::new(move |s| DMGFunction::calculate_damage_generic(element, damage_type, scaling, amplifier, instances, motion_value, s, dual_buffs.as_ref(), &dual_standard)),
+        );
+        self.set_hits(name, instances.round() as u32)
+    }
+
+    /// add a damage action whose instances are split between reactions, errors if the mix cannot be triggered by the element
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_damage_with_reactions(&mut self, name: &str, element: Element, damage_type: DamageType, scaling: BaseScaling, reactions: &ReactionMix, motion_value: f32, instances: f32, buffs: Option<StatTable>) -> anyhow::Result<&mut Self> {
+        let reactions = reactions.triggered_by(element)?;
+        let dual_reactions = reactions.clone();
+        let dual_buffs = buffs.clone();
+        let (standard, dual_standard) = (self.standard.clone(), self.standard.clone());
+        self.add_differentiable(
+            String::from(name),
+            Box::new(move |s| DMGFunction::calculate_damage_with_reactions_generic(element, damage_type, scaling, &reactions, instances, motion_value, s, buffs.as_ref(), &standard)),
+            Box::new(move |s| DMGFunction::calculate_damage_with_reactions_generic(element, damage_type, scaling, &dual_reactions, instances, motion_value, s, dual_buffs.as_ref(), &dual_standard)),
+        );
+        Ok(self.set_hits(name, instances.round() as u32))
+    }
+
+    /// add a healing action, the rotation value of the action is the total healing
+    pub fn add_healing(&mut self, name: &str, scaling: BaseScaling, motion_value: f32, flat: f32, instances: f32, buffs: Option<StatTable>) -> &mut Self {
+        let dual_buffs = buffs.clone();
+        self.add_differentiable(
+            String::from(name),
+            Box::new(move |s| HealFunction::calculate_healing(scaling, instances, motion_value, flat, s, buffs.as_ref())),
+            Box::new(move |s| HealFunction::calculate_healing(scaling, instances, motion_value, flat, s, dual_buffs.as_ref())),
+        )
+    }
+
+    /// add a shield action, the rotation value of the action is the damage of an element the shield absorbs
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_shield(&mut self, name: &str, shield_element: Element, damage_element: Element, scaling: BaseScaling, motion_value: f32, flat: f32, buffs: Option<StatTable>) -> &mut Self {
+        let dual_buffs = buffs.clone();
+        self.add_differentiable(
+            String::from(name),
+            Box::new(move |s| ShieldFunction::calculate_effective_shield(shield_element, damage_element, scaling, motion_value, flat, s, buffs.as_ref())),
+            Box::new(move |s| ShieldFunction::calculate_effective_shield(shield_element, damage_element, scaling, motion_value, flat, s, dual_buffs.as_ref())),
+        )
+    }
+
+    /// add an effective hp action, the rotation value of the action is the raw enemy damage of an element the character survives
+    pub fn add_effective_hp(&mut self, name: &str, element: Element, enemy_level: i8, shield: Option<ShieldSource>, buffs: Option<StatTable>) -> &mut Self {
+        let dual_buffs = buffs.clone();
+        self.add_differentiable(
+            String::from(name),
+            Box::new(move |s| DefenseFunction::calculate_effective_hp(element, enemy_level, shield.as_ref(), s, buffs.as_ref())),
+            Box::new(move |s| DefenseFunction::calculate_effective_hp(element, enemy_level, shield.as_ref(), s, dual_buffs.as_ref())),
+        )
+    }
+
+    /// add a damage taken action, the rotation value of the action is minus the damage taken so maximizing it minimizes damage taken
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_damage_taken(&mut self, name: &str, element: Element, enemy_level: i8, enemy_atk: f32, motion_value: f32, buffs: Option<StatTable>) -> &mut Self {
+        let dual_buffs = buffs.clone();
+        self.add_differentiable(
+            String::from(name),
+            Box::new(move |s| -DefenseFunction::calculate_damage_taken(element, enemy_level, enemy_atk, motion_value, s, buffs.as_ref())),
+            Box::new(move |s| -DefenseFunction::calculate_damage_taken(element, enemy_level, enemy_atk, motion_value, s, dual_buffs.as_ref())),
+        )
+    }
+
+    /// sets the number of hits of an action that roll crit independently
+    pub fn set_hits(&mut self, name: &str, hits: u32) -> &mut Self {
+        self.hits.insert(String::from(name), hits.max(1));
+        self
+    }
+
+    /// number of hits of an action that roll crit independently, 1 if not set
+    pub fn hits(&self, name: &str) -> u32 {
+        self.hits.get(name).cloned().unwrap_or(1)
+    }
+
+    /// names of all actions in the rotation
+    pub fn action_names(&self) -> impl Iterator<Item = &String> {
+        self.inner.keys()
+    }
+
+    /// compute the result of a single action, none if the action is not in the rotation
+    pub fn evaluate_action(&self, name: &str, stats: &StatTable) -> Option<f32> {
+        self.inner.get(name).map(|f| f(stats))
+    }
+
+    /// true if every action has a dual number counterpart
+    pub fn is_differentiable(&self) -> bool {
+        self.inner.keys().all(|k| self.differentiable.contains_key(k))
+    }
+
+    /// exact partial derivatives of the rotation with respect to every stat
+    /// returns none if any action is not differentiable
+    pub fn gradients(&self, stats: &StatTable) -> Option<std::collections::HashMap<Stat, f32>> {
+        if !self.is_differentiable() {
+            return None;
+        }
+        let duals = DualStatTable::seed(stats);
+        let total = self.differentiable.values()
+            .map(|f| f(&duals))
+            .fold(<Dual as crate::core::dual::Numeric>::constant(0.0), |acc, x| acc + x);
+        Some(ALL_STATS.iter().map(|s| (*s, total.derivative(s))).collect())
+    }
+
     /// compute and return sum of all actions based on a given stat table instance
     pub fn evaluate(&self, stats: &StatTable) -> f32 {
         self.inner.iter()
@@ -34,12 +177,17 @@ impl Rotation {
             .sum()
     }
 
+    /// distribution of total damage where each hit's crit is a bernoulli trial
+    pub fn evaluate_distribution(&self, stats: &StatTable, mode: DistributionMode) -> anyhow::Result<DamageDistribution> {
+        rotation_distribution(self, stats, mode)
+    }
+
     /// Create a copy of this rotation
     /// Note: This is a shallow copy that recreates the operations
     pub fn copy(&self) -> Self {
         // Since we can't clone Box<dyn Fn>, we'll create a new empty rotation
         // The WASM bindings will need to handle this differently
-        Self::new()
+        Self::with_standard(&self.standard)
     }
 }
 
@@ -63,4 +211,45 @@ impl Rotation {
         let res = r.evaluate(&s);
         debug_assert_eq!(res, 1.6*2.0)
     }
-}
\ No newline at end of file
+
+    #[test] fn gradients_require_every_action_to_be_differentiable() {
+        let mut r = Rotation::new();
+        r.add_differentiable(
+            String::from("double atk"),
+            Box::new(|stats| 2.0 * stats.get(&Stat::FlatATK)),
+            Box::new(|stats| {
+                use crate::core::stattable::StatLookup;
+                let two = <Dual as crate::core::dual::Numeric>::constant(2.0);
+                two * stats.get(&Stat::FlatATK)
+            }),
+        );
+        let s = StatTable::of(&[(Stat::FlatATK, 10.0)]);
+        let grads = r.gradients(&s).unwrap();
+        assert_eq!(grads[&Stat::FlatATK], 2.0);
+        assert_eq!(grads[&Stat::CritRate], 0.0);
+
+        r.add(String::from("plain"), Box::new(|stats| stats.get(&Stat::FlatATK)));
+        assert!(!r.is_differentiable());
+        assert!(r.gradients(&s).is_none());
+    }
+
+    #[test] fn damage_actions_are_differentiable() {
+        let mut r = Rotation::new();
+        r.add_damage("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 2.0, 3.0, None);
+        let s = StatTable::of(&[(Stat::BaseATK, 1000.0), (Stat::CritRate, 0.5), (Stat::CritDMG, 1.0)]);
+        assert_eq!(r.evaluate(&s), DMGFunction::calculate_damage(Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 3.0, 2.0, &s, None));
+        assert_eq!(r.hits("skill"), 3);
+        assert!(r.gradients(&s).unwrap()[&Stat::ATKPercent] > 0.0);
+    }
+
+    #[test] fn damage_taken_is_minimized_by_def_and_res() {
+        let mut r = Rotation::new();
+        r.add_damage_taken("enemy slam", Element::Physical, 100, 1000.0, 2.0, None);
+        let s = StatTable::of(&[(Stat::BaseDEF, 1000.0)]);
+        assert_eq!(r.evaluate(&s), -1000.0);
+        let grads = r.gradients(&s).unwrap();
+        assert!(grads[&Stat::DEFPercent] > 0.0);
+        assert!(grads[&Stat::PhysicalRES] > 0.0);
+        assert_eq!(grads[&Stat::PyroRES], 0.0);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::core::aura::Reaction;
+use crate::core::calc_standard::CalcStandard;
+use crate::core::dual::{Dual, Numeric};
+use crate::core::reaction_mix::ReactionMix;
+use crate::core::rotation::Rotation;
+use crate::core::stattable::StatTable;
+use crate::core::types::{BaseScaling, DamageType, Element, Stat};
+use crate::functions::dmg_function::DMGFunction;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// data driven damage action
+/// eg: {"name": "n1", "element": "Pyro", "damage_type": "Normal", "scaling": "ATK", "motion_value": 0.8, "instances": 5, "reactions": [["Vaporize", 0.4]]}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionSpec {
+    pub name: String,
+    pub element: Element,
+    pub damage_type: DamageType,
+    pub scaling: BaseScaling,
+    pub motion_value: f32,
+    #[serde(default = "one")]
+    pub instances: f32,
+    #[serde(default)]
+    pub reactions: ReactionMix,
+    #[serde(default)]
+    pub buffs: Vec<(Stat, f32)>,
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+impl ActionSpec {
+    fn buffs(&self) -> Option<StatTable> {
+        if self.buffs.is_empty() { None } else { Some(StatTable::of(&self.buffs)) }
+    }
+
+    /// damage of the action split by reaction
+    pub fn breakdown(&self, stats: &StatTable, standard: &CalcStandard) -> Vec<(Reaction, f32)> {
+        DMGFunction::reaction_breakdown_generic(self.element, self.damage_type, self.scaling, &self.reactions, self.instances, self.motion_value, stats, self.buffs().as_ref(), standard)
+    }
+}
+
+/// data driven rotation of damage actions
+/// eg: {"actions": [...]}
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "RawRotationSpec")]
+pub struct RotationSpec {
+    pub actions: Vec<ActionSpec>,
+}
+
+#[derive(Deserialize)]
+struct RawRotationSpec {
+    actions: Vec<ActionSpec>,
+}
+
+impl TryFrom<RawRotationSpec> for RotationSpec {
+    type Error = anyhow::Error;
+
+    /// vaporize and melt take their direction from the element of the action
+    fn try_from(raw: RawRotationSpec) -> Result<Self> {
+        let actions = raw.actions.into_iter()
+            .map(|mut action| {
+                action.reactions = action.reactions.triggered_by(action.element).with_context(|| format!("invalid reactions for {}", action.name))?;
+                Ok(action)
+            })
+            .collect::<Result<Vec<ActionSpec>>>()?;
+        Ok(Self { actions })
+    }
+}
+
+impl RotationSpec {
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("failed to parse rotation")
+    }
+
+    /// builds a differentiable rotation, actions with the same name are merged into one
+    pub fn to_rotation(&self, standard: &CalcStandard) -> Rotation {
+        let mut rotation = Rotation::with_standard(standard);
+        let mut names: Vec<&String> = Vec::new();
+        for action in &self.actions {
+            if !names.contains(&&action.name) {
+                names.push(&action.name);
+            }
+        }
+        for name in names {
+            let actions: Vec<ActionSpec> = self.actions.iter().filter(|a| &a.name == name).cloned().collect();
+            let dual_actions = actions.clone();
+            let (standard, dual_standard) = (standard.clone(), standard.clone());
+            let hits = actions.iter().map(|a| a.instances.round() as u32).sum();
+            rotation.add_differentiable(
+                name.clone(),
+                Box::new(move |s| actions.iter()
+                    .map(|a| DMGFunction::calculate_damage_with_reactions_generic(a.element, a.damage_type, a.scaling, &a.reactions, a.instances, a.motion_value, s, a.buffs().as_ref(), &standard))
+                    .sum()),
+                Box::new(move |s| dual_actions.iter()
+                    .map(|a| DMGFunction::calculate_damage_with_reactions_generic(a.element, a.damage_type, a.scaling, &a.reactions, a.instances, a.motion_value, s, a.buffs().as_ref(), &dual_standard))
+                    .fold(Dual::constant(0.0), |acc, x| acc + x)),
+            );
+            rotation.set_hits(name, hits);
+        }
+        rotation
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::types::Amplifier;
+    use crate::assert_aprx;
+
+    fn stats() -> StatTable {
+        StatTable::of(&[
+            (Stat::BaseATK, 1000.0),
+            (Stat::CritRate, 0.5),
+            (Stat::CritDMG, 1.0),
+            (Stat::ElementalMastery, 100.0),
+        ])
+    }
+
+    #[test] fn json_rotation_splits_instances_by_reaction() {
+        let spec = RotationSpec::from_json(r#"{"actions": [
+            {"name": "n1", "element": "Pyro", "damage_type": "Normal", "scaling": "ATK", "motion_value": 0.8, "instances": 5, "reactions": [["Vaporize", 0.4]]},
+            {"name": "skill", "element": "Pyro", "damage_type": "Skill", "scaling": "ATK", "motion_value": 2.0, "buffs": [["CritRate", 0.2]]}
+        ]}"#).unwrap();
+        assert_eq!(spec.actions[1].instances, 1.0);
+        assert_eq!(spec.actions[1].reactions, ReactionMix::new());
+
+        let n1 = |amplifier, instances| DMGFunction::calculate_damage(Element::Pyro, DamageType::Normal, BaseScaling::ATK, amplifier, instances, 0.8, &stats(), None);
+        let breakdown = spec.actions[0].breakdown(&stats(), &CalcStandard::KQMC);
+        assert_eq!(breakdown.len(), 2);
+        assert_eq!(spec.actions[0].reactions.shares()[0].0, Reaction::ReverseVaporize);
+        assert_aprx!(breakdown[0].1, n1(Amplifier::Reverse, 2.0), 0.01);
+        assert_aprx!(breakdown[1].1, n1(Amplifier::None, 3.0), 0.01);
+
+        let rotation = spec.to_rotation(&CalcStandard::KQMC);
+        assert_aprx!(rotation.evaluate_action("n1", &stats()).unwrap(), n1(Amplifier::Reverse, 2.0) + n1(Amplifier::None, 3.0), 0.1);
+        assert_eq!(rotation.hits("n1"), 5);
+        assert!(rotation.is_differentiable());
+    }
+
+    #[test] fn invalid_json_is_error() {
+        assert!(RotationSpec::from_json(r#"{"actions": [{"name": "n1"}]}"#).is_err());
+        assert!(RotationSpec::from_json(r#"{"actions": [{"name": "n1", "element": "Pyro", "damage_type": "Normal", "scaling": "ATK", "motion_value": 1.0, "reactions": [["Melt", 0.8], ["Vaporize", 0.8]]}]}"#).is_err());
+        assert!(RotationSpec::from_json(r#"{"actions": [{"name": "n1", "element": "Electro", "damage_type": "Normal", "scaling": "ATK", "motion_value": 1.0, "reactions": [["Melt", 0.5]]}]}"#).is_err());
+    }
+}
@@ -0,0 +1,216 @@
+use crate::core::calc_standard::CalcStandard;
+use crate::core::enemy::Enemy;
+use crate::core::rotation::Rotation;
+use crate::core::stattable::StatTable;
+use crate::core::types::{Amplifier, BaseScaling, DamageType, Element};
+use crate::functions::dmg_function::DMGFunction;
+
+/// enemies an action hits
+#[derive(Debug, Clone, PartialEq)]
+pub enum Targets {
+    Count(usize), // the first n enemies of the scenario
+    Named(Vec<String>),
+    All,
+}
+
+impl Targets {
+    fn hits(&self, index: usize, enemy: &Enemy) -> bool {
+        match self {
+            Targets::Count(n) => index < *n,
+            Targets::Named(names) => names.contains(&enemy.name),
+            Targets::All => true,
+        }
+    }
+}
+
+/// a damage action of a scenario, hits every enemy its targets select
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenarioAction {
+    pub name: String,
+    pub element: Element,
+    pub damage_type: DamageType,
+    pub scaling: BaseScaling,
+    pub amplifier: Amplifier,
+    pub motion_value: f32,
+    pub instances: f32,
+    pub buffs: Option<StatTable>,
+    pub targets: Targets,
+}
+
+impl ScenarioAction {
+    /// single target action
+    pub fn new(name: &str, element: Element, damage_type: DamageType, scaling: BaseScaling, motion_value: f32, instances: f32) -> Self {
+        Self { name: String::from(name), element, damage_type, scaling, amplifier: Amplifier::None, motion_value, instances, buffs: None, targets: Targets::Count(1) }
+    }
+
+    pub fn with_amplifier(mut self, amplifier: Amplifier) -> Self {
+        self.amplifier = amplifier;
+        self
+    }
+
+    pub fn with_buffs(mut self, buffs: StatTable) -> Self {
+        self.buffs = Some(buffs);
+        self
+    }
+
+    pub fn with_targets(mut self, targets: Targets) -> Self {
+        self.targets = targets;
+        self
+    }
+
+    /// damage of the action against an enemy
+    pub fn damage(&self, stats: &StatTable, enemy: &Enemy, standard: &CalcStandard) -> f32 {
+        DMGFunction::calculate_damage_generic(self.element, self.damage_type, self.scaling, self.amplifier, self.instances, self.motion_value, stats, self.buffs.as_ref(), &enemy.standard(self.element, standard))
+    }
+}
+
+/// damage dealt to one enemy of a scenario
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetDamage {
+    pub name: String,
+    pub damage: f32, // capped at the enemy's hp
+    pub killed: bool,
+}
+
+/// damage of a scenario, single target is the damage to the first (primary) enemy, aoe is the damage to every enemy
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScenarioResult {
+    pub targets: Vec<TargetDamage>,
+    pub single_target: f32,
+    pub aoe: f32,
+    pub overkill: f32, // damage past the hp of dead enemies
+}
+
+/// a fight against several enemies at once
+/// eg: a boss (single target) versus a pack of mobs (aoe)
+pub struct Scenario {
+    enemies: Vec<Enemy>,
+    actions: Vec<ScenarioAction>, // in order, for hp pools
+    standard: CalcStandard,
+}
+
+impl Scenario {
+    pub fn new(standard: &CalcStandard) -> Self {
+        Self { enemies: Vec::new(), actions: Vec::new(), standard: standard.clone() }
+    }
+
+    /// adds an enemy, the first enemy added is the primary target
+    pub fn add_enemy(&mut self, enemy: Enemy) -> &mut Self {
+        self.enemies.push(enemy);
+        self
+    }
+
+    pub fn add(&mut self, action: ScenarioAction) -> &mut Self {
+        self.actions.push(action);
+        self
+    }
+
+    pub fn enemies(&self) -> &[Enemy] {
+        &self.enemies
+    }
+
+    /// runs the actions in order, damage to an enemy past its hp is counted as overkill
+    pub fn evaluate(&self, stats: &StatTable) -> ScenarioResult {
+        let mut targets: Vec<TargetDamage> = self.enemies.iter()
+            .map(|e| TargetDamage { name: e.name.clone(), damage: 0.0, killed: false })
+            .collect();
+        let mut overkill = 0.0;
+        for action in &self.actions {
+            for (i, enemy) in self.enemies.iter().enumerate().filter(|(i, e)| action.targets.hits(*i, e)) {
+                let damage = action.damage(stats, enemy, &self.standard);
+                let target = &mut targets[i];
+                let dealt = match enemy.hp {
+                    Some(hp) => damage.min(hp - target.damage),
+                    None => damage,
+                };
+                overkill += damage - dealt;
+                target.damage += dealt;
+                target.killed = enemy.hp.is_some_and(|hp| target.damage >= hp);
+            }
+        }
+        ScenarioResult {
+            single_target: targets.first().map(|x| x.damage).unwrap_or(0.0),
+            aoe: targets.iter().map(|x| x.damage).sum(),
+            targets,
+            overkill,
+        }
+    }
+
+    /// rotation of the damage to every enemy (or only the primary enemy) ignoring hp, so it can be optimized
+    /// actions are named "{action} @ {enemy}"
+    pub fn to_rotation(&self, single_target: bool) -> Rotation {
+        let mut rotation = Rotation::with_standard(&self.standard);
+        for action in &self.actions {
+            for (i, enemy) in self.enemies.iter().enumerate().filter(|(i, e)| action.targets.hits(*i, e)) {
+                if single_target && i > 0 {
+                    continue;
+                }
+                let standard = enemy.standard(action.element, &self.standard);
+                let (a, d) = (action.clone(), action.clone());
+                let dual_standard = standard.clone();
+                rotation.add_differentiable(
+                    format!("{} @ {}", action.name, enemy.name),
+                    Box::new(move |s| DMGFunction::calculate_damage_generic(a.element, a.damage_type, a.scaling, a.amplifier, a.instances, a.motion_value, s, a.buffs.as_ref(), &standard)),
+                    Box::new(move |s| DMGFunction::calculate_damage_generic(d.element, d.damage_type, d.scaling, d.amplifier, d.instances, d.motion_value, s, d.buffs.as_ref(), &dual_standard)),
+                );
+            }
+        }
+        rotation
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::types::Stat;
+    use crate::assert_aprx;
+
+    fn stats() -> StatTable {
+        StatTable::of(&[
+            (Stat::BaseATK, 1000.0),
+            (Stat::CritRate, 0.5),
+            (Stat::CritDMG, 1.0),
+        ])
+    }
+
+    fn mobs() -> Scenario {
+        let mut scenario = Scenario::new(&CalcStandard::KQMC);
+        scenario.add_enemy(Enemy::from_standard("boss", &CalcStandard::KQMC));
+        scenario.add_enemy(Enemy::from_standard("mob 1", &CalcStandard::KQMC));
+        scenario.add_enemy(Enemy::from_standard("mob 2", &CalcStandard::KQMC).with_element_resistance(Element::Pyro, 0.7));
+        scenario
+    }
+
+    #[test] fn aoe_and_single_target_are_reported_separately() {
+        let mut scenario = mobs();
+        scenario.add(ScenarioAction::new("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, 2.0, 1.0).with_targets(Targets::All));
+        scenario.add(ScenarioAction::new("burst", Element::Pyro, DamageType::Burst, BaseScaling::ATK, 4.0, 1.0));
+        let result = scenario.evaluate(&stats());
+
+        let one = DMGFunction::calculate_damage(Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 2.0, &stats(), None);
+        assert_aprx!(result.single_target, 3.0 * one, 0.1);
+        // the pyro slime takes 0.3 / 0.9 of the damage
+        assert_aprx!(result.aoe, 3.0 * one + one + one * 0.3 / 0.9, 0.1);
+        assert_aprx!(scenario.to_rotation(false).evaluate(&stats()), result.aoe, 0.1);
+        assert_aprx!(scenario.to_rotation(true).evaluate(&stats()), result.single_target, 0.1);
+    }
+
+    #[test] fn actions_hit_selected_targets() {
+        let mut scenario = mobs();
+        scenario.add(ScenarioAction::new("cleave", Element::Physical, DamageType::Normal, BaseScaling::ATK, 1.0, 1.0).with_targets(Targets::Count(2)));
+        scenario.add(ScenarioAction::new("snipe", Element::Physical, DamageType::Charged, BaseScaling::ATK, 1.0, 1.0).with_targets(Targets::Named(vec![String::from("mob 2")])));
+        let result = scenario.evaluate(&stats());
+        let damage: Vec<f32> = result.targets.iter().map(|x| x.damage).collect();
+        assert!(damage[0] > 0.0 && damage[1] > 0.0);
+        assert_aprx!(damage[2], damage[0], 0.1);
+    }
+
+    #[test] fn hp_pools_cap_damage() {
+        let mut scenario = Scenario::new(&CalcStandard::KQMC);
+        scenario.add_enemy(Enemy::from_standard("mob", &CalcStandard::KQMC).with_hp(1000.0));
+        scenario.add(ScenarioAction::new("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, 2.0, 1.0));
+        let result = scenario.evaluate(&stats());
+        assert!(result.targets[0].killed);
+        assert_eq!(result.aoe, 1000.0);
+        assert!(result.overkill > 0.0);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::core::calc_standard::CalcStandard;
+use crate::core::stattable::StatTable;
+use crate::core::types::{Debuffs, Element};
+
+/// elements with an enemy res
+pub const RESISTANCE_ELEMENTS: [Element; 8] = [
+    Element::Pyro,
+    Element::Hydro,
+    Element::Electro,
+    Element::Anemo,
+    Element::Geo,
+    Element::Dendro,
+    Element::Cryo,
+    Element::Physical,
+];
+
+/// a def or res shred from a source
+/// eg: Shred::new("superconduct", Debuffs::PhysicalResistanceReduction, 0.4)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shred {
+    pub source: String, // source id, copies of the same source do not stack
+    pub debuff: Debuffs,
+    pub value: f32,
+}
+
+impl Shred {
+    pub fn new(source: &str, debuff: Debuffs, value: f32) -> Self {
+        Self { source: String::from(source), debuff, value }
+    }
+
+    /// res shred of every element from one source (eg: zhongli's shield)
+    pub fn all_resistance(source: &str, value: f32) -> Vec<Self> {
+        RESISTANCE_ELEMENTS.iter()
+            .filter_map(|e| Debuffs::resistance_reduction(*e))
+            .map(|debuff| Self::new(source, debuff, value))
+            .collect()
+    }
+}
+
+/// shreds with duplicate sources removed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedShred {
+    pub stats: StatTable, // summed shred stats of distinct sources, can be chained as buffs
+    pub warnings: Vec<String>, // one per source that was counted more than once
+}
+
+impl ResolvedShred {
+    /// effective res of the enemy against an element
+    pub fn resistance(&self, element: Element, standard: &CalcStandard) -> f32 {
+        match Debuffs::resistance_reduction(element) {
+            Some(debuff) => standard.enemy_resistance - self.stats.get(&debuff.stat()),
+            None => 0.0,
+        }
+    }
+
+    /// effective res of the enemy against every element
+    pub fn resistances(&self, standard: &CalcStandard) -> std::collections::HashMap<Element, f32> {
+        RESISTANCE_ELEMENTS.iter().map(|e| (*e, self.resistance(*e, standard))).collect()
+    }
+
+    /// effective def of the enemy: (5 x level + 500) x (1 - def reduction) x (1 - def ignore)
+    /// def reduction is clamped at 0.9 like formulas::def_multiplier
+    pub fn enemy_def(&self, standard: &CalcStandard) -> f32 {
+        let def_reduction = self.stats.get(&Debuffs::DefReduction.stat()).min(0.9);
+        let def_ignore = self.stats.get(&Debuffs::DefIgnore.stat());
+        (5.0 * standard.enemy_level as f32 + 500.0) * (1.0 - def_reduction) * (1.0 - def_ignore)
+    }
+}
+
+/// de-duplicates shreds by source: the strongest shred of a source and debuff is kept, distinct sources stack
+pub fn resolve_shred(shreds: &[Shred]) -> ResolvedShred {
+    let mut strongest: Vec<&Shred> = Vec::new();
+    let mut warnings = Vec::new();
+    for shred in shreds {
+        match strongest.iter_mut().find(|x| x.source == shred.source && x.debuff == shred.debuff) {
+            Some(kept) => {
+                let warning = format!("shred source {} is counted more than once, only the strongest copy applies", shred.source);
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+                if shred.value > kept.value {
+                    *kept = shred;
+                }
+            },
+            None => strongest.push(shred),
+        }
+    }
+    let mut stats = StatTable::new();
+    for shred in strongest {
+        stats.add(&shred.debuff.stat(), shred.value);
+    }
+    ResolvedShred { stats, warnings }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::types::Stat;
+    use crate::assert_aprx;
+
+    #[test] fn distinct_sources_stack() {
+        let mut shreds = vec![
+            Shred::new("superconduct", Debuffs::PhysicalResistanceReduction, 0.4),
+            Shred::new("viridescent venerer", Debuffs::PyroResistanceReduction, 0.4),
+        ];
+        shreds.extend(Shred::all_resistance("zhongli shield", 0.2));
+        let resolved = resolve_shred(&shreds);
+        assert!(resolved.warnings.is_empty());
+        assert_aprx!(resolved.stats.get(&Stat::PhysicalResistanceReduction), 0.6, 0.0001);
+        assert_aprx!(resolved.resistance(Element::Pyro, &CalcStandard::KQMC), -0.5, 0.0001);
+        assert_aprx!(resolved.resistance(Element::Cryo, &CalcStandard::KQMC), -0.1, 0.0001);
+        assert_eq!(resolved.resistances(&CalcStandard::KQMC).len(), 8);
+    }
+
+    #[test] fn duplicate_sources_do_not_stack_and_warn() {
+        let resolved = resolve_shred(&[
+            Shred::new("viridescent venerer", Debuffs::HydroResistanceReduction, 0.4),
+            Shred::new("viridescent venerer", Debuffs::HydroResistanceReduction, 0.4),
+            Shred::new("lisa a4", Debuffs::DefReduction, 0.15),
+            Shred::new("lisa a4", Debuffs::DefReduction, 0.15),
+        ]);
+        assert_eq!(resolved.warnings.len(), 2);
+        assert_aprx!(resolved.stats.get(&Stat::HydroResistanceReduction), 0.4, 0.0001);
+        assert_aprx!(resolved.stats.get(&Stat::DefReduction), 0.15, 0.0001);
+    }
+
+    #[test] fn enemy_def_is_clamped() {
+        let standard = CalcStandard::KQMC;
+        let base = 5.0 * standard.enemy_level as f32 + 500.0;
+        assert_aprx!(resolve_shred(&[]).enemy_def(&standard), base, 0.01);
+        let resolved = resolve_shred(&[
+            Shred::new("a", Debuffs::DefReduction, 0.6),
+            Shred::new("b", Debuffs::DefReduction, 0.6),
+            Shred::new("raiden c2", Debuffs::DefIgnore, 0.6),
+        ]);
+        assert_aprx!(resolved.enemy_def(&standard), base * 0.1 * 0.4, 0.01);
+    }
+}
@@ -0,0 +1,406 @@
+use crate::core::buff::{Buff, BuffSchedule, BuffWindow};
+use crate::core::stattable::StatTable;
+use crate::core::types::{Amplifier, BaseScaling, DamageType, Element, Stat};
+use crate::energy::energy_formulas::energy_formulas::{calculate_energy, EnergySource};
+use crate::functions::dmg_function::DMGFunction;
+use anyhow::{Result, anyhow};
+
+/// time after a swap before the next character can be swapped in
+pub const SWAP_COOLDOWN: f32 = 1.0;
+
+/// tolerance for comparing event times, so an action exactly at the end of a cooldown is allowed
+const TIME_EPSILON: f32 = 1e-4;
+
+/// tolerance for comparing energy, so float sums of particles can fill a burst
+const ENERGY_EPSILON: f32 = 1e-3;
+
+/// a character of a simulated team
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimCharacter {
+    pub name: String,
+    pub element: Element,
+    pub stats: StatTable,
+    pub skill_cooldown: f32,
+    pub burst_cooldown: f32,
+    pub burst_energy: f32, // energy cost of the burst
+    pub initial_energy: f32,
+}
+
+impl SimCharacter {
+    /// character that starts with a full burst
+    pub fn new(name: &str, element: Element, stats: StatTable, skill_cooldown: f32, burst_cooldown: f32, burst_energy: f32) -> Self {
+        Self { name: String::from(name), element, stats, skill_cooldown, burst_cooldown, burst_energy, initial_energy: burst_energy }
+    }
+
+    pub fn with_initial_energy(mut self, energy: f32) -> Self {
+        self.initial_energy = energy;
+        self
+    }
+}
+
+/// a hit of an action, delay is the time after the action starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimHit {
+    pub name: String,
+    pub delay: f32,
+    pub element: Element,
+    pub damage_type: DamageType,
+    pub scaling: BaseScaling,
+    pub amplifier: Amplifier,
+    pub motion_value: f32,
+}
+
+impl SimHit {
+    pub fn new(name: &str, delay: f32, element: Element, damage_type: DamageType, scaling: BaseScaling, motion_value: f32) -> Self {
+        Self { name: String::from(name), delay, element, damage_type, scaling, amplifier: Amplifier::None, motion_value }
+    }
+
+    pub fn with_amplifier(mut self, amplifier: Amplifier) -> Self {
+        self.amplifier = amplifier;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    Normal,
+    Skill, // goes on skill cooldown
+    Burst, // goes on burst cooldown and consumes the burst energy
+    Swap,
+}
+
+/// an action a character performs at a point in time, a character that is not on field swaps in first
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimAction {
+    pub time: f32,
+    pub character: String,
+    pub kind: ActionKind,
+    pub hits: Vec<SimHit>,
+    pub buffs: Vec<Buff>,
+    pub energy: Vec<EnergySource>, // particles and orbs of the action, caught by whoever is on field
+}
+
+impl SimAction {
+    pub fn new(time: f32, character: &str, kind: ActionKind) -> Self {
+        Self { time, character: String::from(character), kind, hits: Vec::new(), buffs: Vec::new(), energy: Vec::new() }
+    }
+
+    pub fn hit(mut self, hit: SimHit) -> Self {
+        self.hits.push(hit);
+        self
+    }
+
+    pub fn buff(mut self, buff: Buff) -> Self {
+        self.buffs.push(buff);
+        self
+    }
+
+    /// particles the action generates, eg: particles(Element::Pyro, 3.0) for a skill
+    pub fn particles(mut self, element: Element, count: f32) -> Self {
+        self.energy.push(EnergySource::particles(element, count, true));
+        self
+    }
+
+    pub fn orbs(mut self, element: Element, count: f32) -> Self {
+        self.energy.push(EnergySource::orbs(element, count, true));
+        self
+    }
+}
+
+/// damage of a single hit of the simulation
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamageEvent {
+    pub time: f32,
+    pub character: String,
+    pub name: String,
+    pub damage: f32,
+}
+
+/// result of a simulation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimResult {
+    pub timeline: Vec<DamageEvent>, // sorted by time
+    pub buffs: Vec<BuffWindow>,
+    pub energy: std::collections::HashMap<String, f32>, // energy of every character at the end
+    pub duration: f32,
+}
+
+impl SimResult {
+    pub fn total_damage(&self) -> f32 {
+        self.timeline.iter().map(|x| x.damage).sum()
+    }
+
+    /// damage per second over the duration of the simulation
+    pub fn dps(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return self.total_damage();
+        }
+        self.total_damage() / self.duration
+    }
+
+    /// total damage of each character
+    pub fn damage_by_character(&self) -> std::collections::HashMap<String, f32> {
+        let mut res = std::collections::HashMap::new();
+        for event in &self.timeline {
+            *res.entry(event.character.clone()).or_insert(0.0) += event.damage;
+        }
+        res
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CharacterState {
+    skill_ready: f32,
+    burst_ready: f32,
+    energy: f32,
+}
+
+/// event driven simulation of a team running a timestamped list of actions
+/// the simulation is deterministic: actions at the same time keep the order they were added in
+pub struct Simulation {
+    characters: Vec<SimCharacter>,
+    actions: Vec<SimAction>, // sorted by time
+    duration: Option<f32>,
+}
+
+impl Simulation {
+    pub fn new() -> Self {
+        Self { characters: Vec::new(), actions: Vec::new(), duration: None }
+    }
+
+    pub fn add_character(&mut self, character: SimCharacter) -> &mut Self {
+        self.characters.push(character);
+        self
+    }
+
+    pub fn add(&mut self, action: SimAction) -> &mut Self {
+        let index = self.actions.partition_point(|x| x.time <= action.time);
+        self.actions.insert(index, action);
+        self
+    }
+
+    /// duration used for dps, defaults to the time of the last hit or action
+    pub fn with_duration(&mut self, duration: f32) -> &mut Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// runs the actions in time order, errors if an action is used on cooldown, without energy or by an unknown character
+    /// particles of an action go to every member with the energy recharge, element and field multipliers of the member
+    pub fn run(&self) -> Result<SimResult> {
+        if self.characters.is_empty() || self.characters.len() > 4 {
+            return Err(anyhow!("a team has 1 to 4 members, got {}", self.characters.len()));
+        }
+        let party = self.characters.len() as i8;
+        let mut states: std::collections::HashMap<&str, CharacterState> = self.characters.iter()
+            .map(|c| (c.name.as_str(), CharacterState { skill_ready: 0.0, burst_ready: 0.0, energy: c.initial_energy }))
+            .collect();
+        let mut on_field: Option<&str> = None;
+        let mut swaps: Vec<(f32, &str)> = Vec::new(); // time, character swapped in
+        let mut last_swap = f32::NEG_INFINITY;
+        let mut schedule = BuffSchedule::new();
+        let mut hits: Vec<(f32, &str, &SimHit)> = Vec::new();
+
+        for action in &self.actions {
+            let character = self.character(&action.character)?;
+            let state = states.get_mut(character.name.as_str()).unwrap();
+
+            if on_field != Some(character.name.as_str()) {
+                if on_field.is_some() && action.time < last_swap + SWAP_COOLDOWN - TIME_EPSILON {
+                    return Err(anyhow!("{} swapped in at {}s while swap is on cooldown until {}s", character.name, action.time, last_swap + SWAP_COOLDOWN));
+                }
+                if on_field.is_some() {
+                    last_swap = action.time;
+                }
+                on_field = Some(character.name.as_str());
+                swaps.push((action.time, character.name.as_str()));
+            }
+
+            match action.kind {
+                ActionKind::Skill => {
+                    if action.time < state.skill_ready - TIME_EPSILON {
+                        return Err(anyhow!("{} used skill at {}s while on cooldown until {}s", character.name, action.time, state.skill_ready));
+                    }
+                    state.skill_ready = action.time + character.skill_cooldown;
+                },
+                ActionKind::Burst => {
+                    if action.time < state.burst_ready - TIME_EPSILON {
+                        return Err(anyhow!("{} used burst at {}s while on cooldown until {}s", character.name, action.time, state.burst_ready));
+                    }
+                    if state.energy < character.burst_energy - ENERGY_EPSILON {
+                        return Err(anyhow!("{} used burst at {}s with {} of {} energy", character.name, action.time, state.energy, character.burst_energy));
+                    }
+                    state.burst_ready = action.time + character.burst_cooldown;
+                    state.energy = 0.0;
+                },
+                ActionKind::Normal | ActionKind::Swap => {},
+            }
+            for member in &self.characters {
+                let sources: Vec<EnergySource> = action.energy.iter()
+                    .map(|source| EnergySource { on_field: member.name == character.name, ..*source })
+                    .collect();
+                let gained = calculate_energy(party, member.stats.get(&Stat::EnergyRecharge), member.element, &sources);
+                let state = states.get_mut(member.name.as_str()).unwrap();
+                state.energy = (state.energy + gained).min(member.burst_energy);
+            }
+
+            for buff in &action.buffs {
+                schedule.apply(action.time, &character.name, buff);
+            }
+            for hit in &action.hits {
+                hits.push((action.time + hit.delay, character.name.as_str(), hit));
+            }
+        }
+
+        hits.sort_by(|a, b| a.0.total_cmp(&b.0)); // stable so hits at the same time keep action order
+        let timeline: Vec<DamageEvent> = hits.iter()
+            .map(|(time, name, hit)| {
+                let character = self.character(name).unwrap();
+                let field = swaps.iter().rev().find(|(t, _)| *t <= *time).map(|(_, c)| *c);
+                let buffs = schedule.stats_at(*time, name, field == Some(*name));
+                let damage = DMGFunction::calculate_damage(hit.element, hit.damage_type, hit.scaling, hit.amplifier, 1.0, hit.motion_value, &character.stats, Some(&buffs));
+                DamageEvent { time: *time, character: String::from(*name), name: hit.name.clone(), damage }
+            })
+            .collect();
+
+        let last_event = timeline.iter().map(|x| x.time)
+            .chain(self.actions.iter().map(|x| x.time))
+            .fold(0.0, f32::max);
+        Ok(SimResult {
+            timeline,
+            buffs: schedule.windows().to_vec(),
+            energy: states.into_iter().map(|(name, state)| (String::from(name), state.energy)).collect(),
+            duration: self.duration.unwrap_or(last_event),
+        })
+    }
+
+    fn character(&self, name: &str) -> Result<&SimCharacter> {
+        self.characters.iter()
+            .find(|c| c.name == name)
+            .ok_or(anyhow!("character {} is not in the team", name))
+    }
+}
+
+impl Default for Simulation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::buff::BuffTarget;
+    use crate::assert_aprx;
+
+    fn stats() -> StatTable {
+        StatTable::of(&[
+            (Stat::BaseATK, 1000.0),
+            (Stat::CritRate, 0.5),
+            (Stat::CritDMG, 1.0),
+            (Stat::EnergyRecharge, 1.0),
+        ])
+    }
+
+    fn team() -> Simulation {
+        let mut sim = Simulation::new();
+        sim.add_character(SimCharacter::new("dps", Element::Pyro, stats(), 6.0, 15.0, 60.0));
+        sim.add_character(SimCharacter::new("support", Element::Hydro, stats(), 10.0, 20.0, 80.0).with_initial_energy(0.0));
+        sim
+    }
+
+    fn slash(delay: f32) -> SimHit {
+        SimHit::new("slash", delay, Element::Physical, DamageType::Normal, BaseScaling::ATK, 1.0)
+    }
+
+    #[test] fn hits_use_buffs_active_at_hit_time() {
+        let atk = Buff::new("atk buff", StatTable::of(&[(Stat::FlatATK, 500.0)]), 5.0, BuffTarget::Team);
+        let mut sim = team();
+        sim.add(SimAction::new(0.0, "support", ActionKind::Skill).buff(atk));
+        sim.add(SimAction::new(1.0, "dps", ActionKind::Normal).hit(slash(0.0)).hit(slash(4.5)));
+        let result = sim.run().unwrap();
+
+        let base = DMGFunction::calculate_damage(Element::Physical, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &stats(), None);
+        assert_aprx!(result.timeline[0].damage, base * 1.5, 0.1);
+        assert_aprx!(result.timeline[1].damage, base, 0.1); // buff ended at 5s
+        assert_eq!(result.duration, 5.5);
+        assert_aprx!(result.dps(), base * 2.5 / 5.5, 0.1);
+    }
+
+    #[test] fn buffs_stack_and_refresh() {
+        let stacking = Buff::new("stacks", StatTable::of(&[(Stat::ATKPercent, 0.1)]), 3.0, BuffTarget::Own).with_max_stacks(2);
+        let mut sim = team();
+        for t in [0.0, 1.0, 2.0] {
+            sim.add(SimAction::new(t, "dps", ActionKind::Normal).buff(stacking.clone()).hit(slash(0.1)));
+        }
+        sim.add(SimAction::new(4.5, "dps", ActionKind::Normal).hit(slash(0.0)));
+        sim.add(SimAction::new(5.5, "support", ActionKind::Normal).hit(slash(0.0)));
+        let result = sim.run().unwrap();
+        let stacks: Vec<u32> = result.buffs.iter().map(|w| w.stacks).collect();
+        assert_eq!(stacks, vec![1, 2, 2]);
+        let base = result.timeline[0].damage / 1.1;
+        assert_aprx!(result.timeline[1].damage, base * 1.2, 0.1);
+        assert_aprx!(result.timeline[3].damage, base * 1.2, 0.1); // refreshed at 2s, lasts until 5s
+        assert_aprx!(result.timeline[4].damage, base, 0.1); // own buff does not apply to the support
+    }
+
+    #[test] fn on_field_buffs_follow_swaps() {
+        let field = Buff::new("field", StatTable::of(&[(Stat::FlatATK, 1000.0)]), 10.0, BuffTarget::OnField);
+        let mut sim = team();
+        sim.add(SimAction::new(0.0, "support", ActionKind::Skill).buff(field).hit(slash(2.0)));
+        sim.add(SimAction::new(1.0, "dps", ActionKind::Normal).hit(slash(0.0)));
+        let result = sim.run().unwrap();
+        // the support's hit lands after the dps swapped in
+        assert!(result.timeline[0].character == "dps" && result.timeline[1].character == "support");
+        assert_aprx!(result.timeline[0].damage, 2.0 * result.timeline[1].damage, 0.1);
+    }
+
+    #[test] fn cooldowns_energy_and_swaps_are_enforced() {
+        let mut sim = team();
+        sim.add(SimAction::new(0.0, "dps", ActionKind::Skill).particles(Element::Pyro, 3.0));
+        sim.add(SimAction::new(3.0, "dps", ActionKind::Skill));
+        assert!(sim.run().is_err());
+
+        let mut sim = team();
+        sim.add(SimAction::new(0.0, "dps", ActionKind::Burst));
+        sim.add(SimAction::new(1.0, "support", ActionKind::Burst));
+        assert!(sim.run().unwrap_err().to_string().contains("energy"));
+
+        let mut sim = team();
+        sim.add(SimAction::new(0.0, "dps", ActionKind::Normal));
+        sim.add(SimAction::new(0.5, "support", ActionKind::Normal));
+        sim.add(SimAction::new(1.0, "dps", ActionKind::Normal));
+        assert!(sim.run().unwrap_err().to_string().contains("swap"));
+
+        let mut sim = team();
+        sim.add(SimAction::new(0.0, "dps", ActionKind::Burst));
+        sim.add(SimAction::new(6.0, "dps", ActionKind::Skill).particles(Element::Pyro, 4.0));
+        sim.add(SimAction::new(12.0, "dps", ActionKind::Skill).particles(Element::Pyro, 4.0));
+        let result = sim.run().unwrap();
+        // the dps catches its own pyro particles on field at 3 energy each
+        assert_aprx!(result.energy["dps"], 24.0, 0.001);
+        // the hydro support gets 1 energy per pyro particle at the 2 party off field multiplier
+        assert_aprx!(result.energy["support"], 2.0 * 4.0 * 0.8, 0.001);
+        assert!(Simulation::new().add(SimAction::new(0.0, "nobody", ActionKind::Normal)).run().is_err());
+    }
+
+    #[test] fn energy_recharge_scales_gained_energy() {
+        let mut sim = Simulation::new();
+        let mut er = stats();
+        er.add(&Stat::EnergyRecharge, 0.5);
+        sim.add_character(SimCharacter::new("dps", Element::Pyro, er, 6.0, 15.0, 60.0).with_initial_energy(0.0));
+        for t in [0.0, 6.0] {
+            sim.add(SimAction::new(t, "dps", ActionKind::Skill).particles(Element::None, 10.0));
+        }
+        sim.add(SimAction::new(7.0, "dps", ActionKind::Burst));
+        // 2 casts of 10 clear particles at 2 energy each and 150% fill the 60 energy burst
+        assert_eq!(sim.run().unwrap().energy["dps"], 0.0);
+    }
+
+    #[test] fn simulation_is_deterministic() {
+        let mut sim = team();
+        sim.add(SimAction::new(0.0, "dps", ActionKind::Normal).hit(slash(0.0)).hit(slash(0.0)));
+        sim.add(SimAction::new(2.0, "support", ActionKind::Normal).hit(slash(0.0)));
+        assert_eq!(sim.run().unwrap(), sim.run().unwrap());
+        assert_eq!(sim.run().unwrap().damage_by_character().len(), 2);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::core::types::Stat;
+use crate::core::dual::Numeric;
 
 pub type StatValue = (Stat, f32);
 
@@ -53,6 +54,33 @@ impl StatTable {
     }
 }
 
+/// read access to stat values of a numeric type, lets formulas run on plain stattables or on dual numbers
+pub trait StatLookup {
+    type Value: Numeric;
+    fn get(&self, stat_type: &Stat) -> Self::Value;
+}
+
+impl StatLookup for StatTable {
+    type Value = f32;
+    fn get(&self, stat_type: &Stat) -> f32 {
+        StatTable::get(self, stat_type)
+    }
+}
+
+/// a stattable with constant buffs added on top
+pub struct Buffed<'a, S: StatLookup> {
+    pub stats: &'a S,
+    pub buffs: Option<&'a StatTable>,
+}
+
+impl<S: StatLookup> StatLookup for Buffed<'_, S> {
+    type Value = S::Value;
+    fn get(&self, stat_type: &Stat) -> S::Value {
+        let buff = self.buffs.map(|b| b.get(stat_type)).unwrap_or(0.0);
+        self.stats.get(stat_type) + S::Value::constant(buff)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,191 @@
+use crate::core::aura::{Aura, ElementApplication, IcdTracker, Reaction};
+use crate::core::calc_standard::CalcStandard;
+use crate::core::rotation::Rotation;
+use crate::core::stattable::StatTable;
+use crate::core::types::{BaseScaling, DamageType, Element};
+use crate::functions::dmg_function::DMGFunction;
+
+/// a single hit of a timestamped rotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedHit {
+    pub name: String,
+    pub time: f32, // seconds since the start of the rotation
+    pub element: Element,
+    pub damage_type: DamageType,
+    pub scaling: BaseScaling,
+    pub motion_value: f32,
+    pub application: Option<ElementApplication>, // none for hits that do not apply their element (eg: physical)
+    pub buffs: Option<StatTable>,
+}
+
+impl TimedHit {
+    pub fn new(name: &str, time: f32, element: Element, damage_type: DamageType, scaling: BaseScaling, motion_value: f32) -> Self {
+        Self { name: String::from(name), time, element, damage_type, scaling, motion_value, application: None, buffs: None }
+    }
+
+    /// the hit applies units of its element in an icd group
+    pub fn applies(mut self, units: f32, icd_group: Option<&str>) -> Self {
+        let mut application = ElementApplication::new(self.element, units);
+        if let Some(group) = icd_group {
+            application = application.with_icd(group);
+        }
+        self.application = Some(application);
+        self
+    }
+
+    pub fn with_buffs(mut self, buffs: StatTable) -> Self {
+        self.buffs = Some(buffs);
+        self
+    }
+}
+
+/// reaction a hit of a timestamped rotation triggered
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitReaction {
+    pub name: String,
+    pub time: f32,
+    pub applied: bool, // false if the hit was on icd
+    pub reaction: Reaction,
+}
+
+/// rotation of hits at points in time, used to track the enemy aura
+pub struct TimedRotation {
+    hits: Vec<TimedHit>, // sorted by time
+}
+
+impl TimedRotation {
+    pub fn new() -> Self {
+        Self { hits: Vec::new() }
+    }
+
+    /// adds a hit, hits at the same time keep the order they were added in
+    pub fn add(&mut self, hit: TimedHit) -> &mut Self {
+        let index = self.hits.partition_point(|x| x.time <= hit.time);
+        self.hits.insert(index, hit);
+        self
+    }
+
+    pub fn hits(&self) -> &[TimedHit] {
+        &self.hits
+    }
+
+    /// tracks the enemy aura over the rotation and returns the reaction of every hit
+    pub fn simulate_reactions(&self, aura: &Aura) -> Vec<HitReaction> {
+        let mut aura = aura.clone();
+        let mut icd = IcdTracker::new();
+        self.hits.iter()
+            .map(|hit| {
+                let (applied, reaction) = match &hit.application {
+                    Some(application) if icd.applies(application.icd_group.as_deref(), hit.time) => {
+                        (true, aura.apply(hit.time, application.element, application.units))
+                    },
+                    _ => (false, Reaction::None),
+                };
+                HitReaction { name: hit.name.clone(), time: hit.time, applied, reaction }
+            })
+            .collect()
+    }
+
+    /// fraction of the hits of each action that triggered a reaction
+    /// eg: ("charged attack", Reaction::Melt) -> 0.5
+    pub fn reaction_rates(&self, aura: &Aura) -> std::collections::HashMap<(String, Reaction), f32> {
+        let reactions = self.simulate_reactions(aura);
+        let mut hits: std::collections::HashMap<String, f32> = std::collections::HashMap::new();
+        let mut counts: std::collections::HashMap<(String, Reaction), f32> = std::collections::HashMap::new();
+        for hit in &reactions {
+            *hits.entry(hit.name.clone()).or_insert(0.0) += 1.0;
+            *counts.entry((hit.name.clone(), hit.reaction)).or_insert(0.0) += 1.0;
+        }
+        counts.into_iter()
+            .map(|((name, reaction), count)| {
+                let rate = count / hits[&name];
+                ((name, reaction), rate)
+            })
+            .collect()
+    }
+
+    /// converts to a rotation where every hit is an action with the reaction it triggered in the aura simulation
+    /// actions are named "{name} #{index}" in time order
+    pub fn to_rotation(&self, aura: &Aura, standard: &CalcStandard) -> Rotation {
+        let mut rotation = Rotation::with_standard(standard);
+        for (i, (hit, reaction)) in self.hits.iter().zip(self.simulate_reactions(aura)).enumerate() {
+            let (element, damage_type, scaling, motion_value) = (hit.element, hit.damage_type, hit.scaling, hit.motion_value);
+            let reaction = reaction.reaction;
+            let (buffs, dual_buffs) = (hit.buffs.clone(), hit.buffs.clone());
+            let (standard, dual_standard) = (standard.clone(), standard.clone());
+            rotation.add_differentiable(
+                format!("{} #{}", hit.name, i),
+                Box::new(move |s| DMGFunction::calculate_reaction_damage_generic(element, damage_type, scaling, reaction, 1.0, motion_value, s, buffs.as_ref(), &standard)),
+                Box::new(move |s| DMGFunction::calculate_reaction_damage_generic(element, damage_type, scaling, reaction, 1.0, motion_value, s, dual_buffs.as_ref(), &dual_standard)),
+            );
+        }
+        rotation
+    }
+
+    /// total damage of the rotation with reactions from the aura simulation
+    pub fn evaluate(&self, stats: &StatTable, aura: &Aura, standard: &CalcStandard) -> f32 {
+        self.to_rotation(aura, standard).evaluate(stats)
+    }
+}
+
+impl Default for TimedRotation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::types::{Amplifier, Stat};
+    use crate::assert_aprx;
+
+    fn stats() -> StatTable {
+        StatTable::of(&[
+            (Stat::BaseATK, 1000.0),
+            (Stat::CritRate, 0.5),
+            (Stat::CritDMG, 1.0),
+            (Stat::ElementalMastery, 200.0),
+        ])
+    }
+
+    #[test] fn only_hits_off_icd_react() {
+        // 4 pyro normal attacks on a strong hydro aura, only the 1st and 4th apply pyro
+        let mut r = TimedRotation::new();
+        for i in 0..4 {
+            r.add(TimedHit::new("n1", i as f32 * 0.4, Element::Pyro, DamageType::Normal, BaseScaling::ATK, 1.0).applies(1.0, Some("normal attack")));
+        }
+        let reactions = r.simulate_reactions(&Aura::with(Element::Hydro, 4.0));
+        let reacted: Vec<Reaction> = reactions.iter().map(|x| x.reaction).collect();
+        assert_eq!(reacted, vec![Reaction::ReverseVaporize, Reaction::None, Reaction::None, Reaction::ReverseVaporize]);
+        assert_eq!(r.reaction_rates(&Aura::with(Element::Hydro, 4.0))[&(String::from("n1"), Reaction::ReverseVaporize)], 0.5);
+
+        let expected = 2.0 * DMGFunction::calculate_damage(Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::Reverse, 1.0, 1.0, &stats(), None)
+            + 2.0 * DMGFunction::calculate_damage(Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &stats(), None);
+        assert_aprx!(r.evaluate(&stats(), &Aura::with(Element::Hydro, 4.0), &CalcStandard::KQMC), expected, 0.1);
+    }
+
+    #[test] fn aura_runs_out_between_hits() {
+        // a 1U cryo aura is consumed by the first 1U forward melt
+        let mut r = TimedRotation::new();
+        r.add(TimedHit::new("skill", 0.0, Element::Pyro, DamageType::Skill, BaseScaling::ATK, 2.0).applies(1.0, None));
+        r.add(TimedHit::new("skill", 1.0, Element::Pyro, DamageType::Skill, BaseScaling::ATK, 2.0).applies(1.0, None));
+        let reactions = r.simulate_reactions(&Aura::with(Element::Cryo, 1.0));
+        assert_eq!(reactions[0].reaction, Reaction::Melt);
+        assert_eq!(reactions[1].reaction, Reaction::None);
+    }
+
+    #[test] fn aggravate_adds_catalyze_damage_and_is_differentiable() {
+        let mut r = TimedRotation::new();
+        r.add(TimedHit::new("dendro skill", 0.0, Element::Dendro, DamageType::Skill, BaseScaling::ATK, 1.0).applies(1.0, None));
+        r.add(TimedHit::new("electro skill", 0.5, Element::Electro, DamageType::Skill, BaseScaling::ATK, 1.0).applies(1.0, None));
+        r.add(TimedHit::new("electro skill", 1.0, Element::Electro, DamageType::Skill, BaseScaling::ATK, 1.0).applies(1.0, None));
+        let reactions: Vec<Reaction> = r.simulate_reactions(&Aura::new()).iter().map(|x| x.reaction).collect();
+        assert_eq!(reactions, vec![Reaction::None, Reaction::Quicken, Reaction::Aggravate]);
+
+        let rotation = r.to_rotation(&Aura::new(), &CalcStandard::KQMC);
+        let quicken = rotation.evaluate_action("electro skill #1", &stats()).unwrap();
+        let aggravate = rotation.evaluate_action("electro skill #2", &stats()).unwrap();
+        assert!(aggravate > quicken);
+        assert!(rotation.gradients(&stats()).unwrap()[&Stat::ElementalMastery] > 0.0);
+    }
+}
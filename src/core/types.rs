@@ -5,7 +5,7 @@ use crate::utils::standardize::*;
 // }
 
 /// Represents a Attribute type
-#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, serde::Deserialize)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Stat {
     BaseHP, 
     FlatHP, 
@@ -36,6 +36,16 @@ pub enum Stat {
     SkillDMGBonus, 
     BurstDMGBonus, 
     HealingBonus,
+    IncomingHealingBonus,
+    ShieldStrength,
+    PyroRES,
+    HydroRES,
+    ElectroRES,
+    AnemoRES,
+    GeoRES,
+    DendroRES,
+    CryoRES,
+    PhysicalRES,
     None,
     //hidden stats
     ReactionBonus,
@@ -48,17 +58,167 @@ pub enum Stat {
     AnemoResistanceReduction,
     GeoResistanceReduction,
     DendroResistanceReduction,
-    PhysicalResistanceReduction
+    PhysicalResistanceReduction,
+    //per attack type and per element crit
+    NormalATKCritRate,
+    ChargeATKCritRate,
+    PlungeATKCritRate,
+    SkillCritRate,
+    BurstCritRate,
+    NormalATKCritDMG,
+    ChargeATKCritDMG,
+    PlungeATKCritDMG,
+    SkillCritDMG,
+    BurstCritDMG,
+    PyroCritRate,
+    HydroCritRate,
+    ElectroCritRate,
+    AnemoCritRate,
+    GeoCritRate,
+    DendroCritRate,
+    CryoCritRate,
+    PhysicalCritRate,
+    PyroCritDMG,
+    HydroCritDMG,
+    ElectroCritDMG,
+    AnemoCritDMG,
+    GeoCritDMG,
+    DendroCritDMG,
+    CryoCritDMG,
+    PhysicalCritDMG,
+    //flat damage added to base damage, eg: zhongli's hp scaling or yun jin's normal attack buff
+    FlatDMG,
+    NormalATKFlatDMG,
+    ChargeATKFlatDMG,
+    PlungeATKFlatDMG,
+    SkillFlatDMG,
+    BurstFlatDMG,
+    PyroFlatDMG,
+    HydroFlatDMG,
+    ElectroFlatDMG,
+    AnemoFlatDMG,
+    GeoFlatDMG,
+    DendroFlatDMG,
+    CryoFlatDMG,
+    PhysicalFlatDMG,
+    //multiplier on base damage on top of 100%, eg: 0.2 = 120% base damage
+    BaseDMGMultiplier,
 }
 
 impl Stat {
     pub fn with_value(&self, value: f32) -> StatValue {
         (*self, value)
     }
+
+    /// position of the stat in [`ALL_STATS`]
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
 }
 
+/// every stat type, in declaration order
+pub const ALL_STATS: &[Stat] = &[
+    Stat::BaseHP,
+    Stat::FlatHP,
+    Stat::HPPercent,
+    Stat::BaseATK,
+    Stat::FlatATK,
+    Stat::ATKPercent,
+    Stat::BaseDEF,
+    Stat::FlatDEF,
+    Stat::DEFPercent,
+    Stat::ElementalMastery,
+    Stat::CritRate,
+    Stat::CritDMG,
+    Stat::EnergyRecharge,
+    Stat::DMGBonus,
+    Stat::ElementalDMGBonus,
+    Stat::PyroDMGBonus,
+    Stat::CryoDMGBonus,
+    Stat::GeoDMGBonus,
+    Stat::DendroDMGBonus,
+    Stat::ElectroDMGBonus,
+    Stat::HydroDMGBonus,
+    Stat::AnemoDMGBonus,
+    Stat::PhysicalDMGBonus,
+    Stat::NormalATKDMGBonus,
+    Stat::ChargeATKDMGBonus,
+    Stat::PlungeATKDMGBonus,
+    Stat::SkillDMGBonus,
+    Stat::BurstDMGBonus,
+    Stat::HealingBonus,
+    Stat::IncomingHealingBonus,
+    Stat::ShieldStrength,
+    Stat::PyroRES,
+    Stat::HydroRES,
+    Stat::ElectroRES,
+    Stat::AnemoRES,
+    Stat::GeoRES,
+    Stat::DendroRES,
+    Stat::CryoRES,
+    Stat::PhysicalRES,
+    Stat::None,
+    Stat::ReactionBonus,
+    Stat::DefReduction,
+    Stat::DefIgnore,
+    Stat::PyroResistanceReduction,
+    Stat::HydroResistanceReduction,
+    Stat::ElectroResistanceReduction,
+    Stat::CryoResistanceReduction,
+    Stat::AnemoResistanceReduction,
+    Stat::GeoResistanceReduction,
+    Stat::DendroResistanceReduction,
+    Stat::PhysicalResistanceReduction,
+    Stat::NormalATKCritRate,
+    Stat::ChargeATKCritRate,
+    Stat::PlungeATKCritRate,
+    Stat::SkillCritRate,
+    Stat::BurstCritRate,
+    Stat::NormalATKCritDMG,
+    Stat::ChargeATKCritDMG,
+    Stat::PlungeATKCritDMG,
+    Stat::SkillCritDMG,
+    Stat::BurstCritDMG,
+    Stat::PyroCritRate,
+    Stat::HydroCritRate,
+    Stat::ElectroCritRate,
+    Stat::AnemoCritRate,
+    Stat::GeoCritRate,
+    Stat::DendroCritRate,
+    Stat::CryoCritRate,
+    Stat::PhysicalCritRate,
+    Stat::PyroCritDMG,
+    Stat::HydroCritDMG,
+    Stat::ElectroCritDMG,
+    Stat::AnemoCritDMG,
+    Stat::GeoCritDMG,
+    Stat::DendroCritDMG,
+    Stat::CryoCritDMG,
+    Stat::PhysicalCritDMG,
+    Stat::FlatDMG,
+    Stat::NormalATKFlatDMG,
+    Stat::ChargeATKFlatDMG,
+    Stat::PlungeATKFlatDMG,
+    Stat::SkillFlatDMG,
+    Stat::BurstFlatDMG,
+    Stat::PyroFlatDMG,
+    Stat::HydroFlatDMG,
+    Stat::ElectroFlatDMG,
+    Stat::AnemoFlatDMG,
+    Stat::GeoFlatDMG,
+    Stat::DendroFlatDMG,
+    Stat::CryoFlatDMG,
+    Stat::PhysicalFlatDMG,
+    Stat::BaseDMGMultiplier,
+];
+
+/// number of stat types
+pub const STAT_COUNT: usize = ALL_STATS.len();
+
 pub type StatValue = (Stat, f32);
 
+/// enemy debuffs that shred def or res
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
 pub enum Debuffs{
     DefReduction,
     DefIgnore,
@@ -72,7 +232,40 @@ pub enum Debuffs{
     PhysicalResistanceReduction
 }
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone)]
+impl Debuffs {
+    /// stat the debuff is summed into
+    pub fn stat(&self) -> Stat {
+        match self {
+            Debuffs::DefReduction => Stat::DefReduction,
+            Debuffs::DefIgnore => Stat::DefIgnore,
+            Debuffs::PyroResistanceReduction => Stat::PyroResistanceReduction,
+            Debuffs::HydroResistanceReduction => Stat::HydroResistanceReduction,
+            Debuffs::ElectroResistanceReduction => Stat::ElectroResistanceReduction,
+            Debuffs::CryoResistanceReduction => Stat::CryoResistanceReduction,
+            Debuffs::AnemoResistanceReduction => Stat::AnemoResistanceReduction,
+            Debuffs::GeoResistanceReduction => Stat::GeoResistanceReduction,
+            Debuffs::DendroResistanceReduction => Stat::DendroResistanceReduction,
+            Debuffs::PhysicalResistanceReduction => Stat::PhysicalResistanceReduction,
+        }
+    }
+
+    /// res shred of an element, none for elements without res (Element::None)
+    pub fn resistance_reduction(element: Element) -> Option<Debuffs> {
+        match element {
+            Element::Pyro => Some(Debuffs::PyroResistanceReduction),
+            Element::Hydro => Some(Debuffs::HydroResistanceReduction),
+            Element::Electro => Some(Debuffs::ElectroResistanceReduction),
+            Element::Cryo => Some(Debuffs::CryoResistanceReduction),
+            Element::Anemo => Some(Debuffs::AnemoResistanceReduction),
+            Element::Geo => Some(Debuffs::GeoResistanceReduction),
+            Element::Dendro => Some(Debuffs::DendroResistanceReduction),
+            Element::Physical => Some(Debuffs::PhysicalResistanceReduction),
+            Element::None => None,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub enum DamageType {
     Normal,
     Charged,
@@ -82,7 +275,25 @@ pub enum DamageType {
     None,
 }
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone)]
+/// talent a character can level up
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone)]
+pub enum TalentType {
+    NormalAttack,
+    Skill,
+    Burst,
+}
+
+impl TalentType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TalentType::NormalAttack => "Normal Attack",
+            TalentType::Skill => "Elemental Skill",
+            TalentType::Burst => "Elemental Burst",
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Element {
     Pyro,
     Hydro,
@@ -95,7 +306,7 @@ pub enum Element {
     None,
 }
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub enum BaseScaling {
     ATK,
     DEF,
@@ -103,7 +314,7 @@ pub enum BaseScaling {
     FlatDamage
 }
 
-#[derive(Eq, PartialEq, Hash, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Copy, Clone, serde::Serialize, serde::Deserialize)]
 pub enum Amplifier {
     Forward,
     Reverse,
@@ -176,6 +387,16 @@ impl Stat{
             Stat::SkillDMGBonus => "SkillDMGBonus",
             Stat::BurstDMGBonus => "BurstDMGBonus",
             Stat::HealingBonus => "HealingBonus",
+            Stat::IncomingHealingBonus => "IncomingHealingBonus",
+            Stat::ShieldStrength => "ShieldStrength",
+            Stat::PyroRES => "PyroRES",
+            Stat::HydroRES => "HydroRES",
+            Stat::ElectroRES => "ElectroRES",
+            Stat::AnemoRES => "AnemoRES",
+            Stat::GeoRES => "GeoRES",
+            Stat::DendroRES => "DendroRES",
+            Stat::CryoRES => "CryoRES",
+            Stat::PhysicalRES => "PhysicalRES",
             Stat::None => "None",
             Stat::ReactionBonus => "ReactionBonus",
             Stat::DefReduction => "DefReduction",
@@ -188,6 +409,47 @@ impl Stat{
             Stat::GeoResistanceReduction => "GeoResistanceReduction",
             Stat::DendroResistanceReduction => "DendroResistanceReduction",
             Stat::PhysicalResistanceReduction => "PhysicalResistanceReduction",
+            Stat::NormalATKCritRate => "NormalATKCritRate",
+            Stat::ChargeATKCritRate => "ChargeATKCritRate",
+            Stat::PlungeATKCritRate => "PlungeATKCritRate",
+            Stat::SkillCritRate => "SkillCritRate",
+            Stat::BurstCritRate => "BurstCritRate",
+            Stat::NormalATKCritDMG => "NormalATKCritDMG",
+            Stat::ChargeATKCritDMG => "ChargeATKCritDMG",
+            Stat::PlungeATKCritDMG => "PlungeATKCritDMG",
+            Stat::SkillCritDMG => "SkillCritDMG",
+            Stat::BurstCritDMG => "BurstCritDMG",
+            Stat::PyroCritRate => "PyroCritRate",
+            Stat::HydroCritRate => "HydroCritRate",
+            Stat::ElectroCritRate => "ElectroCritRate",
+            Stat::AnemoCritRate => "AnemoCritRate",
+            Stat::GeoCritRate => "GeoCritRate",
+            Stat::DendroCritRate => "DendroCritRate",
+            Stat::CryoCritRate => "CryoCritRate",
+            Stat::PhysicalCritRate => "PhysicalCritRate",
+            Stat::PyroCritDMG => "PyroCritDMG",
+            Stat::HydroCritDMG => "HydroCritDMG",
+            Stat::ElectroCritDMG => "ElectroCritDMG",
+            Stat::AnemoCritDMG => "AnemoCritDMG",
+            Stat::GeoCritDMG => "GeoCritDMG",
+            Stat::DendroCritDMG => "DendroCritDMG",
+            Stat::CryoCritDMG => "CryoCritDMG",
+            Stat::PhysicalCritDMG => "PhysicalCritDMG",
+            Stat::FlatDMG => "FlatDMG",
+            Stat::NormalATKFlatDMG => "NormalATKFlatDMG",
+            Stat::ChargeATKFlatDMG => "ChargeATKFlatDMG",
+            Stat::PlungeATKFlatDMG => "PlungeATKFlatDMG",
+            Stat::SkillFlatDMG => "SkillFlatDMG",
+            Stat::BurstFlatDMG => "BurstFlatDMG",
+            Stat::PyroFlatDMG => "PyroFlatDMG",
+            Stat::HydroFlatDMG => "HydroFlatDMG",
+            Stat::ElectroFlatDMG => "ElectroFlatDMG",
+            Stat::AnemoFlatDMG => "AnemoFlatDMG",
+            Stat::GeoFlatDMG => "GeoFlatDMG",
+            Stat::DendroFlatDMG => "DendroFlatDMG",
+            Stat::CryoFlatDMG => "CryoFlatDMG",
+            Stat::PhysicalFlatDMG => "PhysicalFlatDMG",
+            Stat::BaseDMGMultiplier => "BaseDMGMultiplier",
         }
     }
 }
@@ -228,6 +490,16 @@ impl FromStr for Stat {
             "skilldmgbonus" => Ok(Stat::SkillDMGBonus),
             "burstdmgbonus" => Ok(Stat::BurstDMGBonus),
             "healingbonus" | "hb" => Ok(Stat::HealingBonus),
+            "incominghealingbonus" => Ok(Stat::IncomingHealingBonus),
+            "shieldstrength" => Ok(Stat::ShieldStrength),
+            "pyrores" | "pyroresistance" => Ok(Stat::PyroRES),
+            "hydrores" | "hydroresistance" => Ok(Stat::HydroRES),
+            "electrores" | "electroresistance" => Ok(Stat::ElectroRES),
+            "anemores" | "anemoresistance" => Ok(Stat::AnemoRES),
+            "geores" | "georesistance" => Ok(Stat::GeoRES),
+            "dendrores" | "dendroresistance" => Ok(Stat::DendroRES),
+            "cryores" | "cryoresistance" => Ok(Stat::CryoRES),
+            "physicalres" | "physicalresistance" => Ok(Stat::PhysicalRES),
             "none" | "n" => Ok(Stat::None),
             "reactionbonus" => Ok(Stat::ReactionBonus),
             "defreduction" => Ok(Stat::DefReduction),
@@ -240,6 +512,47 @@ impl FromStr for Stat {
             "georesistancereduction" => Ok(Stat::GeoResistanceReduction),
             "dendroresistancereduction" => Ok(Stat::DendroResistanceReduction),
             "physicalresistancereduction" => Ok(Stat::PhysicalResistanceReduction),
+            "normalatkcritrate" => Ok(Stat::NormalATKCritRate),
+            "chargeatkcritrate" => Ok(Stat::ChargeATKCritRate),
+            "plungeatkcritrate" => Ok(Stat::PlungeATKCritRate),
+            "skillcritrate" => Ok(Stat::SkillCritRate),
+            "burstcritrate" => Ok(Stat::BurstCritRate),
+            "normalatkcritdmg" => Ok(Stat::NormalATKCritDMG),
+            "chargeatkcritdmg" => Ok(Stat::ChargeATKCritDMG),
+            "plungeatkcritdmg" => Ok(Stat::PlungeATKCritDMG),
+            "skillcritdmg" => Ok(Stat::SkillCritDMG),
+            "burstcritdmg" => Ok(Stat::BurstCritDMG),
+            "pyrocritrate" => Ok(Stat::PyroCritRate),
+            "hydrocritrate" => Ok(Stat::HydroCritRate),
+            "electrocritrate" => Ok(Stat::ElectroCritRate),
+            "anemocritrate" => Ok(Stat::AnemoCritRate),
+            "geocritrate" => Ok(Stat::GeoCritRate),
+            "dendrocritrate" => Ok(Stat::DendroCritRate),
+            "cryocritrate" => Ok(Stat::CryoCritRate),
+            "physicalcritrate" => Ok(Stat::PhysicalCritRate),
+            "pyrocritdmg" => Ok(Stat::PyroCritDMG),
+            "hydrocritdmg" => Ok(Stat::HydroCritDMG),
+            "electrocritdmg" => Ok(Stat::ElectroCritDMG),
+            "anemocritdmg" => Ok(Stat::AnemoCritDMG),
+            "geocritdmg" => Ok(Stat::GeoCritDMG),
+            "dendrocritdmg" => Ok(Stat::DendroCritDMG),
+            "cryocritdmg" => Ok(Stat::CryoCritDMG),
+            "physicalcritdmg" => Ok(Stat::PhysicalCritDMG),
+            "flatdmg" => Ok(Stat::FlatDMG),
+            "normalatkflatdmg" => Ok(Stat::NormalATKFlatDMG),
+            "chargeatkflatdmg" => Ok(Stat::ChargeATKFlatDMG),
+            "plungeatkflatdmg" => Ok(Stat::PlungeATKFlatDMG),
+            "skillflatdmg" => Ok(Stat::SkillFlatDMG),
+            "burstflatdmg" => Ok(Stat::BurstFlatDMG),
+            "pyroflatdmg" => Ok(Stat::PyroFlatDMG),
+            "hydroflatdmg" => Ok(Stat::HydroFlatDMG),
+            "electroflatdmg" => Ok(Stat::ElectroFlatDMG),
+            "anemoflatdmg" => Ok(Stat::AnemoFlatDMG),
+            "geoflatdmg" => Ok(Stat::GeoFlatDMG),
+            "dendroflatdmg" => Ok(Stat::DendroFlatDMG),
+            "cryoflatdmg" => Ok(Stat::CryoFlatDMG),
+            "physicalflatdmg" => Ok(Stat::PhysicalFlatDMG),
+            "basedmgmultiplier" => Ok(Stat::BaseDMGMultiplier),
             "physicaldmgpercent" | "physical dmg%" => Ok(Stat::PhysicalDMGBonus),
             _ => Err(())
         }
@@ -251,4 +564,24 @@ impl std::fmt::Display for Stat {
         let s = self.as_str();
         write!(f, "{s}")
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    #[test] fn all_stats_are_in_declaration_order() {
+        assert!(ALL_STATS.iter().enumerate().all(|(i, stat)| stat.index() == i));
+        assert_eq!(ALL_STATS.last().unwrap().index(), STAT_COUNT - 1);
+    }
+
+    #[test] fn stat_names_round_trip() {
+        assert!(ALL_STATS.iter().all(|stat| Stat::from_str(stat.as_str()) == Ok(*stat)));
+    }
+
+    #[test] fn per_type_stat_names() {
+        assert_eq!(Stat::from_str("Burst Crit Rate"), Ok(Stat::BurstCritRate));
+        assert_eq!(Stat::from_str("ChargeATKCritDMG"), Ok(Stat::ChargeATKCritDMG));
+        assert_eq!(Stat::NormalATKFlatDMG.as_str(), "NormalATKFlatDMG");
+        assert_eq!(Stat::from_str("base dmg multiplier"), Ok(Stat::BaseDMGMultiplier));
+    }
+}
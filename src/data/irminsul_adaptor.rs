@@ -1,5 +1,6 @@
 use crate::{core::stattable::StatTable, standardize::flatten_str};
 use crate::core::types::Stat;
+use crate::core::calc_standard::CalcStandard;
 use std::io::prelude::*;
 use std::str::FromStr;
 use serde::Deserialize;
@@ -47,7 +48,8 @@ pub struct CharacterJSON {
     pub ascension_stat: String,
     pub base_stats: Vec<CharacterBaseStatJSON>,
     //pub ascension_costs: Vec<CharacterAscensionCostJSON>,
-    //pub talents: Vec<CharacterTalentJSON>,
+    #[serde(default)]
+    pub talents: Vec<CharacterTalentJSON>,
     //pub passives: Vec<CharacterPassiveJSON>,
     //pub constellations: Vec<CharacterConstellationJSON>,
 }
@@ -78,14 +80,17 @@ pub struct CharacterBaseStatJSON {
 
 impl CharacterBaseStatJSON {
     pub fn to_stattable(&self) -> Result<StatTable>{
+        self.to_stattable_with_standard(&CalcStandard::KQMC)
+    }
+
+    /// base stats with the crit and energy recharge defaults of a calc standard
+    pub fn to_stattable_with_standard(&self, standard: &CalcStandard) -> Result<StatTable>{
         let mut stats = StatTable::of(&[
             (Stat::BaseHP, self.base_hp.parse::<f32>().unwrap()),
             (Stat::BaseATK, self.base_atk.parse::<f32>().unwrap()),
             (Stat::BaseDEF, self.base_def.parse::<f32>().unwrap()),
-            (Stat::CritRate, 0.05),
-            (Stat::CritDMG, 0.5),
-            (Stat::EnergyRecharge, 1.0),
         ]);
+        stats.add_table(standard.character_base_stats().iter());
 
         if self.stat_value != "-" {
             let substat = Stat::from_str(&self.stat_type.as_str())
@@ -109,22 +114,48 @@ pub struct CharacterConstellationJSON {
     pub properties: Vec<serde_json::Value>, // use concrete type if structure known
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct CharacterTalentJSON {
     pub name: String,
     #[serde(rename = "type")]
     pub type_: String,
     pub description: String,
     pub attributes: Option<Vec<CharacterTalentAttributeJSON>>,
+    #[serde(default)]
     pub properties: Vec<serde_json::Value>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone)]
 pub struct CharacterTalentAttributeJSON {
     pub hit: String,
     pub values: Vec<serde_json::Value>, // numbers or strings, hence generic
 }
 
+impl CharacterTalentAttributeJSON {
+    /// motion value of the hit at a talent level as a fraction (eg: 84.01% -> 0.8401)
+    /// multi hit values such as "47.3+60.2" or "44.4×3" are summed into a single motion value
+    pub fn motion_value(&self, level: i8) -> Result<f32> {
+        let value = level.checked_sub(1)
+            .and_then(|i| self.values.get(i as usize))
+            .ok_or_else(|| anyhow!("no {} value for talent level {level}", self.hit))?;
+
+        let percent = match value {
+            serde_json::Value::Number(n) => n.as_f64()
+                .ok_or_else(|| anyhow!("failed to parse talent value {n}"))? as f32,
+            serde_json::Value::String(text) => text.split('+')
+                .map(|term| {
+                    let mut factors = term.split(['×', '*']).map(|x| x.trim().parse::<f32>());
+                    let first = factors.next().unwrap_or(Ok(0.0));
+                    factors.try_fold(first?, |acc, x| x.map(|x| acc * x))
+                })
+                .sum::<Result<f32, _>>()
+                .map_err(|e| anyhow!("failed to parse talent value {text}: {e}"))?,
+            _ => Err(anyhow!("invalid talent value {value}"))?,
+        };
+        Ok(percent / 100.0)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CharacterPassiveJSON {
     pub name: String,
@@ -302,6 +333,7 @@ pub struct ArtifactSubStatJson{
 
 #[cfg(test)] mod tests {
     use super::*;
+    use crate::assert_aprx;
 
     #[test] fn test_to_stattable() {
         let w = WeaponBaseStatJSON {
@@ -315,4 +347,17 @@ pub struct ArtifactSubStatJson{
         assert_eq!(w.get(&Stat::BaseATK), 510.0);
         assert_eq!(w.get(&Stat::PhysicalDMGBonus), 51.7);
     }
+
+    #[test] fn test_talent_motion_value() {
+        let attribute = CharacterTalentAttributeJSON {
+            hit: "Charged Attack DMG (%)".to_string(),
+            values: vec![serde_json::json!(84.01), serde_json::json!("47.3+60.2"), serde_json::json!("44.4×3"), serde_json::json!("5s")],
+        };
+        assert_aprx!(attribute.motion_value(1).unwrap(), 0.8401, 0.0001);
+        assert_aprx!(attribute.motion_value(2).unwrap(), 1.075, 0.0001);
+        assert_aprx!(attribute.motion_value(3).unwrap(), 1.332, 0.0001);
+        assert!(attribute.motion_value(4).is_err());
+        assert!(attribute.motion_value(0).is_err());
+        assert!(attribute.motion_value(5).is_err());
+    }
 }
\ No newline at end of file
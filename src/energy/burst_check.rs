@@ -0,0 +1,327 @@
+use crate::core::stattable::StatTable;
+use crate::core::types::{Element, Stat};
+use crate::energy::energy_formulas::energy_formulas::{calculate_energy, EnergySource};
+use crate::energy::specification::{find_member, refers_to, CharacterEnergySpec, SkillCast};
+use anyhow::{Result, anyhow};
+
+/// leeway for float energy and timestamps
+const EPSILON: f32 = 1e-4;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Skill(SkillCast),
+    Particles(Element, f32, String), // element, count, catcher of the particles of a skill
+    Source(String, EnergySource), // receiver, source (eg: enemy drops)
+    Burst(String),
+}
+
+/// a timestamped team rotation to check burst uptime on
+/// particles on cast are gathered at the time of the skill cast, turrets give theirs every second over their duration
+/// every member starts with a full burst unless set otherwise
+pub struct BurstCheck {
+    team: Vec<CharacterEnergySpec>,
+    events: Vec<(f32, Event)>, // sorted by time, events at the same time keep their order
+    initial_energy: Vec<(String, f32)>,
+}
+
+impl BurstCheck {
+    pub fn new(team: Vec<CharacterEnergySpec>) -> Self {
+        Self { team, events: Vec::new(), initial_energy: Vec::new() }
+    }
+
+    pub fn skill(&mut self, time: f32, cast: SkillCast) -> &mut Self {
+        self.add(time, Event::Skill(cast))
+    }
+
+    /// adds energy a character receives at a time outside of skill casts
+    pub fn add_source(&mut self, time: f32, character: &str, source: EnergySource) -> &mut Self {
+        self.add(time, Event::Source(String::from(character), source))
+    }
+
+    pub fn burst(&mut self, time: f32, character: &str) -> &mut Self {
+        self.add(time, Event::Burst(String::from(character)))
+    }
+
+    pub fn with_initial_energy(mut self, character: &str, energy: f32) -> Self {
+        self.initial_energy.push((String::from(character), energy));
+        self
+    }
+
+    fn add(&mut self, time: f32, event: Event) -> &mut Self {
+        let index = self.events.partition_point(|(t, _)| *t <= time);
+        self.events.insert(index, (time, event));
+        self
+    }
+
+    /// checks every burst against its cooldown and the energy gathered with each member's energy recharge
+    /// stats are the final stats of every member by name, errors if a skill is cast on cooldown
+    pub fn check(&self, stats: &[(&str, &StatTable)]) -> Result<BurstReport> {
+        if self.team.is_empty() || self.team.len() > 4 {
+            return Err(anyhow!("a team has 1 to 4 members, got {}", self.team.len()));
+        }
+        let mut states = self.team.iter()
+            .map(|member| {
+                let (_, member_stats) = stats.iter()
+                    .find(|(name, _)| refers_to(&self.team, name, member))
+                    .ok_or_else(|| anyhow!("no stats for {}", member.character_name))?;
+                let energy = self.initial_energy.iter()
+                    .find(|(name, _)| refers_to(&self.team, name, member))
+                    .map(|(_, energy)| *energy)
+                    .unwrap_or(member.burst_energy as f32);
+                Ok(MemberState::new(member, member_stats.get(&Stat::EnergyRecharge), energy))
+            })
+            .collect::<Result<Vec<MemberState>>>()?;
+
+        let party = self.team.len() as i8;
+        let mut bursts: Vec<BurstResult> = Vec::new();
+        for (time, event) in &self.particle_events()? {
+            match event {
+                Event::Skill(_) => {},
+                Event::Particles(element, count, catcher) => {
+                    for (member, state) in self.team.iter().zip(states.iter_mut()) {
+                        let source = EnergySource::particles(*element, *count, member.character_name == *catcher);
+                        let gained = calculate_energy(party, state.energy_recharge, member.element, &[source]);
+                        if let Some((index, available)) = state.gain(*time, gained, member) {
+                            bursts[index].available = Some(available);
+                        }
+                    }
+                }
+                Event::Source(character, source) => {
+                    let index = self.index_of(character)?;
+                    let member = &self.team[index];
+                    let gained = calculate_energy(party, states[index].energy_recharge, member.element, std::slice::from_ref(source));
+                    if let Some((late, available)) = states[index].gain(*time, gained, member) {
+                        bursts[late].available = Some(available);
+                    }
+                }
+                Event::Burst(character) => {
+                    let index = self.index_of(character)?;
+                    let burst = states[index].burst(*time, &self.team[index], bursts.len());
+                    bursts.push(burst);
+                }
+            }
+        }
+        Ok(BurstReport { bursts })
+    }
+
+    /// events with the particles of every skill cast at the time they are gathered
+    /// errors if a skill is cast while on cooldown
+    fn particle_events(&self) -> Result<Vec<(f32, Event)>> {
+        let mut events = Vec::with_capacity(self.events.len());
+        let mut turrets = Vec::new();
+        let mut skill_ready = vec![0.0; self.team.len()];
+        for (time, event) in &self.events {
+            events.push((*time, event.clone()));
+            let Event::Skill(cast) = event else { continue };
+            let index = self.index_of(&cast.character)?;
+            let caster = &self.team[index];
+            let skill = caster.skill(&cast.skill)?;
+            if *time < skill_ready[index] - EPSILON {
+                return Err(anyhow!("{} used {} at {}s while on cooldown until {}s", caster.character_name, skill.label, time, skill_ready[index]));
+            }
+            skill_ready[index] = time + skill.cool_down;
+            let catcher = find_member(&self.team, &cast.on_field)?.character_name.clone();
+            events.push((*time, Event::Particles(caster.element, skill.particles * cast.count, catcher.clone())));
+            for second in 1..=skill.duration {
+                turrets.push((time + second as f32, Event::Particles(caster.element, skill.per_second * cast.count, catcher.clone())));
+            }
+        }
+        // turret particles arrive after the events planned at the same time
+        events.extend(turrets);
+        events.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Ok(events)
+    }
+
+    fn index_of(&self, character: &str) -> Result<usize> {
+        let member = find_member(&self.team, character)?;
+        Ok(self.team.iter().position(|c| c.character_name == member.character_name).unwrap())
+    }
+}
+
+struct MemberState {
+    energy_recharge: f32,
+    energy: f32,
+    full_at: Option<f32>, // time the member gathered enough energy for their next burst
+    last_burst: Option<f32>,
+    late: Option<LateBurst>,
+}
+
+/// a burst planned before its energy was gathered, its energy is tracked until it fills or the next burst
+struct LateBurst {
+    index: usize, // index in the report
+    energy: f32,
+    off_cooldown: f32,
+}
+
+impl MemberState {
+    fn new(member: &CharacterEnergySpec, energy_recharge: f32, energy: f32) -> Self {
+        let energy = energy.min(member.burst_energy as f32);
+        let full_at = if energy >= member.burst_energy as f32 { Some(0.0) } else { None };
+        Self { energy_recharge, energy, full_at, last_burst: None, late: None }
+    }
+
+    /// returns the index and the earliest time of a late burst whose energy filled up
+    fn gain(&mut self, time: f32, energy: f32, member: &CharacterEnergySpec) -> Option<(usize, f32)> {
+        let cost = member.burst_energy as f32;
+        self.energy = (self.energy + energy).min(cost);
+        if self.full_at.is_none() && self.energy >= cost - EPSILON {
+            self.full_at = Some(time);
+        }
+        let late = self.late.as_mut()?;
+        late.energy += energy;
+        if late.energy < cost - EPSILON {
+            return None;
+        }
+        let late = self.late.take().unwrap();
+        Some((late.index, time.max(late.off_cooldown)))
+    }
+
+    /// bursts at the planned time even if it is not castable, the rotation assumes it happened
+    /// index is where the burst goes in the report
+    fn burst(&mut self, time: f32, member: &CharacterEnergySpec, index: usize) -> BurstResult {
+        let off_cooldown = self.last_burst.map(|t| t + member.burst_cooldown).unwrap_or(0.0);
+        let available = self.full_at.map(|full| full.max(off_cooldown));
+        let result = BurstResult {
+            character: member.character_name.clone(),
+            planned: time,
+            available,
+            energy: self.energy,
+            feasible: available.is_some_and(|t| t <= time + EPSILON),
+        };
+        self.energy = 0.0;
+        self.full_at = None;
+        self.last_burst = Some(time);
+        self.late = None;
+        self.gain(time, member.burst_discount as f32, member);
+        if available.is_none() {
+            self.late = Some(LateBurst { index, energy: result.energy, off_cooldown });
+        }
+        result
+    }
+}
+
+/// a burst of the rotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurstResult {
+    pub character: String,
+    pub planned: f32,
+    pub available: Option<f32>, // earliest time the burst could be cast, none if the energy is not gathered before the next burst
+    pub energy: f32, // energy at the planned time
+    pub feasible: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BurstReport {
+    pub bursts: Vec<BurstResult>,
+}
+
+impl BurstReport {
+    pub fn is_feasible(&self) -> bool {
+        self.bursts.iter().all(|b| b.feasible)
+    }
+
+    /// bursts the rotation assumes but can not be cast on time
+    pub fn infeasible(&self) -> impl Iterator<Item = &BurstResult> {
+        self.bursts.iter().filter(|b| !b.feasible)
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::functions::stat_factory::StatFactory;
+    use crate::assert_aprx;
+
+    fn duo() -> BurstCheck {
+        let team = ["xingqiu", "bennett"].iter()
+            .map(|name| StatFactory::get_character_energy_spec(name).unwrap())
+            .collect();
+        BurstCheck::new(team)
+    }
+
+    #[test] fn bursts_need_energy_and_cooldown() {
+        let er = StatTable::of(&[(Stat::EnergyRecharge, 1.0)]);
+        let stats = [("xingqiu", &er), ("bennett", &er)];
+        let mut check = duo();
+        check.burst(0.0, "xingqiu")
+            .skill(1.0, SkillCast::new("xingqiu", "press", 1.0, "xingqiu"))
+            .burst(2.0, "xingqiu");
+        let report = check.check(&stats).unwrap();
+        assert!(report.bursts[0].feasible);
+        assert!(!report.is_feasible());
+        assert_eq!(report.infeasible().count(), 1);
+        assert_eq!(report.bursts[1].available, None);
+        let xingqiu = StatFactory::get_character_energy_spec("xingqiu").unwrap();
+        assert_aprx!(report.bursts[1].energy, xingqiu.skill("press").unwrap().particles_per_cast() * 3.0, 0.001);
+    }
+
+    #[test] fn energy_recharge_decides_feasibility() {
+        let mut check = duo();
+        check.burst(0.0, "bennett");
+        for t in [2.0, 8.0] {
+            check.skill(t, SkillCast::new("bennett", "press", 1.0, "bennett"));
+        }
+        check.burst(15.0, "bennett");
+
+        // 2 casts of 2.5 pyro particles on field is 15 energy, bennett needs 60
+        let low = StatTable::of(&[(Stat::EnergyRecharge, 1.0)]);
+        assert!(!check.check(&[("xingqiu", &low), ("bennett", &low)]).unwrap().is_feasible());
+        let high = StatTable::of(&[(Stat::EnergyRecharge, 4.0)]);
+        let report = check.check(&[("xingqiu", &low), ("bennett", &high)]).unwrap();
+        assert!(report.is_feasible());
+        // the energy is gathered at the second cast, before the burst is off cooldown
+        assert_eq!(report.bursts[1].available, Some(15.0));
+    }
+
+    #[test] fn late_bursts_report_when_their_energy_arrives() {
+        let high = StatTable::of(&[(Stat::EnergyRecharge, 4.0)]);
+        let mut check = duo();
+        check.burst(0.0, "bennett")
+            .skill(2.0, SkillCast::new("bennett", "press", 1.0, "bennett"))
+            .burst(15.0, "bennett")
+            .skill(17.0, SkillCast::new("bennett", "press", 1.0, "bennett"));
+        // 30 energy per cast, the second cast fills the burst 2s late
+        let report = check.check(&[("xingqiu", &high), ("bennett", &high)]).unwrap();
+        assert!(!report.bursts[1].feasible);
+        assert_aprx!(report.bursts[1].energy, 30.0, 0.001);
+        assert_eq!(report.bursts[1].available, Some(17.0));
+    }
+
+    #[test] fn initial_energy_and_missing_stats() {
+        let er = StatTable::of(&[(Stat::EnergyRecharge, 1.0)]);
+        let mut check = duo().with_initial_energy("xingqiu", 0.0);
+        check.burst(0.0, "xingqiu");
+        assert!(!check.check(&[("xingqiu", &er), ("bennett", &er)]).unwrap().is_feasible());
+        assert!(check.check(&[("xingqiu", &er)]).is_err());
+    }
+
+    #[test] fn turret_particles_arrive_over_the_duration() {
+        let team = ["fischl", "bennett"].iter()
+            .map(|name| StatFactory::get_character_energy_spec(name).unwrap())
+            .collect();
+        let er = StatTable::of(&[(Stat::EnergyRecharge, 1.0)]);
+        let stats = [("fischl", &er), ("bennett", &er)];
+        let mut check = BurstCheck::new(team);
+        check.burst(0.0, "fischl")
+            .skill(0.0, SkillCast::new("fischl", "oz", 1.0, "fischl"))
+            .burst(5.5, "fischl")
+            .burst(30.0, "fischl");
+        let report = check.check(&stats).unwrap();
+        // 5 of the 10 seconds of oz, 0.67 electro particles each
+        assert_aprx!(report.bursts[1].energy, 5.0 * 0.67 * 3.0, 0.001);
+        // the other 5 seconds after the burst at 5.5s
+        assert_aprx!(report.bursts[2].energy, 5.0 * 0.67 * 3.0, 0.001);
+    }
+
+    #[test] fn skills_on_cooldown_are_errors() {
+        let er = StatTable::of(&[(Stat::EnergyRecharge, 1.0)]);
+        let stats = [("xingqiu", &er), ("bennett", &er)];
+        let mut check = duo();
+        check.skill(0.0, SkillCast::new("bennett", "press", 1.0, "bennett"))
+            .skill(3.0, SkillCast::new("bennett", "press", 1.0, "bennett"));
+        assert!(check.check(&stats).unwrap_err().to_string().contains("cooldown"));
+        let mut check = duo();
+        check.skill(0.0, SkillCast::new("bennett", "press", 1.0, "bennett"))
+            .skill(5.0, SkillCast::new("bennett", "press", 1.0, "bennett"));
+        assert!(check.check(&stats).is_ok());
+    }
+}
@@ -1,92 +1,145 @@
 pub mod energy_formulas {
     use crate::energy::specification::*;
+    use crate::core::types::Element;
 
-    /// compute the total energy of a character given num party members, their energy recharge and particles/orbs consumbed
-    /// this is the most basic abstraction to calculate total energy stored for a character
-    pub fn calculate_energy(
-        num_party_memebers: i8,
-        energy_recharge: f32,
+    /// whether energy drops as a particle or an orb
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DropKind {
+        Particle,
+        Orb, //orb is just a particle multiplied by 6
+    }
 
-        //speciify energy and orbs pool consumed by character
-        same_particals_caught: f32,
-        different_particals_caught: f32,
-        white_particals_caught: f32,
+    /// particles or orbs of an element a character receives
+    /// Element::None is a clear (white) drop
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct EnergySource {
+        pub element: Element,
+        pub kind: DropKind,
+        pub count: f32,
+        pub on_field: bool, //whether the receiver is on field when the drop is caught
+    }
 
-        same_particals_not_caught: f32,
-        different_particals_not_caught: f32,
-        white_particals_not_caught: f32,
+    impl EnergySource {
+        pub fn particles(element: Element, count: f32, on_field: bool) -> Self {
+            Self { element, kind: DropKind::Particle, count, on_field }
+        }
+
+        pub fn orbs(element: Element, count: f32, on_field: bool) -> Self {
+            Self { element, kind: DropKind::Orb, count, on_field }
+        }
+
+        /// energy the source gives a character of an element before energy recharge
+        pub fn energy(&self, receiver_element: Element, num_party_members: i8) -> f32 {
+            let element_multiplier = if self.element == Element::None {
+                NO_ELEMENT
+            } else if self.element == receiver_element {
+                SAME_ELEMENT
+            } else {
+                DIFFERENT_ELEMENT
+            };
+            let kind_multiplier = match self.kind {
+                DropKind::Particle => 1,
+                DropKind::Orb => ORB_MULTIPLIER,
+            };
+            let field_multiplier = if self.on_field { 1.0 } else { off_field_multiplier(num_party_members) };
+            self.count * element_multiplier as f32 * kind_multiplier as f32 * field_multiplier
+        }
+    }
 
-        same_orbs_caught: f32,
-        different_orbs_caught: f32,
-        white_orbs_caught: f32,
+    /// energy a proc gives, flat energy is not affected by energy recharge
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum ProcReward {
+        Flat(f32),
+        Source(EnergySource),
+    }
 
-        same_orbs_not_caught: f32,
-        different_orbs_not_caught: f32,
-        white_orbs_not_caught: f32
-    ) -> f32 {
-        assert!(num_party_memebers > 0 || num_party_memebers <= 4);
+    /// energy gained with a chance on hit (eg: favonius weapons on crit)
+    /// who catches particle rewards on field is up to the team, not the proc
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct EnergyProc {
+        pub chance: f32, // chance per hit, multiplied by crit rate for crit procs
+        pub on_crit: bool,
+        pub hits: f32, // hits per proc window (eg: hits within the cooldown of the proc)
+        pub windows: f32, // proc windows per rotation
+        pub reward: ProcReward,
+    }
+
+    impl EnergyProc {
+        /// favonius weapons: crits have a 60-100% chance to give 3 clear particles, on a 12-6s cooldown
+        pub fn favonius(refinement: i8, hits: f32, windows: f32) -> Self {
+            let refinement = refinement.clamp(1, 5) as f32;
+            Self {
+                chance: 0.5 + 0.1 * refinement,
+                on_crit: true,
+                hits,
+                windows,
+                reward: ProcReward::Source(EnergySource::particles(Element::None, 3.0, true)),
+            }
+        }
+
+        /// chance the proc triggers at least once in a window
+        pub fn window_chance(&self, crit_rate: f32) -> f32 {
+            let chance = if self.on_crit { self.chance * crit_rate.clamp(0.0, 1.0) } else { self.chance };
+            1.0 - (1.0 - chance.clamp(0.0, 1.0)).powf(self.hits)
+        }
+
+        /// expected procs per rotation
+        pub fn expected_procs(&self, crit_rate: f32) -> f32 {
+            self.windows * self.window_chance(crit_rate)
+        }
+
+        /// expected reward per rotation
+        pub fn expected_reward(&self, crit_rate: f32) -> ProcReward {
+            let procs = self.expected_procs(crit_rate);
+            match self.reward {
+                ProcReward::Flat(energy) => ProcReward::Flat(energy * procs),
+                ProcReward::Source(source) => ProcReward::Source(EnergySource { count: source.count * procs, ..source }),
+            }
+        }
+    }
 
-        let off_field_multiplier = match num_party_memebers {
+    /// energy multiplier of drops caught off field
+    pub fn off_field_multiplier(num_party_members: i8) -> f32 {
+        assert!((1..=4).contains(&num_party_members));
+        match num_party_members {
             4 => OFF_FIELD_4_PARTY_MULTIPLIER,
             3 => OFF_FIELD_3_PARTY_MULTIPLIER,
             2 => OFF_FIELD_2_PARTY_MULTIPLIER,
             _ => 0.,
-        };
-
-        let mut total_energy = 0.;
-        total_energy += same_particals_caught * SAME_ELEMENT as f32;
-        total_energy += different_particals_caught * DIFFERENT_ELEMENT as f32;
-        total_energy += white_particals_caught * NO_ELEMENT as f32;
-
-        total_energy += same_particals_not_caught * SAME_ELEMENT as f32 * off_field_multiplier;
-        total_energy += different_particals_not_caught * DIFFERENT_ELEMENT as f32 * off_field_multiplier;
-        total_energy += white_particals_not_caught * NO_ELEMENT as f32 * off_field_multiplier;
-
-        total_energy += same_orbs_caught * SAME_ELEMENT as f32 * ORB_MULTIPLIER as f32;
-        total_energy += different_orbs_caught * DIFFERENT_ELEMENT as f32 * ORB_MULTIPLIER as f32;
-        total_energy += white_orbs_caught * NO_ELEMENT as f32 * ORB_MULTIPLIER as f32;
-
-        total_energy += same_orbs_not_caught * SAME_ELEMENT as f32 * off_field_multiplier * ORB_MULTIPLIER as f32;
-        total_energy += different_orbs_not_caught * DIFFERENT_ELEMENT as f32 * off_field_multiplier * ORB_MULTIPLIER as f32;
-        total_energy += white_orbs_not_caught * NO_ELEMENT as f32 * off_field_multiplier * ORB_MULTIPLIER as f32;
+        }
+    }
 
-        total_energy *= energy_recharge;
-        
-        total_energy
+    /// compute the total energy of a character given num party members, their energy recharge and particles/orbs consumbed
+    /// this is the most basic abstraction to calculate total energy stored for a character
+    pub fn calculate_energy(
+        num_party_members: i8,
+        energy_recharge: f32,
+        receiver_element: Element,
+        sources: &[EnergySource],
+    ) -> f32 {
+        sources.iter()
+            .map(|s| s.energy(receiver_element, num_party_members))
+            .sum::<f32>() * energy_recharge
     }
 
-    /// calculate the total amount of energy needed for a character to burst per rotation given the amount of particles/orbs consumed in a team
-    /// this is a thin wrapper around calculate total energy
+    /// calculate the energy recharge a character needs to burst once per rotation given the particles/orbs consumed in a team
+    /// flat energy (eg: from a burst refund) is subtracted from the burst cost
     pub fn calculate_energy_recharge_requirements(
-        num_party_memebers: i8,
-        burst_cost: i8,
-
-        same_particals_caught: f32,
-        different_particals_caught: f32,
-        white_particals_caught: f32,
-
-        same_particals_not_caught: f32,
-        different_particals_not_caught: f32,
-        white_particals_not_caught: f32,
-
-        same_orbs_caught: f32,
-        different_orbs_caught: f32,
-        white_orbs_caught: f32,
-
-        same_orbs_not_caught: f32,
-        different_orbs_not_caught: f32,
-        white_orbs_not_caught: f32
-    ) -> f32{
-        let energy = calculate_energy(
-            num_party_memebers, 1.0,
-            same_particals_caught, different_particals_caught, white_particals_caught,
-            same_particals_not_caught, different_particals_not_caught, white_particals_not_caught,
-            same_orbs_caught, different_orbs_caught, white_orbs_caught,
-            same_orbs_not_caught, different_orbs_not_caught, white_orbs_not_caught,
-        );
-        //let energy_needed_to_burst = energy - burst_cost;
-        let energy_recharge_requirements = burst_cost as f32/energy ;
-        energy_recharge_requirements
+        num_party_members: i8,
+        burst_cost: f32,
+        flat_energy: f32,
+        receiver_element: Element,
+        sources: &[EnergySource],
+    ) -> f32 {
+        let needed = burst_cost - flat_energy;
+        if needed <= 0.0 {
+            return 0.0;
+        }
+        let energy = calculate_energy(num_party_members, 1.0, receiver_element, sources);
+        if energy <= 0.0 {
+            return f32::INFINITY; // no energy recharge is enough
+        }
+        needed / energy
     }
 
 
@@ -94,32 +147,51 @@ pub mod energy_formulas {
     use super::*;
 
     #[test] fn test_calculate_energy_no_particles_or_orbs() {
-        let energy = calculate_energy(
-            4, 1.0,
-            0., 0., 0.,
-            0., 0., 0.,
-            0., 0., 0.,
-            0., 0., 0.,
-        );
-        //println!("{:?}", energy)
+        let energy = calculate_energy(4, 1.0, Element::Pyro, &[]);
         assert!(energy==0.)
     }
 
     #[test] fn calculate_energy_recharge_requirements_from_calculate_energy_function() {
-        let energy = calculate_energy(
-            4, 1.0,
-            (3. + 3.), 0., 0.,
-            3., (6.+4.+4.), 4.,
-            //ignore orbs, these come from enemies
-            0., 0., 0.,
-            0., 0., 0.,
-        );
-
-        let burst_cost = 80.;
-        //let energy_needed_to_burst = energy - burst_cost;
-        let energy_recharge_requirements = burst_cost/energy ;
-        println!("{:?}", energy_recharge_requirements)
-        //assert!(energy==0.)
+        let sources = [
+            EnergySource::particles(Element::Pyro, 3. + 3., true),
+            EnergySource::particles(Element::Pyro, 3., false),
+            EnergySource::particles(Element::Hydro, 6. + 4. + 4., false),
+            EnergySource::particles(Element::None, 4., false),
+        ];
+        // 6 * 3 + 3 * 3 * 0.6 + 14 * 0.6 + 4 * 2 * 0.6 = 36.6
+        let energy = calculate_energy(4, 1.0, Element::Pyro, &sources);
+        assert!((energy - 36.6).abs() < 0.001);
+        assert!((calculate_energy(4, 1.5, Element::Pyro, &sources) - 54.9).abs() < 0.001);
+
+        let energy_recharge_requirements = calculate_energy_recharge_requirements(4, 80., 0., Element::Pyro, &sources);
+        assert!((energy_recharge_requirements - 80. / 36.6).abs() < 0.001);
+        assert!((calculate_energy_recharge_requirements(4, 80., 8., Element::Pyro, &sources) - 72. / 36.6).abs() < 0.001);
+        // flat energy covers the burst without any particles
+        assert_eq!(calculate_energy_recharge_requirements(4, 40., 50., Element::Pyro, &[]), 0.0);
     }
+
+    #[test] fn orbs_are_worth_six_particles() {
+        let orb = calculate_energy(2, 1.0, Element::Geo, &[EnergySource::orbs(Element::Geo, 1., false)]);
+        let particles = calculate_energy(2, 1.0, Element::Geo, &[EnergySource::particles(Element::Geo, 6., false)]);
+        assert_eq!(orb, particles);
+        assert!((orb - 6. * 3. * 0.8).abs() < 0.001);
+    }
+
+    #[test] fn crit_procs_scale_with_crit_rate() {
+        let favonius = EnergyProc::favonius(1, 1.0, 2.0);
+        assert!((favonius.expected_procs(0.5) - 0.6).abs() < 0.001);
+        assert_eq!(favonius.expected_procs(0.0), 0.0);
+        // two hits per window: 1 - (1 - 0.3)^2
+        let two_hits = EnergyProc::favonius(1, 2.0, 1.0);
+        assert!((two_hits.window_chance(0.5) - 0.51).abs() < 0.001);
+        match EnergyProc::favonius(5, 1.0, 1.0).expected_reward(1.0) {
+            ProcReward::Source(source) => assert_eq!(source.count, 3.0),
+            ProcReward::Flat(_) => panic!("favonius gives particles"),
+        }
+    }
+
+    #[test] #[should_panic] fn party_size_is_checked() {
+        off_field_multiplier(5);
+    }
+}
 }
-}
\ No newline at end of file
@@ -0,0 +1,353 @@
+use crate::core::constraint::Constraint;
+use crate::core::stattable::StatTable;
+use crate::core::types::Stat;
+use crate::energy::energy_formulas::energy_formulas::{calculate_energy_recharge_requirements, EnergyProc, EnergySource, ProcReward};
+use crate::energy::specification::{energy_sources, find_member, find_name, refers_to, sample_energy_sources, CharacterEnergySpec, EnemyDrop, SkillCast};
+use crate::utils::rng::Rng;
+use anyhow::{Result, anyhow};
+
+/// burst reliabilities kqm style energy recharge calculators report
+pub const RELIABILITIES: [f32; 4] = [0.5, 0.75, 0.9, 1.0];
+
+/// energy recharge of a character without any buffs
+pub const BASE_ENERGY_RECHARGE: f32 = 1.0;
+
+/// energy of a team over one rotation
+/// every member bursts once per rotation unless set otherwise
+pub struct TeamEnergy {
+    team: Vec<CharacterEnergySpec>,
+    casts: Vec<SkillCast>,
+    extra_sources: Vec<(String, EnergySource)>, // receiver, source (eg: enemy drops)
+    flat: Vec<(String, f32)>, // receiver, energy not affected by energy recharge
+    bursts: Vec<(String, f32)>,
+}
+
+impl TeamEnergy {
+    pub fn new(team: Vec<CharacterEnergySpec>) -> Self {
+        Self { team, casts: Vec::new(), extra_sources: Vec::new(), flat: Vec::new(), bursts: Vec::new() }
+    }
+
+    pub fn cast(&mut self, cast: SkillCast) -> &mut Self {
+        self.casts.push(cast);
+        self
+    }
+
+    /// adds energy a character receives outside of skill casts
+    pub fn add_source(&mut self, character: &str, source: EnergySource) -> &mut Self {
+        self.extra_sources.push((String::from(character), source));
+        self
+    }
+
+    /// adds energy a character receives regardless of energy recharge (eg: a constellation or a teammate's burst refund)
+    pub fn add_flat_energy(&mut self, character: &str, energy: f32) -> Result<&mut Self> {
+        let member = find_member(&self.team, character)?;
+        self.flat.push((member.character_name.clone(), energy));
+        Ok(self)
+    }
+
+    /// adds the expected energy of a proc of a character, crit procs use the crit rate of the character's stats
+    /// flat energy goes to the character, particles are caught like enemy drops by the character on field
+    /// procs are counted at their expected value, monte carlo does not sample them
+    pub fn add_proc(&mut self, character: &str, proc: &EnergyProc, on_field: &str, stats: &StatTable) -> Result<&mut Self> {
+        find_member(&self.team, character)?;
+        match proc.expected_reward(stats.get(&Stat::CritRate)) {
+            ProcReward::Flat(energy) => self.add_flat_energy(character, energy),
+            ProcReward::Source(source) => {
+                let catcher = find_member(&self.team, on_field)?;
+                for member in &self.team {
+                    let on_field = member.character_name == catcher.character_name;
+                    self.extra_sources.push((member.character_name.clone(), EnergySource { on_field, ..source }));
+                }
+                Ok(self)
+            }
+        }
+    }
+
+    /// adds enemy drops, the character on field catches them and the rest of the team gets the off field share
+    pub fn add_enemy_drops(&mut self, drops: &[EnemyDrop], on_field: &str) -> Result<&mut Self> {
+        let catcher = find_member(&self.team, on_field)?;
+        for member in &self.team {
+            let caught = member.character_name == catcher.character_name;
+            for drop in drops {
+                self.extra_sources.push((member.character_name.clone(), drop.to_source(caught)));
+            }
+        }
+        Ok(self)
+    }
+
+    /// sets how many times a character bursts per rotation
+    pub fn set_bursts(&mut self, character: &str, bursts: f32) -> &mut Self {
+        self.bursts.retain(|(name, _)| name != character);
+        self.bursts.push((String::from(character), bursts));
+        self
+    }
+
+    /// every energy source of every member
+    pub fn sources(&self) -> Result<std::collections::HashMap<String, Vec<EnergySource>>> {
+        self.with_extra_sources(energy_sources(&self.team, &self.casts)?)
+    }
+
+    /// energy recharge every member needs to burst as often as set per rotation
+    pub fn solve(&self) -> Result<ErRequirements> {
+        if self.team.is_empty() || self.team.len() > 4 {
+            return Err(anyhow!("a team has 1 to 4 members, got {}", self.team.len()));
+        }
+        let sources = self.sources()?;
+        let mut requirements = std::collections::HashMap::new();
+        for member in &self.team {
+            let requirement = self.requirement(member, &sources[&member.character_name]);
+            if !requirement.is_finite() {
+                return Err(anyhow!("{} receives no energy", member.character_name));
+            }
+            requirements.insert(member.character_name.clone(), requirement);
+        }
+        Ok(ErRequirements { requirements, flat: self.flat_energies() })
+    }
+
+    /// samples the particles of every skill cast over many rotations
+    /// every rotation is independent, a rotation without energy for a member is a sample no energy recharge reaches
+    pub fn solve_monte_carlo(&self, rotations: usize, seed: u64) -> Result<ErDistribution> {
+        if rotations == 0 {
+            return Err(anyhow!("monte carlo needs at least one rotation"));
+        }
+        self.solve()?;
+        let mut rng = Rng::new(seed);
+        let mut samples: std::collections::HashMap<String, Vec<f32>> = self.team.iter()
+            .map(|c| (c.character_name.clone(), Vec::with_capacity(rotations)))
+            .collect();
+        for _ in 0..rotations {
+            let sources = self.with_extra_sources(sample_energy_sources(&self.team, &self.casts, &mut rng)?)?;
+            for member in &self.team {
+                let requirement = self.requirement(member, &sources[&member.character_name]);
+                samples.get_mut(&member.character_name).unwrap().push(requirement);
+            }
+        }
+        for requirements in samples.values_mut() {
+            requirements.sort_by(|a, b| a.total_cmp(b));
+        }
+        Ok(ErDistribution { samples, flat: self.flat_energies() })
+    }
+
+    /// flat energy of every member per rotation
+    fn flat_energies(&self) -> std::collections::HashMap<String, f32> {
+        self.team.iter().map(|member| (member.character_name.clone(), self.flat_energy(member))).collect()
+    }
+
+    fn flat_energy(&self, member: &CharacterEnergySpec) -> f32 {
+        self.flat.iter()
+            .filter(|(name, _)| refers_to(&self.team, name, member))
+            .map(|(_, energy)| *energy)
+            .sum()
+    }
+
+    fn with_extra_sources(&self, mut sources: std::collections::HashMap<String, Vec<EnergySource>>) -> Result<std::collections::HashMap<String, Vec<EnergySource>>> {
+        for (character, source) in &self.extra_sources {
+            let member = find_member(&self.team, character)?;
+            sources.get_mut(&member.character_name).unwrap().push(*source);
+        }
+        Ok(sources)
+    }
+
+    /// energy recharge a member needs to burst as often as set with the energy of the given sources
+    /// never below the base 100% energy recharge every character has
+    fn requirement(&self, member: &CharacterEnergySpec, sources: &[EnergySource]) -> f32 {
+        let bursts = self.bursts.iter()
+            .rfind(|(name, _)| refers_to(&self.team, name, member))
+            .map(|(_, bursts)| *bursts)
+            .unwrap_or(1.0);
+        let cost = bursts * member.burst_energy as f32;
+        let flat = bursts * member.burst_discount as f32 + self.flat_energy(member);
+        let requirement = calculate_energy_recharge_requirements(self.team.len() as i8, cost, flat, member.element, sources)
+            - member.additional_er.unwrap_or(0.0);
+        requirement.max(BASE_ENERGY_RECHARGE)
+    }
+}
+
+/// sampled energy recharge requirements of every team member, one per rotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErDistribution {
+    samples: std::collections::HashMap<String, Vec<f32>>, // sorted
+    flat: std::collections::HashMap<String, f32>,
+}
+
+impl ErDistribution {
+    /// energy recharge a character needs to burst in at least a fraction of rotations
+    /// eg: requirement("xingqiu", 0.9) bursts on cooldown 90% of the time
+    /// errors if the character gathers no energy in too many rotations to reach the reliability
+    pub fn requirement(&self, character: &str, reliability: f32) -> Result<f32> {
+        let name = find_name(self.samples.keys().map(String::as_str), character)?;
+        let samples = &self.samples[name];
+        let index = ((reliability.clamp(0.0, 1.0) * samples.len() as f32).ceil() as usize).clamp(1, samples.len()) - 1;
+        if !samples[index].is_finite() {
+            let reachable = samples.iter().filter(|x| x.is_finite()).count() as f32 / samples.len() as f32;
+            return Err(anyhow!("{} gathers energy in only {:.1}% of rotations, {:.1}% reliability is unreachable", name, 100.0 * reachable, 100.0 * reliability));
+        }
+        Ok(samples[index])
+    }
+
+    /// requirements of every member at a reliability, a drop in for the deterministic solver
+    pub fn requirements(&self, reliability: f32) -> Result<ErRequirements> {
+        let requirements = self.samples.keys()
+            .map(|name| Ok((name.clone(), self.requirement(name, reliability)?)))
+            .collect::<Result<_>>()?;
+        Ok(ErRequirements { requirements, flat: self.flat.clone() })
+    }
+}
+
+/// energy recharge every team member needs
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErRequirements {
+    requirements: std::collections::HashMap<String, f32>,
+    flat: std::collections::HashMap<String, f32>, // flat energy per rotation, already subtracted from the burst cost
+}
+
+impl ErRequirements {
+    pub fn get(&self, character: &str) -> Result<f32> {
+        Ok(self.requirements[find_name(self.requirements.keys().map(String::as_str), character)?])
+    }
+
+    /// flat energy a character gets per rotation besides their burst refund
+    pub fn flat_energy(&self, character: &str) -> Result<f32> {
+        Ok(self.flat[find_name(self.flat.keys().map(String::as_str), character)?])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &f32)> {
+        self.requirements.iter()
+    }
+
+    /// optimizer constraint for the energy recharge of a character
+    pub fn constraint(&self, character: &str) -> Result<Constraint> {
+        Ok(Constraint::energy_recharge(self.get(character)?))
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::types::Element;
+    use crate::functions::stat_factory::StatFactory;
+
+    fn national() -> TeamEnergy {
+        let team = ["raiden", "xiangling", "xingqiu", "bennett"].iter()
+            .map(|name| StatFactory::get_character_energy_spec(name).unwrap())
+            .collect();
+        let mut energy = TeamEnergy::new(team);
+        energy.cast(SkillCast::new("bennett", "press", 2.0, "bennett"))
+            .cast(SkillCast::new("xiangling", "guoba", 1.0, "xiangling"))
+            .cast(SkillCast::new("xingqiu", "press", 1.0, "xingqiu"))
+            .cast(SkillCast::new("raiden", "eye of stormy judgement", 1.0, "xiangling"));
+        energy
+    }
+
+    #[test] fn solves_every_member() {
+        let requirements = national().solve().unwrap();
+        // xingqiu: 4.5 hydro particles on field, 5 + 4 pyro and 5 electro off field
+        let xingqiu_energy = 4.5 * 3.0 + (5.0 + 4.0 + 5.0) * 0.6;
+        assert!((requirements.get("xingqiu").unwrap() - 80.0 / xingqiu_energy).abs() < 0.001);
+        assert_eq!(requirements.iter().count(), 4);
+        assert!(requirements.get("sucrose").is_err());
+    }
+
+    #[test] fn extra_sources_and_bursts_change_requirements() {
+        let base = national().solve().unwrap().get("bennett").unwrap();
+        let mut energy = national();
+        energy.add_source("bennett", EnergySource::orbs(Element::None, 1.0, true));
+        assert!(energy.solve().unwrap().get("bennett").unwrap() < base);
+        energy.set_bursts("bennett", 2.0);
+        assert!(energy.solve().unwrap().get("bennett").unwrap() > base);
+    }
+
+    #[test] fn requirements_feed_optimizer_constraints() {
+        use crate::core::stattable::StatTable;
+        use crate::core::types::Stat;
+        let requirements = national().solve().unwrap();
+        let constraint = requirements.constraint("xingqiu").unwrap();
+        let needed = requirements.get("xingqiu").unwrap();
+        assert!(constraint.is_satisfied(&StatTable::of(&[(Stat::EnergyRecharge, needed + 0.01)])));
+        assert!(!constraint.is_satisfied(&StatTable::of(&[(Stat::EnergyRecharge, needed - 0.01)])));
+    }
+
+    #[test] fn members_without_energy_are_errors() {
+        let team = vec![StatFactory::get_character_energy_spec("bennett").unwrap()];
+        assert!(TeamEnergy::new(team).solve().is_err());
+        assert!(TeamEnergy::new(Vec::new()).solve().is_err());
+    }
+
+    #[test] fn flat_energy_can_cover_the_burst() {
+        let team = vec![StatFactory::get_character_energy_spec("bennett").unwrap()];
+        let mut energy = TeamEnergy::new(team);
+        energy.add_flat_energy("bennett", 60.0).unwrap();
+        assert_eq!(energy.solve().unwrap().get("bennett").unwrap(), BASE_ENERGY_RECHARGE);
+        // razor's additional energy recharge does not take the requirement below the base
+        let mut razor = TeamEnergy::new(vec![StatFactory::get_character_energy_spec("razor").unwrap()]);
+        razor.add_flat_energy("razor", 80.0).unwrap();
+        assert_eq!(razor.solve().unwrap().get("razor").unwrap(), BASE_ENERGY_RECHARGE);
+    }
+
+    #[test] fn monte_carlo_requirements_grow_with_reliability() {
+        let energy = national();
+        let distribution = energy.solve_monte_carlo(5000, 42).unwrap();
+        assert_eq!(distribution, energy.solve_monte_carlo(5000, 42).unwrap());
+        let xingqiu: Vec<f32> = RELIABILITIES.iter().map(|r| distribution.requirement("xingqiu", *r).unwrap()).collect();
+        assert!(xingqiu.windows(2).all(|x| x[0] <= x[1]));
+        assert!(xingqiu[0] < xingqiu[3]);
+        // the median is close to the deterministic requirement
+        let mean = energy.solve().unwrap().get("xingqiu").unwrap();
+        assert!((xingqiu[0] - mean).abs() < 0.05 * mean);
+        assert_eq!(distribution.requirements(0.9).unwrap().get("xingqiu").unwrap(), xingqiu[2]);
+        assert!(energy.solve_monte_carlo(0, 42).is_err());
+    }
+
+    #[test] fn unreachable_reliabilities_are_errors() {
+        // the eye gives no particles in about 0.4% of rotations
+        let mut energy = TeamEnergy::new(vec![StatFactory::get_character_energy_spec("raiden").unwrap()]);
+        energy.cast(SkillCast::new("raiden", "eye of stormy judgement", 1.0, "raiden"));
+        let distribution = energy.solve_monte_carlo(5000, 42).unwrap();
+        assert!(distribution.requirement("raiden", 0.5).unwrap().is_finite());
+        assert!(distribution.requirement("raiden", 1.0).is_err());
+        assert!(distribution.requirements(1.0).is_err());
+    }
+
+    #[test] fn enemy_drops_lower_requirements() {
+        use crate::energy::specification::{DropEncounter, EnemyDropTable};
+        let base = national().solve().unwrap();
+        let mut floor = DropEncounter::new(3.0).unwrap();
+        floor.kill(EnemyDropTable::boss());
+        let mut energy = national();
+        energy.add_enemy_drops(&floor.drops_per_rotation(), "xiangling").unwrap();
+        let with_drops = energy.solve().unwrap();
+        assert!(with_drops.get("xiangling").unwrap() < base.get("xiangling").unwrap());
+        assert!(with_drops.get("bennett").unwrap() < base.get("bennett").unwrap());
+        assert!(energy.add_enemy_drops(&[], "sucrose").is_err());
+    }
+
+    #[test] fn flat_energy_and_procs_lower_requirements() {
+        let base = national().solve().unwrap();
+        let mut energy = national();
+        energy.add_flat_energy("xingqiu", 10.0).unwrap();
+        let requirements = energy.solve().unwrap();
+        assert_eq!(requirements.flat_energy("xingqiu").unwrap(), 10.0);
+        let xingqiu_energy = 4.5 * 3.0 + (5.0 + 4.0 + 5.0) * 0.6;
+        assert!((requirements.get("xingqiu").unwrap() - 70.0 / xingqiu_energy).abs() < 0.001);
+
+        let favonius = EnergyProc::favonius(1, 3.0, 1.0);
+        let mut no_crit = national();
+        no_crit.add_proc("bennett", &favonius, "bennett", &StatTable::new()).unwrap();
+        assert_eq!(no_crit.solve().unwrap().get("bennett").unwrap(), base.get("bennett").unwrap());
+        let mut crit = national();
+        crit.add_proc("bennett", &favonius, "bennett", &StatTable::of(&[(Stat::CritRate, 0.6)])).unwrap();
+        assert!(crit.solve().unwrap().get("bennett").unwrap() < base.get("bennett").unwrap());
+        assert!(energy.add_flat_energy("sucrose", 10.0).is_err());
+        assert!(energy.add_proc("sucrose", &favonius, "bennett", &StatTable::new()).is_err());
+    }
+
+    #[test] fn proc_particles_are_spread_over_the_team() {
+        let favonius = EnergyProc::favonius(5, 1.0, 1.0);
+        let mut energy = national();
+        energy.add_proc("bennett", &favonius, "xiangling", &StatTable::of(&[(Stat::CritRate, 1.0)])).unwrap();
+        let sources = energy.sources().unwrap();
+        // 3 clear particles, caught on field by xiangling
+        assert_eq!(*sources["Xiangling"].last().unwrap(), EnergySource::particles(Element::None, 3.0, true));
+        assert_eq!(*sources["Bennett"].last().unwrap(), EnergySource::particles(Element::None, 3.0, false));
+        assert_eq!(*sources["Raiden Shogun"].last().unwrap(), EnergySource::particles(Element::None, 3.0, false));
+    }
+}
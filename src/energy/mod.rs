@@ -1,2 +1,4 @@
 pub mod specification;
-pub mod energy_formulas;
\ No newline at end of file
+pub mod energy_formulas;
+pub mod er_solver;
+pub mod burst_check;
@@ -1,19 +1,32 @@
 use crate::core::types::Element;
 
+use crate::data::irminsul_adaptor::NamedJSON;
+use crate::utils::standardize::flatten_str;
+use crate::functions::stat_factory::StatFactory;
+use crate::utils::rng::Rng;
+use crate::energy::energy_formulas::energy_formulas::{DropKind, EnergySource};
+use crate::core::encounter::{Encounter, EncounterResult};
+use crate::core::enemy::Enemy;
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
 //character energy data
 
 /**
  * information about character energy.
  * includes datamined and gameplay tested information not found in game
  */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CharacterEnergySpec{
-    character_name: String,
-    element: Element,
-    skills: [SkillEnergySpec;3],
-    burst_cooldown: f32,
-    burst_energy: i8,
-    burst_discount: i8,
-    addtional_er: Option<i8>, //mainly for razor
+    pub character_name: String,
+    pub element: Element,
+    pub skills: Vec<SkillEnergySpec>,
+    pub burst_cooldown: f32,
+    pub burst_energy: i8,
+    #[serde(default)]
+    pub burst_discount: i8, //energy refunded after bursting
+    #[serde(default)]
+    pub additional_er: Option<f32>, //mainly for razor
 
     //addtional string info
     //help flexable
@@ -25,16 +38,132 @@ pub struct CharacterEnergySpec{
 /**
  * energy information about a character elemetnal skill
  */
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SkillEnergySpec{
-    label: String,
-    particles: f32,
-    variance: f32,
-    per_second: f32,
-    duration: i8,
-    cool_down: f32
+    pub label: String,
+    pub particles: f32, //particles on cast
+    pub variance: f32,
+    pub per_second: f32, //particles per second over the duration (eg: turrets)
+    pub duration: i8,
+    pub cool_down: f32
     
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CharacterEnergySpecList {
+    pub data: Vec<CharacterEnergySpec>,
+}
+
+impl NamedJSON for CharacterEnergySpec {
+    fn name(&self) -> &str {
+        &self.character_name
+    }
+}
+
+impl CharacterEnergySpec {
+    /// energy spec of a skill by label (eg: "press", "hold")
+    pub fn skill(&self, label: &str) -> Result<&SkillEnergySpec> {
+        self.skills.iter()
+            .find(|s| flatten_str(&s.label) == flatten_str(label))
+            .ok_or_else(|| anyhow!("no skill {} found for {}", label, self.character_name))
+    }
+}
+
+impl SkillEnergySpec {
+    /// average particles of one cast
+    pub fn particles_per_cast(&self) -> f32 {
+        self.particles + self.per_second * self.duration as f32
+    }
+
+    /// samples the particles of one cast
+    /// a cast gives particles - variance or particles + variance with equal chance (eg: 4.5 +- 0.5 is 4 or 5 particles)
+    /// turrets roll a particle every second with per second as the chance, or give a fixed amount above 1 per second
+    pub fn sample_particles(&self, rng: &mut Rng) -> f32 {
+        let on_cast = if rng.chance(0.5) { self.particles - self.variance } else { self.particles + self.variance };
+        let over_duration = if self.per_second <= 1.0 {
+            (0..self.duration).filter(|_| rng.chance(self.per_second)).count() as f32
+        } else {
+            self.per_second * self.duration as f32
+        };
+        on_cast.max(0.0) + over_duration
+    }
+
+    /// samples the particles of a number of casts, a fractional cast gives its share of the average
+    pub fn sample_casts(&self, count: f32, rng: &mut Rng) -> f32 {
+        let whole = count.floor();
+        (0..whole as u32).map(|_| self.sample_particles(rng)).sum::<f32>()
+            + (count - whole) * self.particles_per_cast()
+    }
+}
+
+/// a skill cast of a team rotation, its particles are caught by the character on field
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkillCast {
+    pub character: String,
+    pub skill: String,
+    pub count: f32, // casts per rotation
+    pub on_field: String,
+}
+
+impl SkillCast {
+    pub fn new(character: &str, skill: &str, count: f32, on_field: &str) -> Self {
+        Self { character: String::from(character), skill: String::from(skill), count, on_field: String::from(on_field) }
+    }
+}
+
+/// particles every team member receives from the skill casts of a rotation
+pub fn energy_sources(team: &[CharacterEnergySpec], casts: &[SkillCast]) -> Result<std::collections::HashMap<String, Vec<EnergySource>>> {
+    sources_with(team, casts, |skill, count| skill.particles_per_cast() * count)
+}
+
+/// particles every team member receives from the skill casts of one sampled rotation
+pub fn sample_energy_sources(team: &[CharacterEnergySpec], casts: &[SkillCast], rng: &mut Rng) -> Result<std::collections::HashMap<String, Vec<EnergySource>>> {
+    sources_with(team, casts, |skill, count| skill.sample_casts(count, rng))
+}
+
+fn sources_with(
+    team: &[CharacterEnergySpec],
+    casts: &[SkillCast],
+    mut particles_of: impl FnMut(&SkillEnergySpec, f32) -> f32
+) -> Result<std::collections::HashMap<String, Vec<EnergySource>>> {
+    let mut sources: std::collections::HashMap<String, Vec<EnergySource>> = team.iter()
+        .map(|c| (c.character_name.clone(), Vec::new()))
+        .collect();
+    for cast in casts {
+        let caster = find_member(team, &cast.character)?;
+        let catcher = find_member(team, &cast.on_field)?;
+        let particles = particles_of(caster.skill(&cast.skill)?, cast.count);
+        for member in team {
+            let on_field = member.character_name == catcher.character_name;
+            sources.get_mut(&member.character_name).unwrap().push(EnergySource::particles(caster.element, particles, on_field));
+        }
+    }
+    Ok(sources)
+}
+
+pub(crate) fn find_member<'a>(team: &'a [CharacterEnergySpec], name: &str) -> Result<&'a CharacterEnergySpec> {
+    let found = find_name(team.iter().map(|c| c.character_name.as_str()), name)?;
+    Ok(team.iter().find(|c| c.character_name == found).unwrap())
+}
+
+/// true if a name refers to a member of the team
+pub(crate) fn refers_to(team: &[CharacterEnergySpec], name: &str, member: &CharacterEnergySpec) -> bool {
+    find_member(team, name).is_ok_and(|c| c.character_name == member.character_name)
+}
+
+/// the name a name refers to, exactly or as a fuzzy match that fits only one name (eg: "raiden" for "Raiden Shogun")
+pub(crate) fn find_name<'a>(names: impl Iterator<Item = &'a str> + Clone, name: &str) -> Result<&'a str> {
+    if let Some(exact) = names.clone().find(|x| flatten_str(x) == flatten_str(name)) {
+        return Ok(exact);
+    }
+    let matches: Vec<&str> = names.filter(|x| StatFactory::fuzzy_match(name, x)).collect();
+    match matches.as_slice() {
+        [found] => Ok(found),
+        [] => Err(anyhow!("{} is not in the team", name)),
+        _ => Err(anyhow!("{} matches more than one member: {}", name, matches.join(", "))),
+    }
+}
+
 
 //energy constaints
 pub const SAME_ELEMENT: i8 = 3;
@@ -49,3 +178,221 @@ pub const OFF_FIELD_2_PARTY_MULTIPLIER: f32 = 0.8;
 
 
 //enemy particle energy
+
+/// particles or orbs an enemy drops
+/// drops from enemies are clear (Element::None) unless the enemy says otherwise
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnemyDrop {
+    pub element: Element,
+    pub kind: DropKind,
+    pub count: f32, // average count
+}
+
+impl EnemyDrop {
+    pub fn particles(count: f32) -> Self {
+        Self { element: Element::None, kind: DropKind::Particle, count }
+    }
+
+    pub fn orbs(count: f32) -> Self {
+        Self { element: Element::None, kind: DropKind::Orb, count }
+    }
+
+    /// the drop as received by a team member
+    pub fn to_source(&self, on_field: bool) -> EnergySource {
+        EnergySource { element: self.element, kind: self.kind, count: self.count, on_field }
+    }
+}
+
+/// drops of an enemy at hp thresholds and on kill
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnemyDropTable {
+    pub thresholds: Vec<(f32, EnemyDrop)>, // hp fraction left at which the drop happens, eg: 0.5 drops at half hp
+    pub kill: Vec<EnemyDrop>,
+}
+
+impl EnemyDropTable {
+    /// common enemies (eg: hilichurls, slimes) drop a clear particle about half the time on kill
+    pub fn common() -> Self {
+        Self { thresholds: Vec::new(), kill: vec![EnemyDrop::particles(0.5)] }
+    }
+
+    /// elite enemies (eg: mitachurls, abyss mages) drop a clear particle at half hp and two on kill
+    pub fn elite() -> Self {
+        Self { thresholds: vec![(0.5, EnemyDrop::particles(1.0))], kill: vec![EnemyDrop::particles(2.0)] }
+    }
+
+    /// bosses drop a clear orb every 25% of hp lost
+    pub fn boss() -> Self {
+        Self {
+            thresholds: [0.75, 0.5, 0.25].iter().map(|x| (*x, EnemyDrop::orbs(1.0))).collect(),
+            kill: vec![EnemyDrop::orbs(1.0)],
+        }
+    }
+
+    /// drops from full hp down to a fraction of hp left, 0 or less is a kill
+    pub fn drops_until(&self, hp_left: f32) -> Vec<EnemyDrop> {
+        let mut drops: Vec<EnemyDrop> = self.thresholds.iter()
+            .filter(|(threshold, _)| hp_left <= *threshold)
+            .map(|(_, drop)| *drop)
+            .collect();
+        if hp_left <= 0.0 {
+            drops.extend(self.kill.iter().cloned());
+        }
+        drops
+    }
+}
+
+/// enemies a team fights over a number of rotations, eg: a chamber half of an abyss floor
+#[derive(Debug, Clone, PartialEq)]
+pub struct DropEncounter {
+    enemies: Vec<(EnemyDropTable, f32)>, // drop table, hp fraction left at the end
+    rotations: f32,
+}
+
+impl DropEncounter {
+    /// errors if the number of rotations is not positive, encounters shorter than a rotation are fractions (eg: 0.5)
+    pub fn new(rotations: f32) -> Result<Self> {
+        if !(rotations > 0.0 && rotations.is_finite()) {
+            return Err(anyhow!("an encounter lasts a positive number of rotations, got {}", rotations));
+        }
+        Ok(Self { enemies: Vec::new(), rotations })
+    }
+
+    /// builds the drops of a wave encounter, every enemy is killed over the time to clear it
+    pub fn from_encounter(encounter: &Encounter, result: &EncounterResult, rotation_duration: f32, table_of: impl Fn(&Enemy) -> EnemyDropTable) -> Result<Self> {
+        let mut drops = Self::new(result.total_time / rotation_duration)?;
+        for enemy in encounter.waves().iter().flat_map(|w| w.enemies.iter()) {
+            drops.kill(table_of(enemy));
+        }
+        Ok(drops)
+    }
+
+    pub fn kill(&mut self, table: EnemyDropTable) -> &mut Self {
+        self.enemies.push((table, 0.0));
+        self
+    }
+
+    /// an enemy that is damaged but not killed (eg: a boss taken down to half hp)
+    pub fn damage(&mut self, table: EnemyDropTable, hp_left: f32) -> &mut Self {
+        self.enemies.push((table, hp_left));
+        self
+    }
+
+    /// average drops per rotation, merged by element and kind
+    pub fn drops_per_rotation(&self) -> Vec<EnemyDrop> {
+        let mut merged: Vec<EnemyDrop> = Vec::new();
+        for drop in self.enemies.iter().flat_map(|(table, hp_left)| table.drops_until(*hp_left)) {
+            match merged.iter_mut().find(|x| x.element == drop.element && x.kind == drop.kind) {
+                Some(x) => x.count += drop.count,
+                None => merged.push(drop),
+            }
+        }
+        for drop in merged.iter_mut() {
+            drop.count /= self.rotations;
+        }
+        merged
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn team() -> Vec<CharacterEnergySpec> {
+        ["raiden", "xiangling", "xingqiu", "bennett"].iter()
+            .map(|name| StatFactory::get_character_energy_spec(name).unwrap())
+            .collect()
+    }
+
+    #[test] fn energy_data_is_loaded() {
+        let bennett = StatFactory::get_character_energy_spec("bennett").unwrap();
+        assert_eq!(bennett.element, Element::Pyro);
+        assert_eq!(bennett.burst_energy, 60);
+        assert_eq!(bennett.skill("Press").unwrap().particles_per_cast(), 2.5);
+        assert!(bennett.skill("tap").is_err());
+        // turret particles are spread over the skill duration
+        let fischl = StatFactory::get_character_energy_spec("fischl").unwrap();
+        assert!((fischl.skills[0].particles_per_cast() - 6.7).abs() < 0.001);
+        assert!(StatFactory::get_character_energy_spec("nobody").is_err());
+    }
+
+    #[test] fn members_are_found_by_unambiguous_names() {
+        assert_eq!(find_member(&team(), "Xingqiu").unwrap().character_name, "Xingqiu");
+        assert_eq!(find_member(&team(), "raiden").unwrap().character_name, "Raiden Shogun");
+        // xiangling and xingqiu both match
+        assert!(find_member(&team(), "xing").unwrap_err().to_string().contains("more than one"));
+        assert!(find_member(&team(), "").is_err());
+        assert!(refers_to(&team(), "xq", &team()[2]));
+        assert!(!refers_to(&team(), "xing", &team()[2]));
+    }
+
+    #[test] fn particles_go_to_the_team_by_element_and_field() {
+        use crate::energy::energy_formulas::energy_formulas::calculate_energy;
+        let casts = [
+            SkillCast::new("bennett", "press", 2.0, "bennett"),
+            SkillCast::new("xiangling", "guoba", 1.0, "xingqiu"),
+        ];
+        let sources = energy_sources(&team(), &casts).unwrap();
+        assert_eq!(sources["Bennett"], vec![
+            EnergySource::particles(Element::Pyro, 5.0, true),
+            EnergySource::particles(Element::Pyro, 4.0, false),
+        ]);
+        assert_eq!(sources["Xingqiu"][1], EnergySource::particles(Element::Pyro, 4.0, true));
+        // xiangling is never on field: 9 pyro particles at 3 energy each with the 4 party off field multiplier
+        assert!((calculate_energy(4, 1.0, Element::Pyro, &sources["Xiangling"]) - 9.0 * 3.0 * 0.6).abs() < 0.001);
+        assert!(energy_sources(&team(), &[SkillCast::new("sucrose", "press", 1.0, "bennett")]).is_err());
+    }
+
+    #[test] fn sampled_particles_follow_variance() {
+        let mut rng = Rng::new(7);
+        let xingqiu = StatFactory::get_character_energy_spec("xingqiu").unwrap();
+        let press = xingqiu.skill("press").unwrap();
+        let samples: Vec<f32> = (0..1000).map(|_| press.sample_particles(&mut rng)).collect();
+        assert!(samples.iter().all(|x| *x == 4.0 || *x == 5.0));
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        assert!((mean - 4.5).abs() < 0.1);
+
+        // oz rolls 10 times at 67%
+        let oz = &StatFactory::get_character_energy_spec("fischl").unwrap().skills[0];
+        let mean = (0..1000).map(|_| oz.sample_particles(&mut rng)).sum::<f32>() / 1000.0;
+        assert!((mean - 6.7).abs() < 0.2);
+        assert_eq!(press.sample_casts(0.5, &mut rng), 2.25);
+    }
+
+    #[test] fn enemies_drop_at_hp_thresholds_and_on_kill() {
+        let boss = EnemyDropTable::boss();
+        assert_eq!(boss.drops_until(1.0).len(), 0);
+        assert_eq!(boss.drops_until(0.5).len(), 2);
+        assert_eq!(boss.drops_until(0.0).len(), 4);
+
+        let mut encounter = DropEncounter::new(2.0).unwrap();
+        encounter.kill(EnemyDropTable::elite()).kill(EnemyDropTable::common()).damage(EnemyDropTable::boss(), 0.6);
+        assert_eq!(encounter.drops_per_rotation(), vec![EnemyDrop::particles(1.75), EnemyDrop::orbs(0.5)]);
+    }
+
+    #[test] fn short_encounters_drop_more_per_rotation() {
+        let mut encounter = DropEncounter::new(0.5).unwrap();
+        encounter.kill(EnemyDropTable::elite());
+        let mut full = DropEncounter::new(1.0).unwrap();
+        full.kill(EnemyDropTable::elite());
+        let (half, once) = (encounter.drops_per_rotation(), full.drops_per_rotation());
+        assert_eq!(half[0].count, 2.0 * once[0].count);
+        assert!(DropEncounter::new(0.0).is_err());
+        assert!(DropEncounter::new(-1.0).is_err());
+        assert!(DropEncounter::new(f32::NAN).is_err());
+    }
+
+    #[test] fn wave_encounters_give_drops_per_rotation() {
+        use crate::core::calc_standard::CalcStandard;
+        use crate::core::encounter::{TeamDamage, Wave, WaveTargeting};
+        let mob = Enemy::from_standard("mob", &CalcStandard::KQMC).with_hp(100000.0);
+        let mut encounter = Encounter::new(Element::Pyro, &CalcStandard::KQMC);
+        encounter.add_wave(Wave::new(vec![mob.clone(), mob.clone()], 0.0, WaveTargeting::Aoe));
+        encounter.add_wave(Wave::new(vec![mob.with_hp(400000.0)], 0.0, WaveTargeting::Aoe));
+        let result = encounter.time_to_clear(&TeamDamage::Dps(10000.0)).unwrap();
+
+        // 50s to clear with 20s rotations
+        let table_of = |enemy: &Enemy| if enemy.hp.unwrap() > 200000.0 { EnemyDropTable::boss() } else { EnemyDropTable::common() };
+        let drops = DropEncounter::from_encounter(&encounter, &result, 20.0, table_of).unwrap().drops_per_rotation();
+        assert_eq!(drops, vec![EnemyDrop::particles(0.4), EnemyDrop::orbs(1.6)]);
+    }
+}
@@ -0,0 +1,283 @@
+//! c abi for embedding the calculator
+//! requests and responses are json strings, every response is {"ok": true, "result": ...} or {"ok": false, "error": "..."}
+//! strings returned by aminus must be freed with aminus_string_free
+
+use crate::core::calc_standard::CalcStandard;
+use crate::core::constraint::Constraint;
+use crate::core::rotation_spec::RotationSpec;
+use crate::core::stattable::StatTable;
+use crate::core::types::Stat;
+use crate::functions::optimizers::optimizers::{artifacts_5_star_stats, global_artifact_optimizer_with_standard, SubstatDistribution};
+use crate::functions::stat_factory::StatFactory;
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+/// a character or weapon by name
+#[derive(Debug, Clone, Deserialize)]
+struct Named {
+    name: String,
+    #[serde(default = "max_level")]
+    level: i8,
+}
+
+fn max_level() -> i8 {
+    90
+}
+
+/// 5 star artifacts, substats are rolls per stat and default to the standard's fixed rolls
+#[derive(Debug, Clone, Deserialize)]
+struct ArtifactsRequest {
+    sands: Stat,
+    goblet: Stat,
+    circlet: Stat,
+    #[serde(default)]
+    substats: Option<SubstatDistribution>,
+}
+
+/// a character build and the rotation to evaluate it on
+/// eg: {"character": {"name": "hu tao"}, "weapon": {"name": "staff of homa"}, "artifacts": {"sands": "HPPercent", "goblet": "PyroDMGBonus", "circlet": "CritRate"}, "rotation": {"actions": [...]}}
+#[derive(Debug, Clone, Deserialize)]
+struct BuildRequest {
+    character: Named,
+    #[serde(default)]
+    weapon: Option<Named>,
+    #[serde(default)]
+    artifacts: Option<ArtifactsRequest>,
+    #[serde(default)]
+    stats: Vec<(Stat, f32)>, // extra stats (eg: buffs)
+    rotation: RotationSpec,
+}
+
+impl BuildRequest {
+    /// character, weapon and extra stats without artifacts
+    fn base_stats(&self) -> Result<StatTable> {
+        let mut stats = StatFactory::get_character_base_stats(&self.character.name, self.character.level)?;
+        if let Some(weapon) = &self.weapon {
+            stats.add_table(StatFactory::get_weapon_base_stats(&weapon.name, weapon.level)?.iter());
+        }
+        stats.add_table(StatTable::of(&self.stats).iter());
+        Ok(stats)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OptimizeRequest {
+    #[serde(flatten)]
+    build: BuildRequest,
+    #[serde(default)]
+    energy_recharge: f32, // minimum energy recharge of the build
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct DamageResult {
+    damage: f32,
+    actions: std::collections::HashMap<String, f32>,
+    stats: std::collections::HashMap<Stat, f32>,
+}
+
+impl DamageResult {
+    fn new(spec: &RotationSpec, stats: &StatTable, standard: &CalcStandard) -> Self {
+        let rotation = spec.to_rotation(standard);
+        let actions = rotation.action_names()
+            .map(|name| (name.clone(), rotation.evaluate_action(name, stats).unwrap()))
+            .collect();
+        Self { damage: rotation.evaluate(stats), actions, stats: stats.iter().collect() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OptimizeResult {
+    main_stats: (Stat, Stat, Stat),
+    substats: SubstatDistribution,
+    #[serde(flatten)]
+    damage: DamageResult,
+}
+
+fn calculate(request: &str) -> Result<DamageResult> {
+    let request: BuildRequest = serde_json::from_str(request).context("failed to parse request")?;
+    let standard = CalcStandard::KQMC;
+    let mut stats = request.base_stats()?;
+    if let Some(artifacts) = &request.artifacts {
+        let mains = (artifacts.sands, artifacts.goblet, artifacts.circlet);
+        let artifact_stats = match &artifacts.substats {
+            Some(substats) => artifacts_5_star_stats(&mains, substats, &standard),
+            None => crate::core::artifact_builder::ArtifactBuilder::all_5_star_with_standard(mains.0, mains.1, mains.2, &standard).build(),
+        };
+        stats = stats.chain(artifact_stats);
+    }
+    Ok(DamageResult::new(&request.rotation, &stats, &standard))
+}
+
+fn optimize(request: &str) -> Result<OptimizeResult> {
+    let request: OptimizeRequest = serde_json::from_str(request).context("failed to parse request")?;
+    let standard = CalcStandard::KQMC;
+    let stats = request.build.base_stats()?;
+    let rotation = request.build.rotation.to_rotation(&standard);
+    let (main_stats, substats) = global_artifact_optimizer_with_standard(&stats, &rotation, &[Constraint::energy_recharge(request.energy_recharge)], &standard)?;
+    let stats = stats.chain(artifacts_5_star_stats(&main_stats, &substats, &standard));
+    Ok(OptimizeResult { main_stats, substats, damage: DamageResult::new(&request.build.rotation, &stats, &standard) })
+}
+
+/// runs a json handler behind the c boundary, errors and panics become error responses
+unsafe fn respond<T: Serialize>(request: *const c_char, handler: fn(&str) -> Result<T>) -> *mut c_char {
+    let response = std::panic::catch_unwind(|| {
+        if request.is_null() {
+            return Err(anyhow!("request is null"));
+        }
+        let request = CStr::from_ptr(request).to_str().context("request is not utf-8")?;
+        handler(request)
+    });
+    let json = match response {
+        Ok(Ok(result)) => serde_json::json!({"ok": true, "result": result}),
+        Ok(Err(e)) => serde_json::json!({"ok": false, "error": format!("{:#}", e)}),
+        Err(panic) => {
+            let message = panic.downcast_ref::<String>().cloned()
+                .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_else(|| String::from("unknown panic"));
+            serde_json::json!({"ok": false, "error": format!("panic: {}", message)})
+        }
+    };
+    CString::new(json.to_string()).map(CString::into_raw).unwrap_or(std::ptr::null_mut())
+}
+
+/// allocates a buffer of len bytes for the caller to write a request into, null for 0 bytes
+///
+/// # Safety
+/// the buffer must be freed with aminus_dealloc and the same len
+#[no_mangle]
+pub unsafe extern "C" fn aminus_alloc(len: usize) -> *mut u8 {
+    match std::alloc::Layout::array::<u8>(len) {
+        Ok(layout) if len > 0 => std::alloc::alloc(layout),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// frees a buffer from aminus_alloc
+///
+/// # Safety
+/// ptr must come from aminus_alloc with the same len and not be freed twice
+#[no_mangle]
+pub unsafe extern "C" fn aminus_dealloc(ptr: *mut u8, len: usize) {
+    if ptr.is_null() || len == 0 {
+        return;
+    }
+    std::alloc::dealloc(ptr, std::alloc::Layout::array::<u8>(len).unwrap());
+}
+
+/// frees a string returned by aminus
+///
+/// # Safety
+/// ptr must come from aminus and not be freed twice
+#[no_mangle]
+pub unsafe extern "C" fn aminus_string_free(ptr: *mut c_char) {
+    if !ptr.is_null() {
+        drop(CString::from_raw(ptr));
+    }
+}
+
+/// version of the library, a static string that must not be freed
+#[no_mangle]
+pub extern "C" fn aminus_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char
+}
+
+/// damage of a build on a rotation
+///
+/// # Safety
+/// request must be null or a nul terminated string, the response must be freed with aminus_string_free
+#[no_mangle]
+pub unsafe extern "C" fn aminus_calculate(request: *const c_char) -> *mut c_char {
+    respond(request, calculate)
+}
+
+/// best 5 star kqmc artifacts of a build on a rotation, above an optional energy recharge
+///
+/// # Safety
+/// request must be null or a nul terminated string, the response must be freed with aminus_string_free
+#[no_mangle]
+pub unsafe extern "C" fn aminus_optimize(request: *const c_char) -> *mut c_char {
+    respond(request, optimize)
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+
+    fn call(f: unsafe extern "C" fn(*const c_char) -> *mut c_char, request: &str) -> serde_json::Value {
+        let request = CString::new(request).unwrap();
+        unsafe {
+            let response = f(request.as_ptr());
+            let json = serde_json::from_str(CStr::from_ptr(response).to_str().unwrap()).unwrap();
+            aminus_string_free(response);
+            json
+        }
+    }
+
+    const BUILD: &str = r#"{
+        "character": {"name": "amber"},
+        "weapon": {"name": "the stringless"},
+        "stats": [["CritRate", 0.2]],
+        "rotation": {"actions": [{"name": "skill", "element": "Pyro", "damage_type": "Skill", "scaling": "ATK", "motion_value": 1.23}]}
+    "#;
+
+    #[test] fn calculates_damage_of_a_build() {
+        let naked = call(aminus_calculate, &format!("{}}}", BUILD));
+        assert_eq!(naked["ok"], true);
+        let geared = call(aminus_calculate, &format!(r#"{}, "artifacts": {{"sands": "ATKPercent", "goblet": "PyroDMGBonus", "circlet": "CritDMG"}}}}"#, BUILD));
+        let damage = geared["result"]["damage"].as_f64().unwrap();
+        assert!(damage > naked["result"]["damage"].as_f64().unwrap());
+        assert_eq!(geared["result"]["actions"]["skill"].as_f64().unwrap(), damage);
+    }
+
+    #[test] fn optimizes_artifacts_of_a_build() {
+        let response = call(aminus_optimize, &format!(r#"{}, "energy_recharge": 1.3}}"#, BUILD));
+        assert_eq!(response["ok"], true, "{}", response);
+        assert!(response["result"]["stats"]["EnergyRecharge"].as_f64().unwrap() >= 1.3);
+        assert_eq!(response["result"]["main_stats"][1], "PyroDMGBonus");
+    }
+
+    #[test] fn errors_and_panics_are_responses() {
+        let bad_json = call(aminus_calculate, "{");
+        assert_eq!(bad_json["ok"], false);
+        let unknown = call(aminus_calculate, r#"{"character": {"name": "not a character"}, "rotation": {"actions": []}}"#);
+        assert_eq!(unknown["ok"], false);
+        unsafe {
+            let response = aminus_calculate(std::ptr::null());
+            assert!(CStr::from_ptr(response).to_str().unwrap().contains("null"));
+            aminus_string_free(response);
+        }
+        let panicked = unsafe { respond::<()>(std::ptr::null(), |_| panic!("boom")) };
+        unsafe { aminus_string_free(panicked) };
+    }
+
+    #[test] fn buffers_and_version() {
+        unsafe {
+            let buffer = aminus_alloc(16);
+            assert!(!buffer.is_null());
+            aminus_dealloc(buffer, 16);
+            assert!(aminus_alloc(0).is_null());
+            assert_eq!(CStr::from_ptr(aminus_version()).to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+        }
+    }
+
+    /// include/aminus.h is generated from the exported functions with cbindgen
+    /// regenerate with AMINUS_WRITE_HEADER=1 cargo test header_is_up_to_date
+    #[test] fn header_is_up_to_date() {
+        let root = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", root)).unwrap();
+        let mut header = Vec::new();
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/ffi.rs", root))
+            .generate()
+            .unwrap()
+            .write(&mut header);
+        let header = String::from_utf8(header).unwrap();
+        let path = format!("{}/include/aminus.h", root);
+        if std::env::var("AMINUS_WRITE_HEADER").is_ok() {
+            std::fs::write(&path, &header).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), header);
+    }
+}
@@ -0,0 +1,266 @@
+use crate::core::artifact_builder::{RollQuality, POSSIBLE_SUB_STATS};
+use crate::core::constraint::Constraint;
+use crate::core::rotation::Rotation;
+use crate::core::stattable::StatTable;
+use crate::core::types::{Stat, TalentType};
+use crate::functions::optimizers::optimizers;
+use crate::functions::stat_factory::StatFactory;
+use anyhow::{Result, anyhow};
+
+/// a weapon's result in a weapon comparison
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeaponRanking {
+    pub name: String,
+    pub rarity: u8,
+    pub dpr: f32,
+    pub relative: f32, // dpr relative to the best weapon, 1.0 is the best
+}
+
+/// ranks every weapon of a category (eg: sword) by rotation damage at a given level and refinement
+/// artifacts are re-optimized with kqmc assumptions for each weapon
+/// weapon passives are not part of the weapon data, so `passive` provides the stats a weapon's passive gives at a refinement (if any)
+/// weapons whose build cannot meet the constraints are left out of the ranking
+pub fn compare_weapons(
+    character: &StatTable,
+    target: &Rotation,
+    category: &str,
+    level: i8,
+    refinement: i8,
+    passive: &dyn Fn(&str, i8) -> Option<StatTable>,
+    constraints: &[Constraint],
+) -> Result<Vec<WeaponRanking>> {
+    if !(1..=5).contains(&refinement) {
+        return Err(anyhow!("invalid refinement {refinement}"));
+    }
+
+    let mut rankings = Vec::new();
+    for (weapon, weapon_stats) in StatFactory::get_weapon_category_base_stats(category, level)? {
+        let mut stats = character.chain(weapon_stats);
+        if let Some(passive_stats) = passive(&weapon.name, refinement) {
+            stats.add_table(passive_stats.iter());
+        }
+        let Ok(optimized) = optimizers::optimal_kqmc_5_artifacts_stats_with_constraints(&stats, target, constraints) else {
+            continue;
+        };
+        rankings.push(WeaponRanking {
+            name: weapon.name.clone(),
+            rarity: weapon.rarity,
+            dpr: target.evaluate(&optimized),
+            relative: 0.0,
+        });
+    }
+
+    if rankings.is_empty() {
+        return Err(anyhow!("no {category} weapon can meet the constraints"));
+    }
+
+    rankings.sort_by(|a, b| b.dpr.total_cmp(&a.dpr));
+    let best = rankings[0].dpr;
+    rankings.iter_mut().for_each(|x| x.relative = if best > 0.0 { x.dpr / best } else { 0.0 });
+    Ok(rankings)
+}
+
+/// character and weapon levels where stats change the most (ascension caps)
+pub const LEVEL_BREAKPOINTS: &[i8] = &[20, 40, 50, 60, 70, 80, 90];
+/// max talent level without constellation boosts
+pub const MAX_TALENT_LEVEL: i8 = 10;
+
+/// talent levels of a character
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TalentLevels {
+    pub normal_attack: i8,
+    pub skill: i8,
+    pub burst: i8,
+}
+
+impl TalentLevels {
+    pub fn get(&self, talent: TalentType) -> i8 {
+        match talent {
+            TalentType::NormalAttack => self.normal_attack,
+            TalentType::Skill => self.skill,
+            TalentType::Burst => self.burst,
+        }
+    }
+
+    /// returns a copy with one talent set to a level
+    pub fn with(&self, talent: TalentType, level: i8) -> Self {
+        let mut res = *self;
+        match talent {
+            TalentType::NormalAttack => res.normal_attack = level,
+            TalentType::Skill => res.skill = level,
+            TalentType::Burst => res.burst = level,
+        }
+        res
+    }
+}
+
+/// the character, weapon and talent investment of a build
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildInvestment {
+    pub character: String,
+    pub level: i8,
+    pub weapon: String,
+    pub weapon_level: i8,
+    pub refinement: i8,
+    pub talents: TalentLevels,
+}
+
+impl BuildInvestment {
+    /// character and weapon stats of the build, including the weapon passive at the build's refinement
+    pub fn stats(&self, passive: &dyn Fn(&str, i8) -> Option<StatTable>) -> Result<StatTable> {
+        let mut stats = StatFactory::get_character_base_stats(&self.character, self.level)?
+            .chain(StatFactory::get_weapon_base_stats(&self.weapon, self.weapon_level)?);
+        if let Some(passive_stats) = passive(&self.weapon, self.refinement) {
+            stats.add_table(passive_stats.iter());
+        }
+        Ok(stats)
+    }
+}
+
+/// an investment that can be made into a build
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Upgrade {
+    CharacterLevel(i8), // level after the upgrade
+    Talent(TalentType, i8),
+    WeaponLevel(i8),
+    Refinement(i8),
+    Substat(Stat), // one extra average 5 star roll
+}
+
+/// the damage an upgrade adds to a build
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpgradeGain {
+    pub upgrade: Upgrade,
+    pub gain: f32, // relative to current damage, eg: 0.05 = +5%
+}
+
+/// reports the percentage damage gain of every candidate upgrade of a build, sorted by gain
+/// candidates are the next character level breakpoint, +1 level on each talent, the next weapon level breakpoint,
+/// +1 refinement and one extra average roll of each substat
+/// `other_stats` are stats that do not change with upgrades (eg: artifacts and buffs), `rotation` builds the rotation for given talent levels
+/// note: character level only changes base stats since the damage formula assumes a level 90 character
+pub fn upgrade_priorities(
+    build: &BuildInvestment,
+    other_stats: &StatTable,
+    rotation: &dyn Fn(&TalentLevels) -> Rotation,
+    passive: &dyn Fn(&str, i8) -> Option<StatTable>,
+) -> Result<Vec<UpgradeGain>> {
+    let evaluate = |build: &BuildInvestment| -> Result<f32> {
+        Ok(rotation(&build.talents).evaluate(&build.stats(passive)?.chain(other_stats.clone())))
+    };
+    let baseline = evaluate(build)?;
+    if baseline <= 0.0 {
+        return Err(anyhow!("build deals no damage to compare upgrades against"));
+    }
+
+    let mut candidates = Vec::new();
+    if let Some(&level) = LEVEL_BREAKPOINTS.iter().find(|&&x| x > build.level) {
+        candidates.push((Upgrade::CharacterLevel(level), BuildInvestment { level, ..build.clone() }));
+    }
+    for talent in [TalentType::NormalAttack, TalentType::Skill, TalentType::Burst] {
+        let level = build.talents.get(talent) + 1;
+        if level <= MAX_TALENT_LEVEL {
+            candidates.push((Upgrade::Talent(talent, level), BuildInvestment { talents: build.talents.with(talent, level), ..build.clone() }));
+        }
+    }
+    if let Some(&weapon_level) = LEVEL_BREAKPOINTS.iter().find(|&&x| x > build.weapon_level) {
+        candidates.push((Upgrade::WeaponLevel(weapon_level), BuildInvestment { weapon_level, ..build.clone() }));
+    }
+    if build.refinement < 5 {
+        candidates.push((Upgrade::Refinement(build.refinement + 1), BuildInvestment { refinement: build.refinement + 1, ..build.clone() }));
+    }
+
+    let mut gains = Vec::new();
+    for (upgrade, upgraded) in candidates {
+        // skip upgrades the data cannot represent (eg: low rarity weapons that cannot reach level 90)
+        if let Ok(value) = evaluate(&upgraded) {
+            gains.push(UpgradeGain { upgrade, gain: value / baseline - 1.0 });
+        }
+    }
+
+    let stats = build.stats(passive)?.chain(other_stats.clone());
+    let target = rotation(&build.talents);
+    for &stat in POSSIBLE_SUB_STATS.iter() {
+        let roll = StatFactory::get_sub_stat_value(5, stat)? * RollQuality::AVG.multiplier();
+        let value = target.evaluate(&stats.clone().chain(StatTable::of(&[(stat, roll)])));
+        gains.push(UpgradeGain { upgrade: Upgrade::Substat(stat), gain: value / baseline - 1.0 });
+    }
+
+    gains.sort_by(|a, b| b.gain.total_cmp(&a.gain));
+    Ok(gains)
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::types::*;
+    use crate::core::constraint::Constraint;
+
+    #[test] fn compare_claymores_for_physical_dps() {
+        let character = StatFactory::get_character_base_stats("eula", 90).unwrap();
+        let target = crate::rotation! {
+            ("na", Element::Physical, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+        }.unwrap();
+        // exaggerated passive so a 3 star weapon outranks every 5 star weapon
+        let passive = |name: &str, refinement: i8| match name {
+            "Debate Club" => Some(crate::stats! { Stat::PhysicalDMGBonus: 5.0 * refinement as f32 }),
+            _ => None,
+        };
+
+        let rankings = compare_weapons(&character, &target, "claymore", 90, 1, &passive, &[Constraint::energy_recharge(1.0)]).unwrap();
+        assert!(rankings.len() > 10);
+        assert_eq!(rankings[0].name, "Debate Club");
+        assert_eq!(rankings[0].relative, 1.0);
+        assert!(rankings.windows(2).all(|w| w[0].dpr >= w[1].dpr));
+        assert!(rankings.iter().all(|x| x.relative <= 1.0 && x.relative > 0.0));
+    }
+
+    #[test] fn upgrade_priorities_for_ayaka() {
+        let build = BuildInvestment {
+            character: String::from("ayaka"),
+            level: 80,
+            weapon: String::from("mistsplitter"),
+            weapon_level: 90,
+            refinement: 1,
+            talents: TalentLevels { normal_attack: 6, skill: 9, burst: 10 },
+        };
+        let artifacts = crate::stats! {
+            Stat::FlatHP: 4780.0,
+            Stat::FlatATK: 311.0,
+            Stat::ATKPercent: 0.466,
+            Stat::CryoDMGBonus: 0.466,
+            Stat::CritDMG: 0.622,
+            Stat::CritRate: 0.3,
+        };
+        let rotation = |talents: &TalentLevels| {
+            let n1 = StatFactory::get_talent_multiplier("ayaka", TalentType::NormalAttack, "1-Hit DMG", talents.normal_attack).unwrap();
+            let skill = StatFactory::get_talent_multiplier("ayaka", TalentType::Skill, "Skill DMG", talents.skill).unwrap();
+            let cuts = StatFactory::get_talent_multiplier("ayaka", TalentType::Burst, "Cutting DMG", talents.burst).unwrap();
+            crate::rotation! {
+                ("n1", Element::Cryo, DamageType::Normal, BaseScaling::ATK, Amplifier::None, n1, 3.0, None),
+                ("skill", Element::Cryo, DamageType::Skill, BaseScaling::ATK, Amplifier::None, skill, 2.0, None),
+                ("burstcuts", Element::Cryo, DamageType::Burst, BaseScaling::ATK, Amplifier::None, cuts, 19.0, None),
+            }.unwrap()
+        };
+        let passive = |_: &str, refinement: i8| Some(crate::stats! { Stat::CryoDMGBonus: 0.12 + 0.03 * (refinement - 1) as f32 });
+
+        let report = upgrade_priorities(&build, &artifacts, &rotation, &passive).unwrap();
+        assert!(report.windows(2).all(|w| w[0].gain >= w[1].gain));
+        let gain_of = |upgrade: Upgrade| report.iter().find(|x| x.upgrade == upgrade).map(|x| x.gain);
+
+        assert!(gain_of(Upgrade::CharacterLevel(90)).unwrap() > 0.0);
+        assert!(gain_of(Upgrade::Talent(TalentType::NormalAttack, 7)).unwrap() > 0.0);
+        assert!(gain_of(Upgrade::Talent(TalentType::Skill, 10)).unwrap() > 0.0);
+        assert_eq!(gain_of(Upgrade::Talent(TalentType::Burst, 11)), None); // already max level
+        assert!(report.iter().all(|x| !matches!(x.upgrade, Upgrade::WeaponLevel(_)))); // weapon already level 90
+        assert!(gain_of(Upgrade::Refinement(2)).unwrap() > 0.0);
+        assert!(gain_of(Upgrade::Substat(Stat::CritDMG)).unwrap() > 0.0);
+        assert_eq!(gain_of(Upgrade::Substat(Stat::ElementalMastery)).unwrap(), 0.0);
+        // burst cuts are most of the damage so the skill level is worth less than a crit roll
+        assert!(gain_of(Upgrade::Substat(Stat::CritRate)).unwrap() > gain_of(Upgrade::Talent(TalentType::Skill, 10)).unwrap());
+    }
+
+    #[test] fn invalid_refinement_is_error() {
+        let target = Rotation::new();
+        assert!(compare_weapons(&StatTable::new(), &target, "sword", 90, 6, &|_, _| None, &[]).is_err());
+    }
+}
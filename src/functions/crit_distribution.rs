@@ -0,0 +1,255 @@
+use crate::core::rotation::Rotation;
+use crate::core::stattable::StatTable;
+use crate::core::types::Stat;
+use crate::utils::rng::Rng;
+use anyhow::{Result, anyhow};
+
+/// max number of distinct damage outcomes an exact distribution can have
+pub const MAX_EXACT_OUTCOMES: usize = 1 << 16;
+
+/// how crits are rolled across the hits of an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HitGrouping {
+    PerHit, // every hit rolls crit independently
+    PerAction, // all hits of an action crit or not together
+}
+
+/// how the distribution of rotation damage is computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistributionMode {
+    Exact(HitGrouping),
+    Sampled { grouping: HitGrouping, samples: usize, seed: u64 },
+}
+
+/// a group of identical hits that each roll crit independently
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CritHit {
+    pub non_crit: f32, // damage of one hit that does not crit
+    pub crit: f32, // damage of one hit that crits
+    pub crit_rate: f32,
+    pub count: u32,
+}
+
+/// distribution of total damage as (damage, probability) outcomes sorted by damage
+#[derive(Debug, Clone, PartialEq)]
+pub struct DamageDistribution {
+    outcomes: Vec<(f32, f32)>,
+}
+
+impl DamageDistribution {
+    pub fn new(mut outcomes: Vec<(f32, f32)>) -> Self {
+        outcomes.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { outcomes }
+    }
+
+    pub fn outcomes(&self) -> &[(f32, f32)] {
+        &self.outcomes
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.outcomes.iter().map(|(v, p)| v * p).sum()
+    }
+
+    pub fn std_dev(&self) -> f32 {
+        let mean = self.mean();
+        self.outcomes.iter()
+            .map(|(v, p)| p * (v - mean) * (v - mean))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    /// smallest damage that is reached with at least a given probability mass below or at it
+    /// eg: percentile(0.9) is the damage 90% of outcomes do not exceed
+    pub fn percentile(&self, q: f32) -> f32 {
+        let mut cumulative = 0.0;
+        for (value, p) in &self.outcomes {
+            cumulative += p;
+            if cumulative >= q - 1e-6 {
+                return *value;
+            }
+        }
+        self.outcomes.last().map(|x| x.0).unwrap_or(0.0)
+    }
+
+    pub fn p10(&self) -> f32 {
+        self.percentile(0.1)
+    }
+
+    pub fn p50(&self) -> f32 {
+        self.percentile(0.5)
+    }
+
+    pub fn p90(&self) -> f32 {
+        self.percentile(0.9)
+    }
+
+    /// probability that total damage is strictly greater than a threshold (eg: enemy hp for one-shots)
+    pub fn probability_exceeding(&self, threshold: f32) -> f32 {
+        self.outcomes.iter()
+            .filter(|(v, _)| *v > threshold)
+            .map(|(_, p)| p)
+            .sum()
+    }
+}
+
+/// splits every action of a rotation into crit hits
+/// non crit and crit damage are found by evaluating the action with crit rate forced out of [0, 1],
+/// the crit rate of the action is then inferred from its expected damage so crit rate buffs inside the action are accounted for
+pub fn crit_hits(rotation: &Rotation, stats: &StatTable, grouping: HitGrouping) -> Vec<CritHit> {
+    let no_crit = stats.chain(StatTable::of(&[(Stat::CritRate, -stats.get(&Stat::CritRate) - 10.0)]));
+    let all_crit = stats.chain(StatTable::of(&[(Stat::CritRate, 10.0)]));
+
+    let mut names: Vec<&String> = rotation.action_names().collect();
+    names.sort(); // deterministic order for sampling
+    names.into_iter()
+        .map(|name| {
+            let expected = rotation.evaluate_action(name, stats).unwrap_or(0.0);
+            let non_crit = rotation.evaluate_action(name, &no_crit).unwrap_or(0.0);
+            let crit = rotation.evaluate_action(name, &all_crit).unwrap_or(0.0);
+            let crit_rate = if (crit - non_crit).abs() > f32::EPSILON * crit.abs() {
+                ((expected - non_crit) / (crit - non_crit)).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let count = match grouping {
+                HitGrouping::PerHit => rotation.hits(name),
+                HitGrouping::PerAction => 1,
+            };
+            CritHit { non_crit: non_crit / count as f32, crit: crit / count as f32, crit_rate, count }
+        })
+        .collect()
+}
+
+/// exact distribution of total damage, outcomes within 0.01 damage are merged
+/// errors if there are more than MAX_EXACT_OUTCOMES distinct outcomes
+pub fn exact_distribution(hits: &[CritHit]) -> Result<DamageDistribution> {
+    let mut outcomes: Vec<(f32, f32)> = vec![(0.0, 1.0)];
+    for hit in hits {
+        let pmf = binomial_pmf(hit.count, hit.crit_rate);
+        let mut merged: std::collections::HashMap<i64, (f32, f32)> = std::collections::HashMap::new();
+        for (value, p) in &outcomes {
+            for (k, pk) in pmf.iter().enumerate().filter(|(_, pk)| **pk > 0.0) {
+                let v = value + hit.count as f32 * hit.non_crit + k as f32 * (hit.crit - hit.non_crit);
+                let entry = merged.entry((v as f64 * 100.0).round() as i64).or_insert((v, 0.0));
+                entry.1 += p * pk;
+            }
+        }
+        if merged.len() > MAX_EXACT_OUTCOMES {
+            return Err(anyhow!("exact distribution has more than {MAX_EXACT_OUTCOMES} outcomes, use sampling instead"));
+        }
+        outcomes = merged.into_values().collect();
+    }
+    Ok(DamageDistribution::new(outcomes))
+}
+
+/// sampled distribution of total damage, each sample rolls crit for every hit
+pub fn sampled_distribution(hits: &[CritHit], samples: usize, rng: &mut Rng) -> DamageDistribution {
+    let mut outcomes = Vec::with_capacity(samples);
+    for _ in 0..samples {
+        let mut total = 0.0;
+        for hit in hits {
+            for _ in 0..hit.count {
+                total += if rng.chance(hit.crit_rate) { hit.crit } else { hit.non_crit };
+            }
+        }
+        outcomes.push((total, 1.0 / samples as f32));
+    }
+    DamageDistribution::new(outcomes)
+}
+
+/// distribution of total damage of a rotation where crits are bernoulli trials
+pub fn rotation_distribution(rotation: &Rotation, stats: &StatTable, mode: DistributionMode) -> Result<DamageDistribution> {
+    match mode {
+        DistributionMode::Exact(grouping) => exact_distribution(&crit_hits(rotation, stats, grouping)),
+        DistributionMode::Sampled { samples: 0, .. } => Err(anyhow!("sampled distribution needs at least one sample")),
+        DistributionMode::Sampled { grouping, samples, seed } => {
+            Ok(sampled_distribution(&crit_hits(rotation, stats, grouping), samples, &mut Rng::new(seed)))
+        },
+    }
+}
+
+/// probability of k successes out of n bernoulli trials for k = 0..=n
+fn binomial_pmf(n: u32, p: f32) -> Vec<f32> {
+    let p = p as f64;
+    let mut coefficient = 1.0_f64;
+    (0..=n)
+        .map(|k| {
+            if k > 0 {
+                coefficient = coefficient * (n - k + 1) as f64 / k as f64;
+            }
+            (coefficient * p.powi(k as i32) * (1.0 - p).powi((n - k) as i32)) as f32
+        })
+        .collect()
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::core::types::*;
+    use crate::assert_aprx;
+
+    fn stats() -> StatTable {
+        StatTable::of(&[
+            (Stat::BaseATK, 1000.0),
+            (Stat::CritRate, 0.5),
+            (Stat::CritDMG, 1.0),
+        ])
+    }
+
+    #[test] fn single_hit_is_a_coin_flip() {
+        let r = crate::rotation! {
+            ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+        }.unwrap();
+        let dist = r.evaluate_distribution(&stats(), DistributionMode::Exact(HitGrouping::PerHit)).unwrap();
+        assert_eq!(dist.outcomes().len(), 2);
+        let (non_crit, crit) = (dist.outcomes()[0].0, dist.outcomes()[1].0);
+        assert_aprx!(crit, 2.0 * non_crit, 0.01);
+        assert_aprx!(dist.mean(), r.evaluate(&stats()), 0.01);
+        assert_aprx!(dist.std_dev(), non_crit / 2.0, 0.01);
+        assert_eq!(dist.p10(), non_crit);
+        assert_eq!(dist.p50(), non_crit);
+        assert_eq!(dist.p90(), crit);
+        assert_aprx!(dist.probability_exceeding(non_crit), 0.5, 0.0001);
+    }
+
+    #[test] fn hits_can_be_grouped_per_action() {
+        let r = crate::rotation! {
+            ("n1", Element::Physical, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 0.5, 3.0, None),
+        }.unwrap();
+        let per_hit = r.evaluate_distribution(&stats(), DistributionMode::Exact(HitGrouping::PerHit)).unwrap();
+        let per_action = r.evaluate_distribution(&stats(), DistributionMode::Exact(HitGrouping::PerAction)).unwrap();
+        assert_eq!(per_hit.outcomes().len(), 4);
+        assert_eq!(per_action.outcomes().len(), 2);
+        assert_aprx!(per_hit.mean(), per_action.mean(), 0.01);
+        assert!(per_hit.std_dev() < per_action.std_dev());
+        assert_aprx!(per_hit.probability_exceeding(per_hit.outcomes()[2].0), 0.125, 0.0001);
+    }
+
+    #[test] fn crit_rate_buffs_inside_actions_are_inferred() {
+        let buffs = StatTable::of(&[(Stat::CritRate, 0.3)]);
+        let r = crate::rotation! {
+            ("burst", Element::Cryo, DamageType::Burst, BaseScaling::ATK, Amplifier::None, 2.0, 1.0, Some(&buffs)),
+        }.unwrap();
+        let hits = crit_hits(&r, &stats(), HitGrouping::PerHit);
+        assert_aprx!(hits[0].crit_rate, 0.8, 0.0001);
+    }
+
+    #[test] fn sampling_is_deterministic_and_close_to_exact() {
+        let r = crate::rotation! {
+            ("n1", Element::Physical, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 0.5, 4.0, None),
+            ("skill", Element::Physical, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 2.0, 1.0, None),
+        }.unwrap();
+        let mode = DistributionMode::Sampled { grouping: HitGrouping::PerHit, samples: 20000, seed: 42 };
+        let a = r.evaluate_distribution(&stats(), mode).unwrap();
+        let b = r.evaluate_distribution(&stats(), mode).unwrap();
+        assert_eq!(a, b);
+
+        let exact = r.evaluate_distribution(&stats(), DistributionMode::Exact(HitGrouping::PerHit)).unwrap();
+        assert!((a.mean() - exact.mean()).abs() < 0.01 * exact.mean());
+        assert!((a.std_dev() - exact.std_dev()).abs() < 0.05 * exact.std_dev());
+    }
+
+    #[test] fn zero_samples_is_error() {
+        let mode = DistributionMode::Sampled { grouping: HitGrouping::PerHit, samples: 0, seed: 0 };
+        assert!(Rotation::new().evaluate_distribution(&stats(), mode).is_err());
+    }
+}
@@ -1,5 +1,10 @@
 use crate::core::stattable::*;
 use crate::core::types::*;
+use crate::core::dual::Numeric;
+use crate::core::calc_standard::CalcStandard;
+use crate::core::aura::Reaction;
+use crate::core::reaction_mix::ReactionMix;
+use crate::core::shred::{resolve_shred, ResolvedShred, Shred};
 use crate::functions::formulas::*;
 
 /// higher level functions to compute damage from statables
@@ -7,6 +12,7 @@ pub struct DMGFunction {}
 impl DMGFunction {
 
     /// higher level function that applys the full damage formula to a statable
+    /// uses the kqmc standard, see calculate_damage_generic for other standards
     pub fn calculate_damage(
         element: Element,
         damage_type: DamageType,
@@ -17,27 +23,153 @@ impl DMGFunction {
         character: &StatTable,
         buffs: Option<&StatTable>
     ) -> f32 {
+        Self::calculate_damage_generic(element, damage_type, scaling, amplifier, instances, motion_value, character, buffs, &CalcStandard::KQMC)
+    }
+
+    /// full damage formula over any stat lookup, evaluating on a DualStatTable gives the exact stat gradients of the damage
+    /// character and enemy levels and enemy resistance are taken from the calc standard
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_damage_generic<S: StatLookup>(
+        element: Element,
+        damage_type: DamageType,
+        scaling: BaseScaling,
+        amplifier: Amplifier,
+        instances: f32,
+        motion_value: f32,
+        character: &S,
+        buffs: Option<&StatTable>,
+        standard: &CalcStandard,
+    ) -> S::Value {
+        Self::damage(element, damage_type, scaling, amplifier, None, instances, motion_value, character, buffs, None, standard)
+    }
+
+    /// full damage formula with def and res shred from source tagged shreds, copies of a source count once
+    /// shred stats of the character and buff tables are ignored, every shred of the hit comes from the shreds
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_damage_with_shred_generic<S: StatLookup>(
+        element: Element,
+        damage_type: DamageType,
+        scaling: BaseScaling,
+        amplifier: Amplifier,
+        instances: f32,
+        motion_value: f32,
+        character: &S,
+        buffs: Option<&StatTable>,
+        shreds: &[Shred],
+        standard: &CalcStandard,
+    ) -> S::Value {
+        let shred = resolve_shred(shreds);
+        Self::damage(element, damage_type, scaling, amplifier, None, instances, motion_value, character, buffs, Some(&shred), standard)
+    }
+
+    /// full damage formula for a hit that triggers a reaction
+    /// melt and vaporize amplify the hit, aggravate and spread add catalyze damage (assuming a level 90 character) to its base damage
+    /// other reactions do not change the damage of the triggering hit
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_reaction_damage_generic<S: StatLookup>(
+        element: Element,
+        damage_type: DamageType,
+        scaling: BaseScaling,
+        reaction: Reaction,
+        instances: f32,
+        motion_value: f32,
+        character: &S,
+        buffs: Option<&StatTable>,
+        standard: &CalcStandard,
+    ) -> S::Value {
+        Self::damage(element, damage_type, scaling, reaction.amplifier(), reaction.catalyze_multiplier(), instances, motion_value, character, buffs, None, standard)
+    }
+
+    /// full damage formula where instances are split between reactions by a reaction mix
+    /// uses the kqmc standard, see calculate_damage_with_reactions_generic for other standards
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_damage_with_reactions(
+        element: Element,
+        damage_type: DamageType,
+        scaling: BaseScaling,
+        reactions: &ReactionMix,
+        instances: f32,
+        motion_value: f32,
+        character: &StatTable,
+        buffs: Option<&StatTable>
+    ) -> f32 {
+        Self::calculate_damage_with_reactions_generic(element, damage_type, scaling, reactions, instances, motion_value, character, buffs, &CalcStandard::KQMC)
+    }
+
+    /// generic full damage formula where instances are split between reactions by a reaction mix
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_damage_with_reactions_generic<S: StatLookup>(
+        element: Element,
+        damage_type: DamageType,
+        scaling: BaseScaling,
+        reactions: &ReactionMix,
+        instances: f32,
+        motion_value: f32,
+        character: &S,
+        buffs: Option<&StatTable>,
+        standard: &CalcStandard,
+    ) -> S::Value {
+        Self::reaction_breakdown_generic(element, damage_type, scaling, reactions, instances, motion_value, character, buffs, standard)
+            .into_iter()
+            .fold(S::Value::constant(0.0), |acc, (_, dmg)| acc + dmg)
+    }
+
+    /// damage of an action split by the reactions of a reaction mix
+    /// eg: [(Reaction::Vaporize, 700.0), (Reaction::None, 150.0)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn reaction_breakdown_generic<S: StatLookup>(
+        element: Element,
+        damage_type: DamageType,
+        scaling: BaseScaling,
+        reactions: &ReactionMix,
+        instances: f32,
+        motion_value: f32,
+        character: &S,
+        buffs: Option<&StatTable>,
+        standard: &CalcStandard,
+    ) -> Vec<(Reaction, S::Value)> {
+        reactions.shares().into_iter()
+            .map(|(reaction, share)| (reaction, Self::calculate_reaction_damage_generic(element, damage_type, scaling, reaction, instances * share, motion_value, character, buffs, standard)))
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn damage<S: StatLookup>(
+        element: Element,
+        damage_type: DamageType,
+        scaling: BaseScaling,
+        amplifier: Amplifier,
+        catalyze_multiplier: Option<f32>,
+        instances: f32,
+        motion_value: f32,
+        character: &S,
+        buffs: Option<&StatTable>,
+        shred: Option<&ResolvedShred>,
+        standard: &CalcStandard,
+    ) -> S::Value {
         if amplifier == Amplifier::Forward || amplifier == Amplifier::Reverse {
             assert!(element == Element::Pyro || element == Element::Hydro || element == Element::Cryo || element == Element::Anemo);
         }
 
-        let mut total = StatTable::new();
-        total.add_table(character.iter());
-        if let Some(buffs) = buffs {
-            total.add_table(buffs.iter());
-        }
+        let total = Buffed { stats: character, buffs };
+        let c = S::Value::constant;
         
         let total_base_scaling_stat = match scaling {
             BaseScaling::ATK => formulas::total_atk(&total),
             BaseScaling::DEF => formulas::total_def(&total),
             BaseScaling::HP => formulas::total_hp(&total),
-            BaseScaling::FlatDamage => 1.,
+            BaseScaling::FlatDamage => c(1.),
         };
 
         let amplifier_multiplier = match amplifier {
-            Amplifier::Forward => formulas::amplifier_multiplier(2.0, total.get(&Stat::ElementalMastery), total.get(&Stat::ReactionBonus)),
-            Amplifier::Reverse => formulas::amplifier_multiplier(1.5, total.get(&Stat::ElementalMastery), total.get(&Stat::ReactionBonus)),
-            Amplifier::None => 1.0,
+            Amplifier::Forward => formulas::amplifier_multiplier(c(2.0), total.get(&Stat::ElementalMastery), total.get(&Stat::ReactionBonus)),
+            Amplifier::Reverse => formulas::amplifier_multiplier(c(1.5), total.get(&Stat::ElementalMastery), total.get(&Stat::ReactionBonus)),
+            Amplifier::None => c(1.0),
+        };
+
+        let catalyze_bonus = match catalyze_multiplier {
+            Some(multiplier) => formulas::catalyze_bonus(c(formulas::REACTION_LEVEL_MULTIPLIER_90), c(multiplier), total.get(&Stat::ElementalMastery), total.get(&Stat::ReactionBonus)),
+            None => c(0.0),
         };
 
         let element_dmg_bonus = match element {
@@ -49,7 +181,7 @@ impl DMGFunction {
             Element::Dendro => total.get(&Stat::DendroDMGBonus),
             Element::Cryo => total.get(&Stat::CryoDMGBonus),
             Element::Physical => total.get(&Stat::PhysicalDMGBonus),
-            Element::None => 0.0,
+            Element::None => c(0.0),
         };
 
         let attack_type_dmg_bonus = match damage_type {
@@ -58,42 +190,142 @@ impl DMGFunction {
             DamageType::Plunging => total.get(&Stat::PlungeATKDMGBonus),
             DamageType::Skill => total.get(&Stat::SkillDMGBonus),
             DamageType::Burst => total.get(&Stat::BurstDMGBonus),
-            DamageType::None => 0.0,
+            DamageType::None => c(0.0),
         };
 
+        // crit rate, crit dmg and flat damage that only apply to the hit's element
+        let (element_crit_rate, element_crit_dmg, element_flat_dmg) = match element {
+            Element::Pyro => (total.get(&Stat::PyroCritRate), total.get(&Stat::PyroCritDMG), total.get(&Stat::PyroFlatDMG)),
+            Element::Hydro => (total.get(&Stat::HydroCritRate), total.get(&Stat::HydroCritDMG), total.get(&Stat::HydroFlatDMG)),
+            Element::Electro => (total.get(&Stat::ElectroCritRate), total.get(&Stat::ElectroCritDMG), total.get(&Stat::ElectroFlatDMG)),
+            Element::Anemo => (total.get(&Stat::AnemoCritRate), total.get(&Stat::AnemoCritDMG), total.get(&Stat::AnemoFlatDMG)),
+            Element::Geo => (total.get(&Stat::GeoCritRate), total.get(&Stat::GeoCritDMG), total.get(&Stat::GeoFlatDMG)),
+            Element::Dendro => (total.get(&Stat::DendroCritRate), total.get(&Stat::DendroCritDMG), total.get(&Stat::DendroFlatDMG)),
+            Element::Cryo => (total.get(&Stat::CryoCritRate), total.get(&Stat::CryoCritDMG), total.get(&Stat::CryoFlatDMG)),
+            Element::Physical => (total.get(&Stat::PhysicalCritRate), total.get(&Stat::PhysicalCritDMG), total.get(&Stat::PhysicalFlatDMG)),
+            Element::None => (c(0.0), c(0.0), c(0.0)),
+        };
+
+        // crit rate, crit dmg and flat damage that only apply to the hit's attack type
+        let (attack_type_crit_rate, attack_type_crit_dmg, attack_type_flat_dmg) = match damage_type {
+            DamageType::Normal => (total.get(&Stat::NormalATKCritRate), total.get(&Stat::NormalATKCritDMG), total.get(&Stat::NormalATKFlatDMG)),
+            DamageType::Charged => (total.get(&Stat::ChargeATKCritRate), total.get(&Stat::ChargeATKCritDMG), total.get(&Stat::ChargeATKFlatDMG)),
+            DamageType::Plunging => (total.get(&Stat::PlungeATKCritRate), total.get(&Stat::PlungeATKCritDMG), total.get(&Stat::PlungeATKFlatDMG)),
+            DamageType::Skill => (total.get(&Stat::SkillCritRate), total.get(&Stat::SkillCritDMG), total.get(&Stat::SkillFlatDMG)),
+            DamageType::Burst => (total.get(&Stat::BurstCritRate), total.get(&Stat::BurstCritDMG), total.get(&Stat::BurstFlatDMG)),
+            DamageType::None => (c(0.0), c(0.0), c(0.0)),
+        };
+
+        let crit_rate = total.get(&Stat::CritRate) + element_crit_rate + attack_type_crit_rate;
+        let crit_dmg = total.get(&Stat::CritDMG) + element_crit_dmg + attack_type_crit_dmg;
+
+        let additive_base_dmg_bonus = catalyze_bonus
+            + total.get(&Stat::FlatDMG)
+            + element_flat_dmg
+            + attack_type_flat_dmg;
+
         let total_dmg_bonus = total.get(&Stat::DMGBonus) 
             + total.get(&Stat::ElementalDMGBonus)
             + element_dmg_bonus
             + attack_type_dmg_bonus;
 
-        let def_reduction = total.get(&Stat::DefReduction);
-        let def_ignore = total.get(&Stat::DefIgnore);
-        
-        let resistance_reduction = match element {
-            Element::Pyro => total.get(&Stat::PyroResistanceReduction),
-            Element::Hydro => total.get(&Stat::HydroResistanceReduction),
-            Element::Electro => total.get(&Stat::ElectroResistanceReduction),
-            Element::Anemo => total.get(&Stat::AnemoResistanceReduction),
-            Element::Geo => total.get(&Stat::GeoResistanceReduction),
-            Element::Dendro => total.get(&Stat::DendroResistanceReduction),
-            Element::Cryo => total.get(&Stat::CryoResistanceReduction),
-            Element::Physical => total.get(&Stat::PhysicalResistanceReduction),
-            Element::None => 0.0,
+        // resolved shred replaces the shred stats of the tables
+        let shred_of = |stat: Stat| match shred {
+            Some(shred) => c(shred.stats.get(&stat)),
+            None => total.get(&stat),
+        };
+        let def_reduction = shred_of(Stat::DefReduction);
+        let def_ignore = shred_of(Stat::DefIgnore);
+        let resistance_reduction = match Debuffs::resistance_reduction(element) {
+            Some(debuff) => shred_of(debuff.stat()),
+            None => c(0.0),
         };
 
         formulas::full_damage_formula(
-            instances,
+            c(instances),
             total_base_scaling_stat,
-            motion_value,
-            1.0,
-            0.0,
-            formulas::avg_crit_multiplier(&total),
+            c(motion_value),
+            c(1.0) + total.get(&Stat::BaseDMGMultiplier),
+            additive_base_dmg_bonus,
+            formulas::crit_multiplier(crit_rate, crit_dmg),
             total_dmg_bonus,
-            0.0,
-            formulas::def_multiplier(90, 100, def_reduction, def_ignore),
-            formulas::res_multiplier(0.1, resistance_reduction), // Assuming KQMC enemy with 10% base resistance
+            c(0.0),
+            formulas::def_multiplier(standard.character_level, standard.enemy_level, def_reduction, def_ignore),
+            formulas::res_multiplier(c(standard.enemy_resistance), resistance_reduction),
             amplifier_multiplier
         )
     }
 
 }
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::assert_aprx;
+
+    #[test] fn enemy_assumptions_come_from_the_standard() {
+        let stats = StatTable::of(&[(Stat::BaseATK, 1000.0)]);
+        let damage = |standard: &CalcStandard| DMGFunction::calculate_damage_generic(
+            Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &stats, None, standard
+        );
+        let kqmc = damage(&CalcStandard::KQMC);
+        assert_aprx!(kqmc, DMGFunction::calculate_damage(Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &stats, None), 0.001);
+        // 1000 atk * 190/390 def multiplier * 0.9 res multiplier
+        assert_aprx!(kqmc, 438.46, 0.01);
+
+        let tanky = CalcStandard { enemy_resistance: 0.5, ..CalcStandard::KQMC };
+        assert_aprx!(damage(&tanky), 243.59, 0.01);
+    }
+
+    #[test] fn shred_copies_of_one_source_do_not_stack() {
+        let stats = StatTable::of(&[(Stat::BaseATK, 1000.0)]);
+        let damage = |shreds: &[Shred]| DMGFunction::calculate_damage_with_shred_generic(
+            Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &stats, None, shreds, &CalcStandard::KQMC
+        );
+        let vv = Shred::new("viridescent venerer", Debuffs::PyroResistanceReduction, 0.4);
+        let once = damage(std::slice::from_ref(&vv));
+        assert_aprx!(damage(&[vv.clone(), vv.clone()]), once, 0.001);
+        // -30% res is halved
+        assert_aprx!(once, 438.46 / 0.9 * 1.15, 0.01);
+        // a different source stacks
+        let zhongli = Shred::new("zhongli shield", Debuffs::PyroResistanceReduction, 0.2);
+        assert!(damage(&[vv.clone(), zhongli]) > once);
+        // shred stats in the tables are ignored
+        let buffs = StatTable::of(&[(Stat::PyroResistanceReduction, 0.4)]);
+        let with_buffs = DMGFunction::calculate_damage_with_shred_generic(Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &stats, Some(&buffs), &[vv], &CalcStandard::KQMC);
+        assert_aprx!(with_buffs, once, 0.001);
+    }
+
+    #[test] fn per_type_and_element_crit_only_apply_to_matching_hits() {
+        let stats = StatTable::of(&[
+            (Stat::BaseATK, 1000.0),
+            (Stat::CritRate, 0.5),
+            (Stat::CritDMG, 1.0),
+            (Stat::BurstCritRate, 0.2),
+            (Stat::CryoCritDMG, 0.4),
+        ]);
+        let plain = StatTable::of(&[(Stat::BaseATK, 1000.0)]);
+        let non_crit = |damage_type, element| DMGFunction::calculate_damage(element, damage_type, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &plain, None);
+        let damage = |damage_type, element| DMGFunction::calculate_damage(element, damage_type, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &stats, None);
+
+        assert_aprx!(damage(DamageType::Skill, Element::Pyro), non_crit(DamageType::Skill, Element::Pyro) * 1.5, 0.01);
+        assert_aprx!(damage(DamageType::Burst, Element::Pyro), non_crit(DamageType::Burst, Element::Pyro) * 1.7, 0.01);
+        assert_aprx!(damage(DamageType::Skill, Element::Cryo), non_crit(DamageType::Skill, Element::Cryo) * 1.7, 0.01);
+        assert_aprx!(damage(DamageType::Burst, Element::Cryo), non_crit(DamageType::Burst, Element::Cryo) * (1.0 + 0.7 * 1.4), 0.01);
+    }
+
+    #[test] fn flat_damage_and_base_multiplier_scale_base_damage() {
+        let stats = StatTable::of(&[(Stat::BaseATK, 1000.0)]);
+        let buffed = StatTable::of(&[
+            (Stat::BaseATK, 1000.0),
+            (Stat::NormalATKFlatDMG, 300.0), // yun jin style normal attack buff
+            (Stat::FlatDMG, 100.0),
+            (Stat::GeoFlatDMG, 50.0),
+            (Stat::BaseDMGMultiplier, 0.5),
+        ]);
+        let multiplier = DMGFunction::calculate_damage(Element::Physical, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &stats, None) / 1000.0;
+        let damage = |damage_type, element| DMGFunction::calculate_damage(element, damage_type, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, &buffed, None);
+        // (1000 atk * 150% base multiplier + flat damage) * other multipliers
+        assert_aprx!(damage(DamageType::Normal, Element::Physical), (1500.0 + 300.0 + 100.0) * multiplier, 0.01);
+        assert_aprx!(damage(DamageType::Skill, Element::Geo), (1500.0 + 100.0 + 50.0) * multiplier, 0.01);
+    }
+}
@@ -2,98 +2,149 @@
 pub mod formulas{
     use crate::core::stattable::*;
     use crate::core::types::*;
+    use crate::core::dual::Numeric;
     
-    pub fn total_atk(stats: &StatTable) -> f32 {
+    pub fn total_atk<S: StatLookup>(stats: &S) -> S::Value {
         let base_atk = stats.get(&Stat::BaseATK);
         let atk_percent = stats.get(&Stat::ATKPercent);
         let flat_atk = stats.get(&Stat::FlatATK);
-        base_atk * (1.0+atk_percent) + flat_atk
+        base_atk * (S::Value::constant(1.0)+atk_percent) + flat_atk
     }
 
-    pub fn total_def(stats: &StatTable) -> f32 {
+    pub fn total_def<S: StatLookup>(stats: &S) -> S::Value {
         let flat_def = stats.get(&Stat::FlatDEF);
         let def_percent = stats.get(&Stat::DEFPercent);
         let base_def = stats.get(&Stat::BaseDEF);
-        base_def * (1.0+def_percent) + flat_def
+        base_def * (S::Value::constant(1.0)+def_percent) + flat_def
     }
 
-    pub fn total_hp(stats: &StatTable) -> f32 { 
+    pub fn total_hp<S: StatLookup>(stats: &S) -> S::Value { 
         let flat_hp = stats.get(&Stat::FlatHP);
         let hp_percent = stats.get(&Stat::HPPercent);
         let base_hp = stats.get(&Stat::BaseHP);
-        base_hp * (1.0+hp_percent) + flat_hp
+        base_hp * (S::Value::constant(1.0)+hp_percent) + flat_hp
     }
 
-    pub fn avg_crit_multiplier(stats: &StatTable) -> f32 {
-        let cr = stats.get(&Stat::CritRate);
-        let cr = if cr > 1.0 { 1.0 } else { cr };
-        let cr = if cr < 0.0 { 0.0 } else { cr };
+    pub fn avg_crit_multiplier<S: StatLookup>(stats: &S) -> S::Value {
+        crit_multiplier(stats.get(&Stat::CritRate), stats.get(&Stat::CritDMG))
+    }
 
-        let cd = stats.get(&Stat::CritDMG);
-        1.0+(cr*cd)
+    /// average crit multiplier with crit rate clamped to [0, 1]
+    pub fn crit_multiplier<T: Numeric>(crit_rate: T, crit_dmg: T) -> T {
+        let cr = Numeric::min(crit_rate, T::constant(1.0));
+        let cr = Numeric::max(cr, T::constant(0.0));
+        T::constant(1.0)+(cr*crit_dmg)
     }
 
-    pub fn def_multiplier(character_level: i8, enemy_level: i8, def_reduction: f32, def_ignore: f32) -> f32{
+    pub fn def_multiplier<T: Numeric>(character_level: i8, enemy_level: i8, def_reduction: T, def_ignore: T) -> T{
         assert!(character_level >= 1);
         assert!(character_level <= 90);
         assert!(enemy_level >= 1);
-        (character_level as f32 + 100.0) / (
-            (character_level as f32 + 100.0) 
-            + (enemy_level as f32 + 100.0)
-            * (1.0 - f32::min(def_reduction, 0.9)) 
-            * (1.0 - def_ignore)
+        let one = T::constant(1.0);
+        T::constant(character_level as f32 + 100.0) / (
+            T::constant(character_level as f32 + 100.0) 
+            + T::constant(enemy_level as f32 + 100.0)
+            * (one - Numeric::min(def_reduction, T::constant(0.9))) 
+            * (one - def_ignore)
         )
     }
 
-    pub fn res_multiplier(enemy_base_resistance: f32, resistance_reduction: f32) -> f32 {
+    pub fn res_multiplier<T: Numeric>(enemy_base_resistance: T, resistance_reduction: T) -> T {
         let resistance = enemy_base_resistance - resistance_reduction;
-        if resistance < 0.0 {
-            1.0 - (resistance / 2.0)
-        } else if resistance < 0.75 {
-            1.0 - resistance
+        if resistance.value() < 0.0 {
+            T::constant(1.0) - (resistance / T::constant(2.0))
+        } else if resistance.value() < 0.75 {
+            T::constant(1.0) - resistance
         } else { //resistance >= 0.75
-            1.0 / (4.0 * resistance + 1.0)
+            T::constant(1.0) / (T::constant(4.0) * resistance + T::constant(1.0))
         }
     }
 
-    pub fn amplifier_multiplier(amplifier: f32, elemental_mastery: f32, reaction_bonus: f32) -> f32 {
-        amplifier * (1.0 + (2.78 * elemental_mastery) / (1400.0 + elemental_mastery) + reaction_bonus)
+    pub fn amplifier_multiplier<T: Numeric>(amplifier: T, elemental_mastery: T, reaction_bonus: T) -> T {
+        amplifier * (T::constant(1.0) + (T::constant(2.78) * elemental_mastery) / (T::constant(1400.0) + elemental_mastery) + reaction_bonus)
     }
 
-    pub fn full_damage_formula(
-        instances: f32,
-        total_scaling_stat: f32,
-        motion_value: f32,
-        base_dmg_multiplier: f32,
-        additive_base_dmg_bonus: f32,
-        avg_crit_multiplier: f32,
-        total_dmg_bonus: f32,
-        dmg_reduction_target: f32,
-        def_multiplier: f32,
-        res_multiplier: f32,
-        amplifier_multiplier: f32
-    ) -> f32 {
+    #[allow(clippy::too_many_arguments)]
+    pub fn full_damage_formula<T: Numeric>(
+        instances: T,
+        total_scaling_stat: T,
+        motion_value: T,
+        base_dmg_multiplier: T,
+        additive_base_dmg_bonus: T,
+        avg_crit_multiplier: T,
+        total_dmg_bonus: T,
+        dmg_reduction_target: T,
+        def_multiplier: T,
+        res_multiplier: T,
+        amplifier_multiplier: T
+    ) -> T {
         (((total_scaling_stat * motion_value) * base_dmg_multiplier) + additive_base_dmg_bonus)
             * avg_crit_multiplier
-            * (1.0 + total_dmg_bonus - dmg_reduction_target)
+            * (T::constant(1.0) + total_dmg_bonus - dmg_reduction_target)
             * def_multiplier
             * res_multiplier
             * amplifier_multiplier
             * instances
     }
 
-    pub fn transformative_reaction_bonus(elemental_mastery: f32, reaction_bonus: f32) -> f32 {
-        1.0 + (16.0 * elemental_mastery) / (2000.0 + elemental_mastery) + reaction_bonus
+    /// healing of an instance: (scaling stat x motion value + flat) x (1 + healing bonus + incoming healing bonus)
+    pub fn healing<T: Numeric>(total_scaling_stat: T, motion_value: T, flat: T, healing_bonus: T, incoming_healing_bonus: T) -> T {
+        (total_scaling_stat * motion_value + flat) * (T::constant(1.0) + healing_bonus + incoming_healing_bonus)
+    }
+
+    /// shield hp: (scaling stat x motion value + flat) x (1 + shield strength)
+    pub fn shield<T: Numeric>(total_scaling_stat: T, motion_value: T, flat: T, shield_strength: T) -> T {
+        (total_scaling_stat * motion_value + flat) * (T::constant(1.0) + shield_strength)
+    }
+
+    /// how much more damage a shield absorbs against an element
+    /// geo shields absorb 150% of all damage, other elemental shields absorb 250% of damage of their element
+    pub fn shield_absorption(shield_element: Element, damage_element: Element) -> f32 {
+        match shield_element {
+            Element::Geo => 1.5,
+            Element::Physical | Element::None => 1.0,
+            _ if shield_element == damage_element => 2.5,
+            _ => 1.0,
+        }
+    }
+
+    /// share of an enemy hit a character takes after their def: 1 - def / (def + 5 x enemy level + 500)
+    pub fn incoming_def_multiplier<T: Numeric>(enemy_level: i8, total_def: T) -> T {
+        assert!(enemy_level >= 1);
+        let enemy_factor = T::constant(5.0 * enemy_level as f32 + 500.0);
+        enemy_factor / (total_def + enemy_factor)
+    }
+
+    /// damage a character takes from an enemy hit with a given raw damage (enemy atk x motion value)
+    pub fn damage_taken<T: Numeric>(raw_damage: T, enemy_level: i8, total_def: T, resistance: T) -> T {
+        raw_damage * incoming_def_multiplier(enemy_level, total_def) * res_multiplier(resistance, T::constant(0.0))
     }
 
-    pub fn transformative_reaction_damage(
-        level_multiplier: f32,
-        reaction_base_multiplier: f32,
-        elemental_mastery: f32,
-        reaction_bonus: f32,
-        res_multiplier: f32,
-        instances: f32,
-    ) -> f32 {
+    /// raw damage a character can take before dying: (hp + shield hp) / (def multiplier x res multiplier)
+    pub fn effective_hp<T: Numeric>(total_hp: T, shield_hp: T, enemy_level: i8, total_def: T, resistance: T) -> T {
+        (total_hp + shield_hp) / (incoming_def_multiplier(enemy_level, total_def) * res_multiplier(resistance, T::constant(0.0)))
+    }
+
+    /// reaction level multiplier of a level 90 character
+    pub const REACTION_LEVEL_MULTIPLIER_90: f32 = 1446.8535;
+
+    /// flat damage added to the base damage of a hit that triggers aggravate or spread
+    pub fn catalyze_bonus<T: Numeric>(level_multiplier: T, catalyze_multiplier: T, elemental_mastery: T, reaction_bonus: T) -> T {
+        level_multiplier * catalyze_multiplier * (T::constant(1.0) + (T::constant(5.0) * elemental_mastery) / (T::constant(1200.0) + elemental_mastery) + reaction_bonus)
+    }
+
+    pub fn transformative_reaction_bonus<T: Numeric>(elemental_mastery: T, reaction_bonus: T) -> T {
+        T::constant(1.0) + (T::constant(16.0) * elemental_mastery) / (T::constant(2000.0) + elemental_mastery) + reaction_bonus
+    }
+
+    pub fn transformative_reaction_damage<T: Numeric>(
+        level_multiplier: T,
+        reaction_base_multiplier: T,
+        elemental_mastery: T,
+        reaction_bonus: T,
+        res_multiplier: T,
+        instances: T,
+    ) -> T {
         level_multiplier
             * reaction_base_multiplier
             * transformative_reaction_bonus(elemental_mastery, reaction_bonus)
@@ -146,6 +197,25 @@ pub mod formulas{
             assert!((def_multiplier(90, enemy_level, 100000.0, 0.0) - 0.904761905).abs() < 0.0001);
         }
     
+        #[test]
+        fn dual_gradients_match_closed_form() {
+            use crate::core::dual::DualStatTable;
+            let stats = StatTable::of(&[
+                (Stat::BaseATK, 800.0),
+                (Stat::ATKPercent, 0.5),
+                (Stat::CritRate, 0.6),
+                (Stat::CritDMG, 1.2),
+            ]);
+            let duals = DualStatTable::seed(&stats);
+            let atk = total_atk(&duals);
+            assert_eq!(atk.value, total_atk(&stats));
+            assert_eq!(atk.derivative(&Stat::ATKPercent), 800.0);
+            assert_eq!(atk.derivative(&Stat::FlatATK), 1.0);
+            let crit = avg_crit_multiplier(&duals);
+            assert!((crit.derivative(&Stat::CritRate) - 1.2).abs() < 0.0001);
+            assert!((crit.derivative(&Stat::CritDMG) - 0.6).abs() < 0.0001);
+        }
+
         #[test]
         fn test_res_multiplier() {
             let enemy_base_resistance = 0.1;
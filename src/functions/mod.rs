@@ -2,4 +2,7 @@ pub mod formulas;
 pub mod optimizers;
 pub mod factories;
 pub mod dmg_function;
-pub mod stat_factory;
\ No newline at end of file
+pub mod stat_factory;
+pub mod comparisons;
+pub mod crit_distribution;
+pub mod support_function;
\ No newline at end of file
@@ -1,49 +1,286 @@
 /// module of functions that provide algorithms to optimize statables
 pub mod optimizers{
     use crate::functions::stat_factory::StatFactory;
-    use crate::core::rotation::{Rotation, Operation};
+    use crate::core::rotation::Rotation;
     use crate::core::stattable::*;
     use crate::core::types::Stat;
     use crate::core::artifact::*;
     use crate::core::artifact_builder::*;
+    use crate::core::constraint::*;
+    use crate::core::calc_standard::CalcStandard;
+    use anyhow::{Context, Result, anyhow};
 
     pub type VariableMainstatType = (Stat,Stat,Stat);
     pub type SubstatDistribution = std::collections::HashMap<Stat, i8>;
 
 
-    ///TODO: Account for if meeting er recs with er sands or just er subs is better
+    /// finds the best 5 star kqmc artifact stats for a statable given a computable
+    /// main stats and substats are optimized jointly so er requirements can be met with er sands or er subs
+    /// returns an error if no main stat combo can meet the er requirements
     pub fn optimal_kqmc_5_artifacts_stats(
         stats: &StatTable,
         target: &Rotation,
         energy_recharge_requirements: f32,
-    ) -> StatTable {
-        let optimal_mainstats = global_kqmc_artifact_main_stat_optimizer(stats, target);
-        
-        let mut builder = ArtifactBuilder::new(
-            Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatHP}),
-            Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatATK}),
-            Some(ArtifactPiece{rarity:5, level:20, stat_type: optimal_mainstats.0}),
-            Some(ArtifactPiece{rarity:5, level:20, stat_type: optimal_mainstats.1}),
-            Some(ArtifactPiece{rarity:5, level:20, stat_type: optimal_mainstats.2})
-        );
-
-        let optimal_substats = gradient_5_star_kqmc_artifact_substat_optimizer(stats, target, 
-            Some(builder.flower.clone().unwrap()), 
-            Some(builder.feather.clone().unwrap()), 
-            Some(builder.sands.clone().unwrap()), 
-            Some(builder.goblet.clone().unwrap()), 
-            Some(builder.circlet.clone().unwrap()), 
-            energy_recharge_requirements);
-        
-        for (stat, count) in optimal_substats.iter() {
-            builder.roll(*stat, RollQuality::AVG, 5, *count);
+    ) -> Result<StatTable> {
+        optimal_kqmc_5_artifacts_stats_with_constraints(stats, target, &[Constraint::energy_recharge(energy_recharge_requirements)])
+    }
+
+    /// finds the best 5 star kqmc artifact stats for a statable given a computable that meet all constraints
+    pub fn optimal_kqmc_5_artifacts_stats_with_constraints(
+        stats: &StatTable,
+        target: &Rotation,
+        constraints: &[Constraint],
+    ) -> Result<StatTable> {
+        optimal_5_artifacts_stats_with_standard(stats, target, constraints, &CalcStandard::KQMC)
+    }
+
+    /// finds the best 5 star artifact stats under a calc standard for a statable given a computable that meet all constraints
+    pub fn optimal_5_artifacts_stats_with_standard(
+        stats: &StatTable,
+        target: &Rotation,
+        constraints: &[Constraint],
+        standard: &CalcStandard,
+    ) -> Result<StatTable> {
+        let (optimal_mainstats, optimal_substats) = global_artifact_optimizer_with_standard(stats, target, constraints, standard)?;
+        Ok(stats.chain(artifacts_5_star_stats(&optimal_mainstats, &optimal_substats, standard)))
+    }
+
+    /// finds best aritfact main stat combo and substat distribution for a statable given a computable
+    /// every sands/goblet/circlet combo of effective main stats (and er sands) is substat optimized under the constraints
+    /// and the combo with the highest final value is picked. combos that cannot meet the constraints are skipped
+    /// if no combo is feasible, the returned error wraps the [`ConstraintViolation`] of the last combo tried
+    pub fn global_kqmc_artifact_optimizer(
+        stats: &StatTable,
+        target: &Rotation,
+        constraints: &[Constraint],
+    ) -> Result<(VariableMainstatType, SubstatDistribution)> {
+        global_artifact_optimizer_with_standard(stats, target, constraints, &CalcStandard::KQMC)
+    }
+
+    /// finds best aritfact main stat combo and substat distribution under a calc standard
+    pub fn global_artifact_optimizer_with_standard(
+        stats: &StatTable,
+        target: &Rotation,
+        constraints: &[Constraint],
+        standard: &CalcStandard,
+    ) -> Result<(VariableMainstatType, SubstatDistribution)> {
+        check_standard(target, standard)?;
+        let mut best: Option<(VariableMainstatType, SubstatDistribution)> = None;
+        let mut best_value = f32::MIN;
+        let mut last_error = None;
+
+        for combo in candidate_kqmc_main_stat_combos(stats, &[target]) {
+            let substats = match optimize_5_star_substats(stats, &|s| target.evaluate(s), &combo, constraints, standard) {
+                Ok(substats) => substats,
+                Err(e) => { // constraints not reachable with this combo
+                    last_error = Some(e);
+                    continue;
+                },
+            };
+            let value = target.evaluate(&stats.chain(artifacts_5_star_stats(&combo, &substats, standard)));
+            if value > best_value {
+                best_value = value;
+                best = Some((combo, substats));
+            }
+        }
+
+        match best {
+            Some(best) => Ok(best),
+            None => Err(last_error
+                .unwrap_or_else(|| anyhow!("no main stat combo to optimize"))
+                .context("constraints cannot be met with any main stat combo")),
+        }
+    }
+
+    /// a build on the pareto front of a multi objective optimization
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ParetoPoint {
+        pub mainstats: VariableMainstatType,
+        pub substats: SubstatDistribution,
+        pub values: Vec<f32>, // value of each objective, in the order objectives were given
+    }
+
+    impl ParetoPoint {
+        /// returns true if this point is at least as good in every objective and better in one
+        pub fn dominates(&self, other: &ParetoPoint) -> bool {
+            self.values.iter().zip(other.values.iter()).all(|(a, b)| a >= b)
+                && self.values.iter().zip(other.values.iter()).any(|(a, b)| a > b)
         }
-            let sum = stats.chain(builder.build());
-        sum
+    }
+
+    /// finds the pareto front of 5 star kqmc artifact builds for multiple objectives (eg: healing, shield strength and personal damage)
+    /// every candidate main stat combo is substat optimized for a grid of objective weightings with `weight_steps` divisions,
+    /// objectives are normalized by their value with fixed subs only so weights are relative
+    /// builds that are dominated by another build are removed
+    pub fn pareto_kqmc_artifact_optimizer(
+        stats: &StatTable,
+        objectives: &[&Rotation],
+        constraints: &[Constraint],
+        weight_steps: usize,
+    ) -> Result<Vec<ParetoPoint>> {
+        pareto_artifact_optimizer_with_standard(stats, objectives, constraints, weight_steps, &CalcStandard::KQMC)
+    }
+
+    /// errors if a rotation's damage was built for another enemy or character level than the standard optimized under
+    fn check_standard(target: &Rotation, standard: &CalcStandard) -> Result<()> {
+        if !target.standard().same_damage_assumptions(standard) {
+            return Err(anyhow!("rotation is evaluated under a different calc standard than the optimizer"));
+        }
+        Ok(())
+    }
+
+    /// finds the pareto front of 5 star artifact builds for multiple objectives under a calc standard
+    pub fn pareto_artifact_optimizer_with_standard(
+        stats: &StatTable,
+        objectives: &[&Rotation],
+        constraints: &[Constraint],
+        weight_steps: usize,
+        standard: &CalcStandard,
+    ) -> Result<Vec<ParetoPoint>> {
+        if objectives.is_empty() {
+            return Err(anyhow!("pareto optimization needs at least one objective"));
+        }
+        for objective in objectives {
+            check_standard(objective, standard)?;
+        }
+        let weight_grid = simplex_weights(objectives.len(), weight_steps.max(1));
+
+        let mut candidates: Vec<ParetoPoint> = Vec::new();
+        let mut last_error = None;
+        for combo in candidate_kqmc_main_stat_combos(stats, objectives) {
+            let fixed_subs = stats.chain(ArtifactBuilder::all_5_star_with_standard(combo.0, combo.1, combo.2, standard).build());
+            let scales: Vec<f32> = objectives.iter()
+                .map(|target| target.evaluate(&fixed_subs))
+                .map(|value| if value > 0.0 { value } else { 1.0 })
+                .collect();
+
+            for weights in &weight_grid {
+                let objective = |s: &StatTable| objectives.iter()
+                    .zip(weights.iter().zip(scales.iter()))
+                    .map(|(target, (weight, scale))| weight * target.evaluate(s) / scale)
+                    .sum::<f32>();
+                let substats = match optimize_5_star_substats(stats, &objective, &combo, constraints, standard) {
+                    Ok(substats) => substats,
+                    Err(e) => { // constraints not reachable with this combo
+                        last_error = Some(e);
+                        break;
+                    },
+                };
+                if candidates.iter().any(|x| x.mainstats == combo && x.substats == substats) {
+                    continue;
+                }
+                let total = stats.chain(artifacts_5_star_stats(&combo, &substats, standard));
+                let values = objectives.iter().map(|target| target.evaluate(&total)).collect();
+                candidates.push(ParetoPoint { mainstats: combo, substats, values });
+            }
+        }
+
+        if candidates.is_empty() {
+            return Err(last_error
+                .unwrap_or_else(|| anyhow!("no main stat combo to optimize"))
+                .context("constraints cannot be met with any main stat combo"));
+        }
+
+        let front = candidates.iter()
+            .filter(|x| !candidates.iter().any(|y| y.dominates(x)))
+            .cloned()
+            .collect();
+        Ok(front)
+    }
+
+    /// picks the point of a pareto front with the highest weighted sum of objective values
+    /// each objective is normalized by its best value on the front so weights express the trade-off between objectives
+    pub fn select_weighted_pareto_point<'a>(front: &'a [ParetoPoint], weights: &[f32]) -> Option<&'a ParetoPoint> {
+        let maxes: Vec<f32> = (0..weights.len())
+            .map(|i| front.iter().map(|x| x.values.get(i).cloned().unwrap_or(0.0)).fold(0.0, f32::max))
+            .map(|max| if max > 0.0 { max } else { 1.0 })
+            .collect();
+        let score = |point: &ParetoPoint| -> f32 {
+            point.values.iter()
+                .zip(weights.iter().zip(maxes.iter()))
+                .map(|(value, (weight, max))| weight * value / max)
+                .sum()
+        };
+        front.iter().max_by(|a, b| score(a).total_cmp(&score(b)))
+    }
+
+    /// all weightings of n objectives that sum to 1 in increments of 1/steps
+    fn simplex_weights(n: usize, steps: usize) -> Vec<Vec<f32>> {
+        fn compositions(n: usize, total: usize) -> Vec<Vec<usize>> {
+            if n == 1 {
+                return vec![vec![total]];
+            }
+            (0..=total)
+                .flat_map(|i| compositions(n - 1, total - i).into_iter().map(move |mut rest| { rest.insert(0, i); rest }))
+                .collect()
+        }
+        compositions(n, steps).into_iter()
+            .map(|c| c.into_iter().map(|x| x as f32 / steps as f32).collect())
+            .collect()
+    }
+
+    /// main stat combos worth searching for a set of targets
+    /// only main stats that increase at least one target are considered, plus er sands since it can be the cheapest way to meet er requirements
+    fn candidate_kqmc_main_stat_combos(stats: &StatTable, targets: &[&Rotation]) -> Vec<VariableMainstatType> {
+        // heuristic: only consider main stats that actually increase target value
+        let pool: std::collections::HashSet<Stat> = POSSIBLE_SANDS_STATS.iter()
+            .chain(POSSIBLE_GOBLET_STATS.iter())
+            .chain(POSSIBLE_CIRCLE_STATS.iter())
+            .cloned()
+            .collect();
+        let slopes = std::collections::HashMap::from_iter(pool.iter().map(|x|(*x, 1.0)));
+        let effective_set: std::collections::HashSet<Stat> = targets.iter()
+            .flat_map(|target| relu_heuristic(stats, target, &slopes))
+            .collect();
+        let effective_subset = |possible: &[Stat]| -> Vec<Stat> {
+            let subset: Vec<Stat> = possible.iter().filter(|x| effective_set.contains(x)).cloned().collect();
+            if subset.is_empty() { possible.to_vec() } else { subset }
+        };
+
+        let mut sands_subset = effective_subset(POSSIBLE_SANDS_STATS);
+        if !sands_subset.contains(&Stat::EnergyRecharge) {
+            sands_subset.push(Stat::EnergyRecharge);
+        }
+        let goblet_subset = effective_subset(POSSIBLE_GOBLET_STATS);
+        let circlet_subset = effective_subset(POSSIBLE_CIRCLE_STATS);
+
+        let mut combos = Vec::new();
+        for &sands in &sands_subset {
+            for &goblet in &goblet_subset {
+                for &circlet in &circlet_subset {
+                    combos.push((sands, goblet, circlet));
+                }
+            }
+        }
+        combos
+    }
+
+    /// runs the substat optimizer for 5 star artifacts with given main stats under a calc standard
+    fn optimize_5_star_substats(
+        stats: &StatTable,
+        objective: &dyn Fn(&StatTable) -> f32,
+        mainstats: &VariableMainstatType,
+        constraints: &[Constraint],
+        standard: &CalcStandard,
+    ) -> Result<SubstatDistribution> {
+        let builder = ArtifactBuilder::all_5_star_with_standard(mainstats.0, mainstats.1, mainstats.2, standard);
+        gradient_substat_search(stats, objective, builder, constraints, standard)
+    }
+
+    /// compiles a 5 star artifact set with given main stats and substat rolls of the standard's quality into a stattable
+    pub fn artifacts_5_star_stats(mainstats: &VariableMainstatType, substats: &SubstatDistribution, standard: &CalcStandard) -> StatTable {
+        let mut builder = ArtifactBuilder::all_5_star_with_standard(mainstats.0, mainstats.1, mainstats.2, standard);
+        builder.rolls.clear();
+        for (stat, count) in substats.iter() {
+            builder.roll(*stat, standard.roll_quality.clone(), 5, *count);
+        }
+        builder.build()
     }
 
     /// finds best aritfact main stat combo for a statable given a computable
     /// eg: best mains for a character for a particular rotation
+    /// ignores energy recharge requirements, use global_kqmc_artifact_optimizer with constraints instead
+    #[deprecated(note = "ignores energy recharge requirements, use global_kqmc_artifact_optimizer")]
     pub fn global_kqmc_artifact_main_stat_optimizer(
         //stats: Box<Statable>,
         //target: Box<Computable>
@@ -99,6 +336,8 @@ pub mod optimizers{
 
 
     /// finds best substat distrubtion 
+    /// returns an error if the energy recharge requirements cannot be met with substats
+    #[allow(clippy::too_many_arguments)]
     pub fn gradient_5_star_kqmc_artifact_substat_optimizer(
         stats: &StatTable,
         target: &Rotation,
@@ -108,47 +347,118 @@ pub mod optimizers{
         goblet: Option<ArtifactPiece>,
         circlet: Option<ArtifactPiece>,
         energy_recharge_requirements: f32,
-    ) -> SubstatDistribution {
-        let mut builder = ArtifactBuilder::kqmc(flower, feather, sands, goblet, circlet);
-        
-        /*
-         * TOOD: instead if having energy requirement as a f32, give a constraint/requirement argument which is a data structure that stores multiple "constraints" which are functions that take a statable/statable and returns a boolean if if the requirement for that statable is meet or not, and based on it 
-         */
-        
-        //meet er reqs from subs
-        while {
-            let combined_stats = stats.chain(builder.build());
-            combined_stats.get(&Stat::EnergyRecharge)
-        } < energy_recharge_requirements {
-            if builder.rolls_left() <= 0 || builder.rolls_left_for_given(&Stat::EnergyRecharge, RollQuality::AVG, 5) <= 0 {
-                panic!("Energy Recharge requirements cannot be met with substats alone");
+    ) -> Result<SubstatDistribution> {
+        try_gradient_5_star_kqmc_artifact_substat_optimizer(stats, target, flower, feather, sands, goblet, circlet, &[Constraint::energy_recharge(energy_recharge_requirements)])
+    }
+
+    /// finds best substat distrubtion that meets all constraints
+    /// returns an error wrapping the violated [`ConstraintViolation`] if the constraints cannot be met with substats
+    #[allow(clippy::too_many_arguments)]
+    pub fn try_gradient_5_star_kqmc_artifact_substat_optimizer(
+        stats: &StatTable,
+        target: &Rotation,
+        flower: Option<ArtifactPiece>,
+        feather: Option<ArtifactPiece>,
+        sands: Option<ArtifactPiece>,
+        goblet: Option<ArtifactPiece>,
+        circlet: Option<ArtifactPiece>,
+        constraints: &[Constraint],
+    ) -> Result<SubstatDistribution> {
+        if [&flower, &feather, &sands, &goblet, &circlet].iter().all(|piece| piece.is_none()) {
+            return Err(anyhow!("no artifacts to roll substats on"));
+        }
+        let builder = ArtifactBuilder::kqmc(flower, feather, sands, goblet, circlet);
+        gradient_substat_search(stats, &|s| target.evaluate(s), builder, constraints, &CalcStandard::KQMC)
+    }
+
+    /// substat and number of its rolls that meet a constraint with the fewest rolls, none if no single substat can
+    fn fewest_rolls_to_satisfy(stats: &StatTable, builder: &mut ArtifactBuilder, constraint: &Constraint, standard: &CalcStandard) -> Option<(Stat, i8)> {
+        let mut best: Option<(Stat, i8)> = None;
+        for &substat in POSSIBLE_SUB_STATS {
+            let max_rolls = builder.rolls_left().min(builder.rolls_left_for_given(&substat, standard.roll_quality.clone(), 5));
+            for rolls in 1..=max_rolls {
+                if best.is_some_and(|(_, fewest)| rolls >= fewest) {
+                    break;
+                }
+                builder.roll(substat, standard.roll_quality.clone(), 5, rolls);
+                let satisfied = constraint.is_satisfied(&stats.chain(builder.build()));
+                builder.unroll(substat, standard.roll_quality.clone(), 5, rolls);
+                if satisfied {
+                    best = Some((substat, rolls));
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    /// greedily rolls the remaining substats of a builder to maximize an objective while meeting all constraints
+    fn gradient_substat_search(
+        stats: &StatTable,
+        objective: &dyn Fn(&StatTable) -> f32,
+        mut builder: ArtifactBuilder,
+        constraints: &[Constraint],
+        standard: &CalcStandard,
+    ) -> Result<SubstatDistribution> {
+        //meet constraints from subs: greedily roll the sub that closes the gap of the first violated constraint the most
+        while let Some(violated) = constraints.iter().find(|c| !c.is_satisfied(&stats.chain(builder.build()))) {
+            let deficit = violated.deficit(&stats.chain(builder.build()));
+            let mut best_sub = Stat::None;
+            let mut best_deficit = deficit;
+            for &substat in POSSIBLE_SUB_STATS {
+                if builder.rolls_left() <= 0 || builder.rolls_left_for_given(&substat, standard.roll_quality.clone(), 5) <= 0 {
+                    continue;
+                }
+                builder.roll(substat, standard.roll_quality.clone(), 5, 1);
+                let new_deficit = violated.deficit(&stats.chain(builder.build()));
+                builder.unroll(substat, standard.roll_quality.clone(), 5, 1);
+                if new_deficit < best_deficit {
+                    best_deficit = new_deficit;
+                    best_sub = substat;
+                }
+            }
+            if best_sub != Stat::None {
+                builder.roll(best_sub, standard.roll_quality.clone(), 5, 1);
+                continue;
+            }
+            //predicates have no distance to close, so roll the substat that meets it in the fewest rolls
+            let fewest_rolls = if violated.derived_value(stats).is_none() {
+                fewest_rolls_to_satisfy(stats, &mut builder, violated, standard)
+            } else {
+                None
+            };
+            match fewest_rolls {
+                Some((substat, rolls)) => builder.roll(substat, standard.roll_quality.clone(), 5, rolls),
+                None => return Err(violated.check(&stats.chain(builder.build())).unwrap_err())
+                    .context("constraint cannot be met with substats alone"),
             }
-            builder.roll(Stat::EnergyRecharge, RollQuality::AVG, 5, 1);
         }
         //println!("builder: {:?}", builder.constraints);
-        let mut possible_subs_to_roll: std::collections::HashSet<Stat> = POSSIBLE_SUB_STATS.iter().cloned().collect();
+        let mut improving = true;
         
         //gradient search loop
-        while builder.rolls_left() > 0 && !possible_subs_to_roll.is_empty() {
+        while builder.rolls_left() > 0 && improving {
             let mut best_sub = Stat::None;
             let mut best_dpr = 0.0;
             
             // Try each possible substat and find the one that gives the best DPR
-            for &substat in &possible_subs_to_roll {
+            // substats are tried in a fixed order so ties go to the first one
+            for &substat in POSSIBLE_SUB_STATS {
                 // Check if we can roll this substat
-                if builder.current_rolls_for_given(&substat, RollQuality::AVG, 5) < builder.substat_constraint(&substat, 5) {
+                if builder.current_rolls_for_given(&substat, standard.roll_quality.clone(), 5) < builder.substat_constraint(&substat, 5) {
                     // Temporarily roll this substat
-                    builder.roll(substat, RollQuality::AVG, 5, 1);
+                    builder.roll(substat, standard.roll_quality.clone(), 5, 1);
                     
                     // Calculate DPR with this roll
                     let combined_stats = stats.chain(builder.build());
-                    let dpr = target.evaluate(&combined_stats);
+                    let dpr = objective(&combined_stats);
+                    let feasible = check_all(constraints, &combined_stats).is_ok();
                     
                     // Unroll to restore previous state
-                    builder.unroll(substat, RollQuality::AVG, 5, 1);
+                    builder.unroll(substat, standard.roll_quality.clone(), 5, 1);
                     
-                    // Update best if this is better
-                    if dpr > best_dpr {
+                    // Update best if this is better and does not break a constraint
+                    if feasible && dpr > best_dpr {
                         best_dpr = dpr;
                         best_sub = substat;
                     }
@@ -156,10 +466,10 @@ pub mod optimizers{
             }
             
             if best_dpr == 0.0 {
-                possible_subs_to_roll.clear();
+                improving = false;
             } else {
                 //println!("substat: {}, dpr: {}", best_sub, best_dpr);
-                builder.roll(best_sub, RollQuality::AVG, 5, 1);
+                builder.roll(best_sub, standard.roll_quality.clone(), 5, 1);
             }
         }
 
@@ -169,15 +479,19 @@ pub mod optimizers{
             distribution.insert(*stat, *count);
         }
         
-        distribution
+        Ok(distribution)
     }
 
     /// computes graident of a statable based on slopes of stats
+    /// uses exact gradients if the rotation is differentiable, otherwise finite differences over the slopes
     pub fn stat_gradients(
         base: &StatTable,
         target: &Rotation,
         slopes: &std::collections::HashMap<Stat, f32>,
     ) -> std::collections::HashMap<Stat, f32> {
+        if let Some(exact) = target.gradients(base) {
+            return slopes.keys().map(|stat| (*stat, exact[stat])).collect();
+        }
         let mut gradients = std::collections::HashMap::new();
         for (stat, delta) in slopes {
             let direction = StatTable::of(&[(*stat, *delta)]);
@@ -246,6 +560,7 @@ pub mod optimizers{
     #[cfg(test)] mod tests {
         use super::*;
         use crate::functions::dmg_function::DMGFunction;
+        use crate::functions::formulas::formulas;
         use crate::core::types::*;
 
         #[test] fn test_gradients() {
@@ -286,8 +601,42 @@ pub mod optimizers{
             assert!(grad.get(&Stat::ElementalMastery).unwrap() == &0.0);
         }
 
+        #[test] fn test_exact_gradients_match_finite_differences() {
+            let stats = StatTable::of(&[
+                (Stat::BaseATK, 800.0),
+                (Stat::ATKPercent, 0.5),
+                (Stat::FlatATK, 300.0),
+                (Stat::CritRate, 0.6),
+                (Stat::CritDMG, 1.2),
+                (Stat::ElementalMastery, 100.0),
+                (Stat::PyroDMGBonus, 0.466),
+            ]);
+            let exact = crate::rotation! {
+                ("vaporize", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::Forward, 2.0, 1.0, None),
+            }.unwrap();
+            let finite = Rotation::of(vec![
+                (String::from("vaporize"), Box::new(|x| DMGFunction::calculate_damage(
+                    Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::Forward, 1.0, 2.0, x, None,
+                ))),
+            ]);
+            assert!(exact.is_differentiable());
+            assert!(!finite.is_differentiable());
+
+            let slopes = std::collections::HashMap::from_iter(POSSIBLE_SUB_STATS.iter().map(|x|
+                (*x, StatFactory::get_sub_stat_value(5, *x).unwrap())
+            ));
+            let exact_grad = stat_gradients(&stats, &exact, &slopes);
+            let finite_grad = stat_gradients(&stats, &finite, &slopes);
+            for stat in POSSIBLE_SUB_STATS {
+                let (a, b) = (exact_grad[stat], finite_grad[stat]);
+                assert!((a - b).abs() <= 0.02 * a.abs().max(1.0), "{stat}: exact {a}, finite {b}");
+            }
+            assert!(exact_grad[&Stat::ElementalMastery] > 0.0);
+            assert_eq!(exact_grad[&Stat::HPPercent], 0.0);
+        }
+
 
-        #[test] fn test_global_kqmc_artifact_main_stat_optimizer() {
+        #[test] #[allow(deprecated)] fn test_global_kqmc_artifact_main_stat_optimizer() {
             let stats = StatTable::of(&[
                 (Stat::BaseATK, 100.0),
                 (Stat::ATKPercent, 0.5),
@@ -296,22 +645,236 @@ pub mod optimizers{
                 (Stat::CritDMG, 0.5),
             ]);
             let mut target = Rotation::new();
-            let atk1: Operation = Box::new(|x| DMGFunction::calculate_damage(
-                Element::Pyro, 
-                DamageType::Normal, 
-                BaseScaling::ATK, 
-                Amplifier::None, 
-                1.0, 1.0, 
-                x, 
-                None,
-            ));
-            target.add(String::from("atk1"), atk1);
+            target.add_damage("atk1", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
 
             let result = global_kqmc_artifact_main_stat_optimizer(&stats, &target);
             println!("{:?}", result);
             assert_eq!(result, (Stat::ATKPercent, Stat::PyroDMGBonus, Stat::CritRate));
         }
 
+        #[test] fn test_global_kqmc_artifact_optimizer_uses_er_sands_when_subs_cannot_meet_er() {
+            let stats = StatTable::of(&[
+                (Stat::BaseATK, 844.85),
+                (Stat::FlatATK, 100.0),
+                (Stat::CritRate, 0.05),
+                (Stat::CritDMG, 0.5),
+                (Stat::EnergyRecharge, 1.0),
+            ]);
+            let mut target = Rotation::new();
+            target.add_damage("atk1", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
+
+            // er subs alone cap out around 166% er, so only an er sands can reach 180%
+            let (mains, _) = global_kqmc_artifact_optimizer(&stats, &target, &[Constraint::energy_recharge(1.8)]).unwrap();
+            assert_eq!(mains.0, Stat::EnergyRecharge);
+
+            let optimized = optimal_kqmc_5_artifacts_stats(&stats, &target, 1.8).unwrap();
+            assert!(optimized.get(&Stat::EnergyRecharge) >= 1.8);
+        }
+
+        #[test] fn test_global_kqmc_artifact_optimizer_uses_er_subs_when_cheaper() {
+            let stats = StatTable::of(&[
+                (Stat::BaseATK, 844.85),
+                (Stat::FlatATK, 100.0),
+                (Stat::CritRate, 0.05),
+                (Stat::CritDMG, 0.5),
+                (Stat::EnergyRecharge, 1.0),
+            ]);
+            let mut target = Rotation::new();
+            target.add_damage("atk1", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
+
+            let (mains, substats) = global_kqmc_artifact_optimizer(&stats, &target, &[Constraint::energy_recharge(1.2)]).unwrap();
+            assert_eq!(mains, (Stat::ATKPercent, Stat::PyroDMGBonus, Stat::CritRate));
+            assert!(substats.get(&Stat::EnergyRecharge).unwrap() > &2);
+        }
+
+        #[test] fn test_global_kqmc_artifact_optimizer_unreachable_er_is_error() {
+            let stats = StatTable::of(&[
+                (Stat::BaseATK, 844.85),
+                (Stat::EnergyRecharge, 1.0),
+            ]);
+            let mut target = Rotation::new();
+            target.add_damage("atk1", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
+
+            let err = global_kqmc_artifact_optimizer(&stats, &target, &[Constraint::energy_recharge(3.0)]).unwrap_err();
+            let violation = err.downcast_ref::<ConstraintViolation>().unwrap();
+            assert_eq!(violation.constraint, Constraint::energy_recharge(3.0).name());
+        }
+
+        #[test] fn test_substat_optimizer_respects_max_and_min_constraints() {
+            let stats = StatTable::of(&[
+                (Stat::BaseATK, 844.85),
+                (Stat::BaseHP, 15000.0),
+                (Stat::FlatATK, 311.0),
+                (Stat::CritRate, 0.7),
+                (Stat::CritDMG, 0.5),
+                (Stat::EnergyRecharge, 1.0),
+            ]);
+            let mut target = Rotation::new();
+            target.add_damage("atk1", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
+
+            let constraints = vec![
+                // 20% crit rate buff in burst window should not overcap
+                Constraint::max("burst crit rate <= 100%", |s| s.get(&Stat::CritRate) + 0.2, 1.0),
+                Constraint::min("total hp >= 25000", formulas::total_hp, 25000.0),
+            ];
+            let res = try_gradient_5_star_kqmc_artifact_substat_optimizer(
+                &stats, 
+                &target, 
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatHP}),
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatATK}),
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::ATKPercent}),
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::PyroDMGBonus}),
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::CritDMG}),
+                &constraints,
+            ).unwrap();
+
+            let mut builder = ArtifactBuilder::kqm_all_5_star(Stat::ATKPercent, Stat::PyroDMGBonus, Stat::CritDMG);
+            builder.rolls.clear();
+            for (stat, count) in res.iter() {
+                builder.roll(*stat, RollQuality::AVG, 5, *count);
+            }
+            let total = stats.chain(builder.build());
+            assert!(check_all(&constraints, &total).is_ok());
+            assert!(res.get(&Stat::HPPercent).unwrap() > &2);
+        }
+
+        #[test] fn test_substat_optimizer_meets_predicates_over_several_rolls() {
+            let stats = StatTable::of(&[
+                (Stat::BaseATK, 844.85),
+                (Stat::BaseHP, 15000.0),
+                (Stat::CritRate, 0.05),
+                (Stat::CritDMG, 0.5),
+                (Stat::EnergyRecharge, 1.0),
+            ]);
+            let mut target = Rotation::new();
+            target.add_damage("atk1", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
+
+            // no single roll gets there, the predicate needs several hp% rolls
+            let constraints = vec![Constraint::predicate("hp passive", |s| formulas::total_hp(s) >= 25000.0)];
+            let res = try_gradient_5_star_kqmc_artifact_substat_optimizer(
+                &stats,
+                &target,
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatHP}),
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatATK}),
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::ATKPercent}),
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::PyroDMGBonus}),
+                Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::CritDMG}),
+                &constraints,
+            ).unwrap();
+            assert!(res.get(&Stat::HPPercent).unwrap() > &3);
+        }
+
+        #[test] fn test_artifact_optimizer_with_stricter_standard() {
+            let stats = StatTable::of(&[
+                (Stat::BaseATK, 800.0),
+                (Stat::CritRate, 0.05),
+                (Stat::CritDMG, 0.5),
+                (Stat::EnergyRecharge, 1.0),
+            ]);
+            let target = crate::rotation! {
+                ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+            }.unwrap();
+            let strict = CalcStandard { fluid_rolls_per_substat_per_piece: 1, roll_penalty_per_piece: 2, ..CalcStandard::KQMC };
+            let (kqmc_mains, kqmc_subs) = global_kqmc_artifact_optimizer(&stats, &target, &[]).unwrap();
+            let (strict_mains, strict_subs) = global_artifact_optimizer_with_standard(&stats, &target, &[], &strict).unwrap();
+            assert_eq!(kqmc_subs.values().sum::<i8>(), 40);
+            assert_eq!(strict_subs.values().sum::<i8>(), 35);
+            assert!(strict_subs.values().all(|x| *x <= 7));
+
+            let kqmc_value = target.evaluate(&stats.chain(artifacts_5_star_stats(&kqmc_mains, &kqmc_subs, &CalcStandard::KQMC)));
+            let strict_value = target.evaluate(&stats.chain(artifacts_5_star_stats(&strict_mains, &strict_subs, &strict)));
+            assert!(strict_value < kqmc_value);
+        }
+
+        #[test] fn test_optimizer_rejects_rotation_of_another_enemy() {
+            let stats = StatTable::of(&[(Stat::BaseATK, 800.0), (Stat::EnergyRecharge, 1.0)]);
+            let tanky = CalcStandard { enemy_resistance: 0.7, ..CalcStandard::KQMC };
+            let kqmc_target = crate::rotation! {
+                ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+            }.unwrap();
+            assert!(global_artifact_optimizer_with_standard(&stats, &kqmc_target, &[], &tanky).is_err());
+            let tanky_target = crate::rotation! {
+                standard: tanky;
+                ("skill", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+            }.unwrap();
+            assert!(global_artifact_optimizer_with_standard(&stats, &tanky_target, &[], &tanky).is_ok());
+        }
+
+        #[test] fn test_healer_artifact_optimizer() {
+            let stats = StatTable::of(&[
+                (Stat::BaseHP, 12000.0),
+                (Stat::BaseATK, 700.0),
+                (Stat::CritRate, 0.05),
+                (Stat::CritDMG, 0.5),
+                (Stat::EnergyRecharge, 1.0),
+            ]);
+            let target = crate::rotation! {
+                (heal "burst", BaseScaling::HP, 0.1, 1000.0, 6.0, None),
+                (shield "skill", Element::Geo, Element::Pyro, BaseScaling::HP, 0.2, 2000.0, None),
+            }.unwrap();
+            let (mains, subs) = global_kqmc_artifact_optimizer(&stats, &target, &[]).unwrap();
+            assert_eq!(mains, (Stat::HPPercent, Stat::HPPercent, Stat::HealingBonus));
+            assert!(subs[&Stat::HPPercent] > 2);
+            assert!(!subs.contains_key(&Stat::CritRate) || subs[&Stat::CritRate] == 2);
+        }
+
+        #[test] fn test_effective_hp_artifact_optimizer() {
+            let stats = StatTable::of(&[
+                (Stat::BaseHP, 12000.0),
+                (Stat::BaseDEF, 800.0),
+                (Stat::BaseATK, 700.0),
+                (Stat::CritRate, 0.05),
+                (Stat::CritDMG, 0.5),
+                (Stat::EnergyRecharge, 1.0),
+            ]);
+            let target = crate::rotation! {
+                (ehp "survival", Element::Pyro, 100, None),
+            }.unwrap();
+            let (mains, subs) = global_kqmc_artifact_optimizer(&stats, &target, &[]).unwrap();
+            // hp and def multiply, so the circlet goes to def once hp is stacked
+            assert_eq!((mains.0, mains.1), (Stat::HPPercent, Stat::HPPercent));
+            assert!(matches!(mains.2, Stat::HPPercent | Stat::DEFPercent));
+            assert!(!subs.contains_key(&Stat::CritRate) || subs[&Stat::CritRate] == 2);
+        }
+
+        #[test] fn test_simplex_weights() {
+            assert_eq!(simplex_weights(1, 4), vec![vec![1.0]]);
+            assert_eq!(simplex_weights(2, 2), vec![vec![0.0, 1.0], vec![0.5, 0.5], vec![1.0, 0.0]]);
+            let grid = simplex_weights(3, 4);
+            assert_eq!(grid.len(), 15);
+            assert!(grid.iter().all(|w| (w.iter().sum::<f32>() - 1.0).abs() < 0.0001));
+        }
+
+        #[test] fn test_pareto_kqmc_artifact_optimizer() {
+            let stats = StatTable::of(&[
+                (Stat::BaseATK, 844.85),
+                (Stat::BaseHP, 15000.0),
+                (Stat::CritRate, 0.05),
+                (Stat::CritDMG, 0.5),
+                (Stat::EnergyRecharge, 1.0),
+            ]);
+            let atk_target = crate::rotation! {
+                ("atk", Element::Pyro, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None),
+            }.unwrap();
+            let hp_target = crate::rotation! {
+                ("hp", Element::Hydro, DamageType::Skill, BaseScaling::HP, Amplifier::None, 0.1, 1.0, None),
+            }.unwrap();
+
+            let front = pareto_kqmc_artifact_optimizer(&stats, &[&atk_target, &hp_target], &[Constraint::energy_recharge(1.1)], 2).unwrap();
+            assert!(front.len() > 1);
+            for a in &front {
+                assert_eq!(a.values.len(), 2);
+                assert!(front.iter().all(|b| !b.dominates(a)));
+            }
+
+            let best_atk = select_weighted_pareto_point(&front, &[1.0, 0.0]).unwrap();
+            let best_hp = select_weighted_pareto_point(&front, &[0.0, 1.0]).unwrap();
+            assert_eq!(best_atk.mainstats.0, Stat::ATKPercent);
+            assert_eq!(best_hp.mainstats.0, Stat::HPPercent);
+            assert!(best_atk.values[0] > best_hp.values[0]);
+            assert!(best_hp.values[1] > best_atk.values[1]);
+        }
+
         #[test] fn test_gradient_5_star_kqmc_artifact_substat_optimizer() {
             let stats = StatTable::of(&[
                 (Stat::BaseATK, 844.85),
@@ -322,16 +885,7 @@ pub mod optimizers{
                 (Stat::EnergyRecharge, 1.0),
             ]);
             let mut target = Rotation::new();
-            let atk1: Operation = Box::new(|x| DMGFunction::calculate_damage(
-                Element::Pyro, 
-                DamageType::Normal, 
-                BaseScaling::ATK, 
-                Amplifier::None, 
-                1.0, 1.0, 
-                x, 
-                None,
-            ));
-            target.add(String::from("atk1"), atk1);
+            target.add_damage("atk1", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
 
             let flower = Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatHP});
             let feather = Some(ArtifactPiece{rarity:5, level:20, stat_type: Stat::FlatATK});
@@ -348,7 +902,7 @@ pub mod optimizers{
                 goblet, 
                 circlet, 
                 1.0 // energy recharge requirement
-            );
+            ).unwrap();
             
             //println!("Substat distribution: {:?}", res);
 
@@ -366,19 +920,10 @@ pub mod optimizers{
             assert_eq!(res.get(&Stat::EnergyRecharge), Some(&(0+2)));
         }
 
-        #[test] fn test_accept_artifact_optimizer() {
+        #[test] #[allow(deprecated)] fn test_accept_artifact_optimizer() {
             // Equivalent to Java test: AcceptArtifactOptimizer
             let mut target = Rotation::new();
-            let atk1: Operation = Box::new(|x| DMGFunction::calculate_damage(
-                Element::Pyro, 
-                DamageType::Normal, 
-                BaseScaling::ATK, 
-                Amplifier::None, 
-                1.0, 1.0, 
-                x, 
-                None,
-            ));
-            target.add(String::from("t"), atk1);
+            target.add_damage("t", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
 
             // Create character stats similar to Hu Tao
             let mut character_stats = StatTable::of(&[
@@ -411,16 +956,7 @@ pub mod optimizers{
 
             // Create a new target for the second evaluation
             let mut target2 = Rotation::new();
-            let atk2: Operation = Box::new(|x| DMGFunction::calculate_damage(
-                Element::Pyro, 
-                DamageType::Normal, 
-                BaseScaling::ATK, 
-                Amplifier::None, 
-                1.0, 1.0, 
-                x, 
-                None,
-            ));
-            target2.add(String::from("t"), atk2);
+            target2.add_damage("t", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
 
             let after = target2.evaluate(&optimized_stats);
             
@@ -430,16 +966,7 @@ pub mod optimizers{
         #[test] fn test_artifact_optimizer_not_enough_er_case() {
             // Equivalent to Java test: ArtifactOptimizerNotEnoughERCase
             let mut target = Rotation::new();
-            let atk1: Operation = Box::new(|x| DMGFunction::calculate_damage(
-                Element::Pyro, 
-                DamageType::Normal, 
-                BaseScaling::ATK, 
-                Amplifier::None, 
-                1.0, 1.0, 
-                x, 
-                None,
-            ));
-            target.add(String::from("t"), atk1);
+            target.add_damage("t", Element::Pyro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
 
             // Create character stats similar to Hu Tao (without weapon)
             let character_stats = StatTable::of(&[
@@ -451,32 +978,21 @@ pub mod optimizers{
                 (Stat::EnergyRecharge, 1.0),
             ]);
 
-            // This should panic because 2.0 ER requirement cannot be met with substats alone
-            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-                gradient_5_star_kqmc_artifact_substat_optimizer(
-                    &character_stats,
-                    &target,
-                    None, None, None, None, None,
-                    2.0 // energy recharge requirement that cannot be met
-                )
-            }));
+            // This should fail because 2.0 ER requirement cannot be met with substats alone
+            let result = gradient_5_star_kqmc_artifact_substat_optimizer(
+                &character_stats,
+                &target,
+                None, None, None, None, None,
+                2.0 // energy recharge requirement that cannot be met
+            );
 
-            assert!(result.is_err(), "Should panic when ER requirements cannot be met");
+            assert!(result.is_err(), "Should fail when ER requirements cannot be met");
         }
 
         #[test] fn test_artifact_optimizer_enough_er_case() {
             // Equivalent to Java test: ArtifactOptimizerEnoughERCase
             let mut target = Rotation::new();
-            let atk1: Operation = Box::new(|x| DMGFunction::calculate_damage(
-                Element::Electro, 
-                DamageType::Normal, 
-                BaseScaling::ATK, 
-                Amplifier::None, 
-                1.0, 1.0, 
-                x, 
-                None,
-            ));
-            target.add(String::from("t"), atk1);
+            target.add_damage("t", Element::Electro, DamageType::Normal, BaseScaling::ATK, Amplifier::None, 1.0, 1.0, None);
 
             // Create character stats similar to Raiden with The Catch
             let mut character_stats = StatTable::of(&[
@@ -509,7 +1025,7 @@ pub mod optimizers{
                 goblet,
                 circlet,
                 2.0 // energy recharge requirement
-            );
+            ).unwrap();
 
             // Calculate total energy recharge after optimization
             let total_er = 1.32 + 0.459 + 0.518 + 0.0; // Base + Weapon + Sands + Substat rolls
@@ -1,5 +1,6 @@
 use crate::{core::stattable::StatTable, standardize::flatten_str};
-use crate::core::types::Stat;
+use crate::core::types::{Stat, TalentType};
+use crate::core::calc_standard::CalcStandard;
 use std::str::FromStr;
 use serde::Deserialize;
 use serde::Serialize;
@@ -8,6 +9,7 @@ use std::fmt;
 use std::error::Error;
 use anyhow::{Result, anyhow};
 use crate::data::irminsul_adaptor::*;
+use crate::energy::specification::{CharacterEnergySpec, CharacterEnergySpecList};
 use once_cell::sync::Lazy;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -35,21 +37,51 @@ static ARTIFACT_SUB_STAT_DATA: Lazy<AllArtifactSubStatJson> = Lazy::new(|| {
     serde_json::from_str(json_str).expect("Failed to parse artifactSubStats.json")
 });
 
+static ENERGY_DATA: Lazy<CharacterEnergySpecList> = Lazy::new(|| {
+    let json_str = include_str!("../../data/energy.json");
+    serde_json::from_str(json_str).expect("Failed to parse energy.json")
+});
+
 /// factory for creating stattables
 pub struct StatFactory{}
 impl StatFactory{
 
     /// reads cached character base stats of a given name at a specific level as a stattable 
     pub fn get_character_base_stats(name: &str, level: i8) -> Result<StatTable> {
+        StatFactory::get_character_base_stats_with_standard(name, level, &CalcStandard::KQMC)
+    }
+
+    /// reads cached character base stats with the crit and energy recharge defaults of a calc standard
+    pub fn get_character_base_stats_with_standard(name: &str, level: i8, standard: &CalcStandard) -> Result<StatTable> {
         let stat_list = StatFactory::find_match(CHARACTER_DATA.data.clone(), name)?;
         
         stat_list.base_stats.iter()
             .filter(|s| s.lvl.starts_with(&format!("{level}/")))
             .last()
-            .map(|x| x.to_stattable())
+            .map(|x| x.to_stattable_with_standard(standard))
             .ok_or_else(|| anyhow!("no character base stats found for level {level}"))?
     }
 
+    /// reads the cached motion value of a character's talent hit at a talent level
+    /// eg: ("ayaka", TalentType::NormalAttack, "1-Hit DMG", 10) -> 0.9039
+    pub fn get_talent_multiplier(name: &str, talent: TalentType, hit: &str, level: i8) -> Result<f32> {
+        let character = StatFactory::find_match(CHARACTER_DATA.data.clone(), name)?;
+        let talent_json = character.talents.iter()
+            .find(|t| t.type_ == talent.as_str())
+            .ok_or_else(|| anyhow!("no {} talent found for {}", talent.as_str(), character.name))?;
+
+        talent_json.attributes.iter()
+            .flatten()
+            .find(|a| flatten_str(&a.hit) == flatten_str(hit))
+            .ok_or_else(|| anyhow!("no {} hit found for {}", hit, talent_json.name))?
+            .motion_value(level)
+    }
+
+    /// reads the cached energy spec of a character (particles per skill cast, burst cost)
+    pub fn get_character_energy_spec(name: &str) -> Result<CharacterEnergySpec> {
+        StatFactory::find_match(ENERGY_DATA.data.clone(), name)
+    }
+
     /// fetches character base stats from Irminsul API asynchronously
     #[cfg(not(target_arch = "wasm32"))]
     pub async fn fetch_character_base_stats(name: &str) -> Result<StatTable> {
@@ -82,6 +114,28 @@ impl StatFactory{
             .ok_or_else(|| anyhow!("no weapon base stats found for level {level}"))?
     }
 
+    /// reads cached base stats of every weapon of a category (eg: sword, claymore) at a specific level
+    /// weapons without base stats for that level are skipped
+    pub fn get_weapon_category_base_stats(category: &str, level: i8) -> Result<Vec<(WeaponJSON, StatTable)>> {
+        let weapons = WEAPON_DATA.data.iter()
+            .filter(|w| flatten_str(&w.category) == flatten_str(category));
+
+        let mut res = Vec::new();
+        for weapon in weapons {
+            let stats = weapon.base_stats.iter()
+                .rfind(|s| s.level.starts_with(&format!("{level}/")))
+                .map(|x| x.to_stattable());
+            if let Some(stats) = stats {
+                res.push((weapon.clone(), stats?));
+            }
+        }
+
+        if res.is_empty() {
+            return Err(anyhow!("no {category} weapons found for level {level}"));
+        }
+        Ok(res)
+    }
+
     /// fetches weapon stats from Irminsul API asynchronously
     #[cfg(not(target_arch = "wasm32"))]
     pub async fn fetch_weapon_stats(name: &str) -> Result<StatTable> {
@@ -202,6 +256,9 @@ impl StatFactory{
     pub fn fuzzy_match(needled: &str, haystack: &str) -> bool {
         let needle = flatten_str(needled);
         let haystack = flatten_str(haystack);
+        if needle.is_empty() {
+            return false;
+        }
         let mut nidx = 0;
         for c in haystack.chars() {
             if c == needle.chars().nth(nidx).unwrap() {
@@ -218,6 +275,7 @@ impl StatFactory{
 
 #[cfg(test)] mod tests {
     use super::*;
+    use crate::assert_aprx;
 
     #[test] fn get_character_expected() {
         let amber = StatFactory::get_character_base_stats("Amber", 90);
@@ -236,8 +294,16 @@ impl StatFactory{
         assert_eq!(amber.get(&Stat::BaseDEF), 166.99);
     }
 
+    #[test] fn get_talent_multiplier_works() {
+        assert_aprx!(StatFactory::get_talent_multiplier("ayaka", TalentType::NormalAttack, "1-Hit DMG", 10).unwrap(), 0.9039, 0.0001);
+        assert_aprx!(StatFactory::get_talent_multiplier("ayaka", TalentType::Burst, "Cutting DMG (%)", 1).unwrap(), 1.123, 0.0001);
+        assert!(StatFactory::get_talent_multiplier("ayaka", TalentType::Burst, "Cutting DMG", 16).is_err());
+        assert!(StatFactory::get_talent_multiplier("ayaka", TalentType::Skill, "not a hit", 1).is_err());
+    }
+
     #[test] fn fuzzy_match_test() {
         assert!(StatFactory::fuzzy_match("ayaka","Kamisato Ayaka"));
+        assert!(!StatFactory::fuzzy_match("","Kamisato Ayaka"));
     }
 
     #[test] fn get_chara_fuzzy() {
@@ -257,6 +323,15 @@ impl StatFactory{
         assert_eq!(w.get(&Stat::CritRate), 0.11);
     }
 
+    #[test] fn get_weapon_category_base_stats_works() {
+        let swords = StatFactory::get_weapon_category_base_stats("sword", 90).unwrap();
+        assert!(swords.iter().all(|(w, _)| w.category == "sword"));
+        let (_, mistsplitter) = swords.iter().find(|(w, _)| w.name == "Mistsplitter Reforged").unwrap();
+        assert_eq!(mistsplitter, &StatFactory::get_weapon_base_stats("mistsplitter", 90).unwrap());
+
+        assert!(StatFactory::get_weapon_category_base_stats("not a category", 90).is_err());
+    }
+
     #[test] fn test_get_mainstat_value() {
         assert_eq!(StatFactory::get_main_stat_value(5, 20, &Stat::FlatATK).unwrap(), 311.0);
         assert_eq!(StatFactory::get_main_stat_value(1, 0, &Stat::FlatATK).unwrap(), 8.0);
@@ -0,0 +1,196 @@
+use crate::core::stattable::*;
+use crate::core::types::*;
+use crate::core::dual::Numeric;
+use crate::functions::formulas::*;
+
+/// higher level functions to compute healing from statables
+pub struct HealFunction {}
+impl HealFunction {
+
+    /// healing of an action on a character, generic over any stat lookup so it can be differentiated
+    /// incoming healing bonus is the stat of the character receiving the healing
+    pub fn calculate_healing<S: StatLookup>(
+        scaling: BaseScaling,
+        instances: f32,
+        motion_value: f32,
+        flat: f32,
+        character: &S,
+        buffs: Option<&StatTable>
+    ) -> S::Value {
+        let total = Buffed { stats: character, buffs };
+        let c = S::Value::constant;
+        formulas::healing(
+            scaling_stat(scaling, &total),
+            c(motion_value),
+            c(flat),
+            total.get(&Stat::HealingBonus),
+            total.get(&Stat::IncomingHealingBonus),
+        ) * c(instances)
+    }
+}
+
+/// higher level functions to compute shields from statables
+pub struct ShieldFunction {}
+impl ShieldFunction {
+
+    /// shield hp of an action
+    pub fn calculate_shield<S: StatLookup>(
+        scaling: BaseScaling,
+        motion_value: f32,
+        flat: f32,
+        character: &S,
+        buffs: Option<&StatTable>
+    ) -> S::Value {
+        let total = Buffed { stats: character, buffs };
+        let c = S::Value::constant;
+        formulas::shield(scaling_stat(scaling, &total), c(motion_value), c(flat), total.get(&Stat::ShieldStrength))
+    }
+
+    /// damage of an element a shield absorbs before breaking
+    #[allow(clippy::too_many_arguments)]
+    pub fn calculate_effective_shield<S: StatLookup>(
+        shield_element: Element,
+        damage_element: Element,
+        scaling: BaseScaling,
+        motion_value: f32,
+        flat: f32,
+        character: &S,
+        buffs: Option<&StatTable>
+    ) -> S::Value {
+        Self::calculate_shield(scaling, motion_value, flat, character, buffs)
+            * S::Value::constant(formulas::shield_absorption(shield_element, damage_element))
+    }
+}
+
+/// a shield a character holds, used for effective hp
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShieldSource {
+    pub element: Element,
+    pub scaling: BaseScaling,
+    pub motion_value: f32,
+    pub flat: f32,
+}
+
+/// higher level functions to compute the damage a character takes from statables
+pub struct DefenseFunction {}
+impl DefenseFunction {
+
+    /// damage a character takes from an enemy hit of a given element
+    /// eg: an enemy with 1000 atk hitting with a 150% attack is calculate_damage_taken(Element::Pyro, 100, 1000.0, 1.5, ...)
+    pub fn calculate_damage_taken<S: StatLookup>(
+        element: Element,
+        enemy_level: i8,
+        enemy_atk: f32,
+        motion_value: f32,
+        character: &S,
+        buffs: Option<&StatTable>
+    ) -> S::Value {
+        let total = Buffed { stats: character, buffs };
+        formulas::damage_taken(
+            S::Value::constant(enemy_atk * motion_value),
+            enemy_level,
+            formulas::total_def(&total),
+            resistance(element, &total),
+        )
+    }
+
+    /// raw enemy damage of an element a character can take before dying, counting hp, def, res and an optional shield
+    pub fn calculate_effective_hp<S: StatLookup>(
+        element: Element,
+        enemy_level: i8,
+        shield: Option<&ShieldSource>,
+        character: &S,
+        buffs: Option<&StatTable>
+    ) -> S::Value {
+        let total = Buffed { stats: character, buffs };
+        let shield_hp = match shield {
+            Some(x) => ShieldFunction::calculate_effective_shield(x.element, element, x.scaling, x.motion_value, x.flat, character, buffs),
+            None => S::Value::constant(0.0),
+        };
+        formulas::effective_hp(
+            formulas::total_hp(&total),
+            shield_hp,
+            enemy_level,
+            formulas::total_def(&total),
+            resistance(element, &total),
+        )
+    }
+}
+
+/// elemental or physical res of a character against an element
+fn resistance<S: StatLookup>(element: Element, total: &S) -> S::Value {
+    let stat = match element {
+        Element::Pyro => Stat::PyroRES,
+        Element::Hydro => Stat::HydroRES,
+        Element::Electro => Stat::ElectroRES,
+        Element::Anemo => Stat::AnemoRES,
+        Element::Geo => Stat::GeoRES,
+        Element::Dendro => Stat::DendroRES,
+        Element::Cryo => Stat::CryoRES,
+        Element::Physical => Stat::PhysicalRES,
+        Element::None => return S::Value::constant(0.0),
+    };
+    total.get(&stat)
+}
+
+/// flat scaling multiplies the motion value by 1, same as damage
+fn scaling_stat<S: StatLookup>(scaling: BaseScaling, total: &S) -> S::Value {
+    match scaling {
+        BaseScaling::ATK => formulas::total_atk(total),
+        BaseScaling::DEF => formulas::total_def(total),
+        BaseScaling::HP => formulas::total_hp(total),
+        BaseScaling::FlatDamage => S::Value::constant(1.0),
+    }
+}
+
+#[cfg(test)] mod tests {
+    use super::*;
+    use crate::assert_aprx;
+
+    #[test] fn healing_formula() {
+        let stats = StatTable::of(&[
+            (Stat::BaseHP, 10000.0),
+            (Stat::HPPercent, 0.5),
+            (Stat::HealingBonus, 0.2),
+            (Stat::IncomingHealingBonus, 0.1),
+        ]);
+        // (15000 * 10% + 1000) * 1.3 * 2 instances
+        assert_aprx!(HealFunction::calculate_healing(BaseScaling::HP, 2.0, 0.1, 1000.0, &stats, None), 6500.0, 0.1);
+    }
+
+    #[test] fn flat_scaling_uses_the_motion_value() {
+        let stats = StatTable::of(&[(Stat::BaseHP, 10000.0), (Stat::ShieldStrength, 0.25)]);
+        // (500 + 1000) * 1 instance
+        assert_aprx!(HealFunction::calculate_healing(BaseScaling::FlatDamage, 1.0, 500.0, 1000.0, &stats, None), 1500.0, 0.1);
+        assert_aprx!(ShieldFunction::calculate_shield(BaseScaling::FlatDamage, 500.0, 1000.0, &stats, None), 1875.0, 0.1);
+    }
+
+    #[test] fn shield_absorption_depends_on_element() {
+        let stats = StatTable::of(&[(Stat::BaseHP, 10000.0), (Stat::ShieldStrength, 0.25)]);
+        let shield = ShieldFunction::calculate_shield(BaseScaling::HP, 0.2, 2000.0, &stats, None);
+        assert_aprx!(shield, 5000.0, 0.1);
+        let absorbed = |shield_element, damage_element| ShieldFunction::calculate_effective_shield(shield_element, damage_element, BaseScaling::HP, 0.2, 2000.0, &stats, None);
+        assert_aprx!(absorbed(Element::Geo, Element::Pyro), 7500.0, 0.1);
+        assert_aprx!(absorbed(Element::Hydro, Element::Hydro), 12500.0, 0.1);
+        assert_aprx!(absorbed(Element::Hydro, Element::Pyro), 5000.0, 0.1);
+        assert_aprx!(absorbed(Element::Physical, Element::Physical), 5000.0, 0.1);
+    }
+
+    #[test] fn damage_taken_formula() {
+        let stats = StatTable::of(&[(Stat::BaseDEF, 800.0), (Stat::DEFPercent, 0.25), (Stat::PyroRES, 0.2)]);
+        // 2000 raw damage from a level 100 enemy: def multiplier 1000 / (1000 + 1000), res multiplier 0.8
+        assert_aprx!(DefenseFunction::calculate_damage_taken(Element::Pyro, 100, 1000.0, 2.0, &stats, None), 800.0, 0.01);
+        // no physical res
+        assert_aprx!(DefenseFunction::calculate_damage_taken(Element::Physical, 100, 1000.0, 2.0, &stats, None), 1000.0, 0.01);
+    }
+
+    #[test] fn effective_hp_counts_shields() {
+        let stats = StatTable::of(&[(Stat::BaseHP, 10000.0), (Stat::BaseDEF, 1000.0), (Stat::ShieldStrength, 0.25)]);
+        let ehp = DefenseFunction::calculate_effective_hp(Element::Pyro, 100, None, &stats, None);
+        assert_aprx!(ehp, 20000.0, 0.1);
+        let shield = ShieldSource { element: Element::Geo, scaling: BaseScaling::HP, motion_value: 0.2, flat: 2000.0 };
+        let shielded = DefenseFunction::calculate_effective_hp(Element::Pyro, 100, Some(&shield), &stats, None);
+        // 10000 hp + 7500 shield hp against pyro, halved by def
+        assert_aprx!(shielded, 35000.0, 0.1);
+    }
+}
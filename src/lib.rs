@@ -9,4 +9,5 @@ pub use utils::*;
 pub mod data;
 pub use data::*;
 pub mod energy;
-pub use energy::*;
\ No newline at end of file
+pub use energy::*;
+pub mod ffi;
@@ -1,3 +1,4 @@
 pub mod testing;
 pub mod percentage;
-pub mod standardize;
\ No newline at end of file
+pub mod standardize;
+pub mod rng;
\ No newline at end of file
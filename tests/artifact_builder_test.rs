@@ -1,4 +1,5 @@
 use aminus::core::artifact_builder::*;
+use aminus::core::calc_standard::CalcStandard;
 use aminus::core::stattable::StatTable;
 use aminus::core::types::Stat;
 use aminus::functions::stat_factory::StatFactory;
@@ -612,4 +613,27 @@ use aminus::assert_aprx;
 //     assert_eq!(bob.substat_constraint(&Stat::CritDMG, 4), 10);
 //     assert_eq!(bob.substat_constraint(&Stat::FlatDEF, 4), 10);
 //     assert_eq!(bob.substat_constraint(&Stat::ATKPercent, 4), 10);
-// }
\ No newline at end of file
+// }
+
+#[test] fn kqmc_is_the_kqmc_standard() {
+    let kqmc = ArtifactBuilder::kqm_all_5_star(Stat::ATKPercent, Stat::PyroDMGBonus, Stat::CritRate);
+    let standard = ArtifactBuilder::all_5_star_with_standard(Stat::ATKPercent, Stat::PyroDMGBonus, Stat::CritRate, &CalcStandard::KQMC);
+    assert_eq!(kqmc.build(), standard.build());
+    assert_eq!(kqmc.rolls_left(), standard.rolls_left());
+    assert_eq!(kqmc.substat_constraint(&Stat::CritDMG, 5), standard.substat_constraint(&Stat::CritDMG, 5));
+}
+
+#[test] fn custom_standard_artifact_builder() {
+    let strict = CalcStandard::from_json(r#"{"fluid_rolls_per_substat_per_piece": 1, "roll_penalty_per_piece": 2, "roll_quality": "MAX"}"#).unwrap();
+    let bob = ArtifactBuilder::all_5_star_with_standard(Stat::ATKPercent, Stat::PyroDMGBonus, Stat::CritRate, &strict);
+
+    // 5 pieces of 5 rolls each, minus 2 per piece
+    assert_eq!(bob.rolls_left(), 15);
+    // 1 fluid roll per piece on 5 pieces + 2 fixed rolls
+    assert_eq!(bob.substat_constraint(&Stat::CritDMG, 5), 7);
+    // crit rate circlet cannot roll crit rate
+    assert_eq!(bob.substat_constraint(&Stat::CritRate, 5), 6);
+    assert_eq!(bob.current_rolls_for_given(&Stat::CritDMG, RollQuality::MAX, 5), 2);
+    assert_aprx!(bob.sub_stats().get(&Stat::CritDMG), StatFactory::get_sub_stat_value(5, Stat::CritDMG).unwrap() * 2.0, 0.0001);
+}
+
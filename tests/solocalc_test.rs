@@ -115,7 +115,7 @@ fn default_cryo_q_formula(name: &str, x: &StatTable, multi: f32, num: i8, option
         default_cryo_q_formula("burstcuts", &ayaka, 1.91, 19, None),
         default_cryo_q_formula("burstexplosion", &ayaka, 2.86, 1, None),
     ]);
-    let ayaka = optimizers::optimal_kqmc_5_artifacts_stats(&ayaka, &rotation, 1.30);
+    let ayaka = optimizers::optimal_kqmc_5_artifacts_stats(&ayaka, &rotation, 1.30).unwrap();
     let dps = rotation.evaluate(&ayaka)/21.;
     
     println!("dps: {}", dps);
@@ -140,8 +140,8 @@ fn default_cryo_q_formula(name: &str, x: &StatTable, multi: f32, num: i8, option
         ("skill", Element::Cryo, DamageType::Skill, BaseScaling::ATK, Amplifier::None, 4.07, 2.0, None),
         ("burstcuts", Element::Cryo, DamageType::Burst, BaseScaling::ATK, Amplifier::None, 1.91, 19.0, None),
         ("burstexplosion", Element::Cryo, DamageType::Burst, BaseScaling::ATK, Amplifier::None, 2.86, 1.0, None),
-    };
-    let ayaka = optimizers::optimal_kqmc_5_artifacts_stats(&ayaka, &rotation, 1.30);
+    }.unwrap();
+    let ayaka = optimizers::optimal_kqmc_5_artifacts_stats(&ayaka, &rotation, 1.30).unwrap();
     let dps = rotation.evaluate(&ayaka)/21.;
     
     println!("dps: {}", dps);